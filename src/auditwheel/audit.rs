@@ -2,7 +2,7 @@ use super::musllinux::{find_musl_libc, get_musl_version};
 use super::policy::{Policy, MANYLINUX_POLICIES, MUSLLINUX_POLICIES};
 use crate::auditwheel::{find_external_libs, PlatformTag};
 use crate::compile::BuildArtifact;
-use crate::target::Target;
+use crate::target::{Arch, Target};
 use anyhow::{bail, Context, Result};
 use fs_err::File;
 use goblin::elf::{sym::STT_FUNC, Elf};
@@ -35,20 +35,20 @@ pub enum AuditWheelError {
     #[error(
     "Your library is not {0} compliant because it links the following forbidden libraries: {1:?}",
     )]
-    LinksForbiddenLibrariesError(Policy, Vec<String>),
+    LinksForbiddenLibrariesError(Box<Policy>, Vec<String>),
     /// The elf file isn't manylinux/musllinux compatible. Contains the list of offending
     /// libraries.
     #[error(
     "Your library is not {0} compliant because of the presence of too-recent versioned symbols: {1:?}. Consider building in a manylinux docker container",
     )]
-    VersionedSymbolTooNewError(Policy, Vec<String>),
+    VersionedSymbolTooNewError(Box<Policy>, Vec<String>),
     /// The elf file isn't manylinux/musllinux compatible. Contains the list of offending
     /// libraries with blacked-list symbols.
     #[error("Your library is not {0} compliant because it depends on black-listed symbols: {1:?}")]
-    BlackListedSymbolsError(Policy, Vec<String>),
+    BlackListedSymbolsError(Box<Policy>, Vec<String>),
     /// The elf file isn't manylinux/musllinux compaible. Contains unsupported architecture
     #[error("Your library is not {0} compliant because it has unsupported architecture: {1}")]
-    UnsupportedArchitecture(Policy, String),
+    UnsupportedArchitecture(Box<Policy>, String),
     /// This platform tag isn't defined by auditwheel yet
     #[error("{0} compatibility policy is not defined by auditwheel yet, pass `--skip-auditwheel` to proceed anyway")]
     UndefinedPolicy(String),
@@ -119,7 +119,7 @@ fn policy_is_satisfied(
     versioned_libraries: &[VersionedLibrary],
 ) -> Result<(), AuditWheelError> {
     let arch_versions = &policy.symbol_versions.get(arch).ok_or_else(|| {
-        AuditWheelError::UnsupportedArchitecture(policy.clone(), arch.to_string())
+        AuditWheelError::UnsupportedArchitecture(Box::new(policy.clone()), arch.to_string())
     })?;
     let mut offending_libs = HashSet::new();
     let mut offending_versioned_syms = HashSet::new();
@@ -200,14 +200,14 @@ fn policy_is_satisfied(
             .map(|(lib, syms)| format!("{}: {}", lib, syms.join(", ")))
             .collect();
         return Err(AuditWheelError::BlackListedSymbolsError(
-            policy.clone(),
+            Box::new(policy.clone()),
             offenders,
         ));
     }
     // Check for too-recent versioned symbols
     if !offending_versioned_syms.is_empty() {
         return Err(AuditWheelError::VersionedSymbolTooNewError(
-            policy.clone(),
+            Box::new(policy.clone()),
             offending_versioned_syms.into_iter().collect(),
         ));
     }
@@ -220,12 +220,42 @@ fn policy_is_satisfied(
             Err(AuditWheelError::LinksLibPythonError(lib.clone()))
         }
         offenders => Err(AuditWheelError::LinksForbiddenLibrariesError(
-            policy.clone(),
+            Box::new(policy.clone()),
             offenders.to_vec(),
         )),
     }
 }
 
+/// Tries the policies from highest to lowest priority and returns the first one that is
+/// satisfied, whether external libraries need to be grafted to satisfy it and why each of
+/// the policies with a higher priority was rejected
+fn find_highest_policy(
+    platform_policies: &[Policy],
+    elf: &Elf,
+    arch: &str,
+    deps: &[String],
+    versioned_libraries: &[VersionedLibrary],
+) -> Result<(Option<Policy>, bool, Vec<AuditWheelError>), AuditWheelError> {
+    let mut rejected = Vec::new();
+    for policy in platform_policies.iter() {
+        let result = policy_is_satisfied(policy, elf, arch, deps, versioned_libraries);
+        match result {
+            Ok(_) => return Ok((Some(policy.clone()), false, rejected)),
+            Err(AuditWheelError::LinksForbiddenLibrariesError(..)) => {
+                return Ok((Some(policy.clone()), true, rejected))
+            }
+            Err(err @ AuditWheelError::VersionedSymbolTooNewError(..))
+            | Err(err @ AuditWheelError::BlackListedSymbolsError(..)) => rejected.push(err),
+            // UnsupportedArchitecture happens when trying 2010 with aarch64
+            Err(AuditWheelError::UnsupportedArchitecture(..)) => continue,
+            // If there was an error parsing the symbols or libpython was linked,
+            // we error no matter what the requested policy was
+            Err(err) => return Err(err),
+        }
+    }
+    Ok((None, false, rejected))
+}
+
 fn get_default_platform_policies() -> Vec<Policy> {
     if let Ok(Some(musl_libc)) = find_musl_libc() {
         if let Ok(Some((major, minor))) = get_musl_version(&musl_libc) {
@@ -242,6 +272,24 @@ fn get_default_platform_policies() -> Vec<Policy> {
     MANYLINUX_POLICIES.clone()
 }
 
+/// Replaces the built-in policies with the custom policies of the same name, and if `add_new`
+/// is set also adds the other custom policies, keeping them sorted by priority
+fn with_custom_policies(
+    mut policies: Vec<Policy>,
+    custom_policies: &[Policy],
+    add_new: bool,
+) -> Vec<Policy> {
+    for custom_policy in custom_policies {
+        match policies.iter_mut().find(|p| p.name == custom_policy.name) {
+            Some(policy) => *policy = custom_policy.clone(),
+            None if add_new => policies.push(custom_policy.clone()),
+            None => {}
+        }
+    }
+    policies.sort_by_key(|policy| -policy.priority);
+    policies
+}
+
 /// An reimplementation of auditwheel, which checks elf files for
 /// manylinux/musllinux compliance.
 ///
@@ -252,13 +300,17 @@ fn get_default_platform_policies() -> Vec<Policy> {
 /// If a specific manylinux/musllinux version is given, compliance is checked and a warning printed if
 /// a higher version would be possible.
 ///
+/// The `custom_policies` are checked alongside the built-in ones, replacing those of the same
+/// name, unless the tag of a custom policy is given which is then checked exclusively.
+///
 /// Does nothing for `platform_tag` set to `Off`/`Linux` or non-linux platforms.
 pub fn auditwheel_rs(
     artifact: &BuildArtifact,
     target: &Target,
-    platform_tag: Option<PlatformTag>,
+    platform_tag: Option<&PlatformTag>,
+    custom_policies: &[Policy],
 ) -> Result<(Policy, bool), AuditWheelError> {
-    if !target.is_linux() || platform_tag == Some(PlatformTag::Linux) {
+    if !target.is_linux() || platform_tag == Some(&PlatformTag::Linux) {
         return Ok((Policy::default(), false));
     }
     let path = &artifact.path;
@@ -274,20 +326,29 @@ pub fn auditwheel_rs(
 
     // Find the highest possible policy, if any
     let platform_policies = match platform_tag {
-        Some(PlatformTag::Manylinux { .. }) => MANYLINUX_POLICIES.clone(),
-        Some(PlatformTag::Musllinux { x, y }) => MUSLLINUX_POLICIES
-            .clone()
-            .into_iter()
-            .filter(|policy| {
-                policy.name == "linux" || policy.name == format!("musllinux_{}_{}", x, y)
-            })
-            .map(|mut policy| {
-                policy.fixup_musl_libc_so_name(target.target_arch());
-                policy
-            })
-            .collect(),
+        Some(PlatformTag::Manylinux { .. }) => {
+            with_custom_policies(MANYLINUX_POLICIES.clone(), custom_policies, false)
+        }
+        Some(PlatformTag::Musllinux { x, y }) => {
+            with_custom_policies(MUSLLINUX_POLICIES.clone(), custom_policies, false)
+                .into_iter()
+                .filter(|policy| {
+                    policy.name == "linux" || policy.name == format!("musllinux_{}_{}", x, y)
+                })
+                .map(|mut policy| {
+                    policy.fixup_musl_libc_so_name(target.target_arch());
+                    policy
+                })
+                .collect()
+        }
+        Some(PlatformTag::Custom(name)) => {
+            let policy = Policy::from_name_with_custom(name, custom_policies)
+                .ok_or_else(|| AuditWheelError::UndefinedPolicy(name.to_string()))?;
+            vec![policy, Policy::default()]
+        }
         None => {
-            let mut policies = get_default_platform_policies();
+            let mut policies =
+                with_custom_policies(get_default_platform_policies(), custom_policies, true);
             for policy in &mut policies {
                 policy.fixup_musl_libc_so_name(target.target_arch());
             }
@@ -295,34 +356,13 @@ pub fn auditwheel_rs(
         }
         Some(PlatformTag::Linux) => unreachable!(),
     };
-    let mut highest_policy = None;
-    let mut should_repair = false;
-    for policy in platform_policies.iter() {
-        let result = policy_is_satisfied(policy, &elf, &arch, &deps, &versioned_libraries);
-        match result {
-            Ok(_) => {
-                highest_policy = Some(policy.clone());
-                should_repair = false;
-                break;
-            }
-            Err(AuditWheelError::LinksForbiddenLibrariesError(..)) => {
-                highest_policy = Some(policy.clone());
-                should_repair = true;
-                break;
-            }
-            Err(AuditWheelError::VersionedSymbolTooNewError(..))
-            | Err(AuditWheelError::BlackListedSymbolsError(..))
-            // UnsupportedArchitecture happens when trying 2010 with aarch64
-            | Err(AuditWheelError::UnsupportedArchitecture(..)) => continue,
-            // If there was an error parsing the symbols or libpython was linked,
-            // we error no matter what the requested policy was
-            Err(err) => return Err(err),
-        }
-    }
+    let (highest_policy, mut should_repair, _) =
+        find_highest_policy(&platform_policies, &elf, &arch, &deps, &versioned_libraries)?;
 
     let policy = if let Some(platform_tag) = platform_tag {
         let tag = platform_tag.to_string();
-        let mut policy = Policy::from_name(&tag).ok_or(AuditWheelError::UndefinedPolicy(tag))?;
+        let mut policy = Policy::from_name_with_custom(&tag, custom_policies)
+            .ok_or(AuditWheelError::UndefinedPolicy(tag))?;
         policy.fixup_musl_libc_so_name(target.target_arch());
 
         if let Some(highest_policy) = highest_policy {
@@ -416,11 +456,12 @@ pub fn get_sysroot_path(target: &Target) -> Result<PathBuf> {
 /// we need to add to repair it
 pub fn get_policy_and_libs(
     artifact: &BuildArtifact,
-    platform_tag: Option<PlatformTag>,
+    platform_tag: Option<&PlatformTag>,
     target: &Target,
+    custom_policies: &[Policy],
 ) -> Result<(Policy, Vec<Library>)> {
-    let (policy, should_repair) =
-        auditwheel_rs(artifact, target, platform_tag).with_context(|| {
+    let (policy, should_repair) = auditwheel_rs(artifact, target, platform_tag, custom_policies)
+        .with_context(|| {
             if let Some(platform_tag) = platform_tag {
                 format!("Error ensuring {} compliance", platform_tag)
             } else {
@@ -443,6 +484,70 @@ pub fn get_policy_and_libs(
     Ok((policy, external_libs))
 }
 
+/// The result of [audit_elf]
+#[derive(Debug)]
+pub struct ElfAudit {
+    /// The highest priority policy the elf file satisfies, possibly only after grafting
+    /// `external_libs` into the wheel, or `linux` if nothing else matches
+    pub policy: Policy,
+    /// The libraries outside of the whitelist of `policy`
+    pub external_libs: Vec<Library>,
+    /// Why each of the policies with a higher priority than `policy` was rejected
+    pub rejected: Vec<AuditWheelError>,
+}
+
+/// Checks an elf file that wasn't necessarily built by us against all manylinux or musllinux
+/// policies and reports the highest one that is satisfied
+///
+/// Unlike [auditwheel_rs], this depends neither on the host's libc nor on a rust toolchain,
+/// so it can be used on wheels built elsewhere. `custom_policies` replace the built-in policies
+/// of the same name and, for glibc elf files, are checked alongside them.
+pub fn audit_elf(
+    path: &Path,
+    arch: Arch,
+    musl: bool,
+    ld_paths: Vec<PathBuf>,
+    custom_policies: &[Policy],
+) -> Result<ElfAudit, AuditWheelError> {
+    let buffer = fs_err::read(path).map_err(AuditWheelError::IoError)?;
+    let elf = Elf::parse(&buffer).map_err(AuditWheelError::GoblinError)?;
+    let deps: Vec<String> = elf.libraries.iter().map(ToString::to_string).collect();
+    let versioned_libraries = find_versioned_libraries(&elf);
+
+    // Custom policies count as glibc policies unless they replace a musllinux policy
+    let mut platform_policies = if musl {
+        with_custom_policies(MUSLLINUX_POLICIES.clone(), custom_policies, false)
+    } else {
+        let custom_policies: Vec<_> = custom_policies
+            .iter()
+            .filter(|policy| !policy.name.starts_with("musllinux"))
+            .cloned()
+            .collect();
+        with_custom_policies(MANYLINUX_POLICIES.clone(), &custom_policies, true)
+    };
+    for policy in &mut platform_policies {
+        policy.fixup_musl_libc_so_name(arch);
+    }
+    let (highest_policy, should_repair, rejected) = find_highest_policy(
+        &platform_policies,
+        &elf,
+        &arch.to_string(),
+        &deps,
+        &versioned_libraries,
+    )?;
+    let policy = highest_policy.unwrap_or_default();
+    let external_libs = if should_repair {
+        find_external_libs(path, &policy, PathBuf::from("/"), ld_paths)?
+    } else {
+        Vec::new()
+    };
+    Ok(ElfAudit {
+        policy,
+        external_libs,
+        rejected,
+    })
+}
+
 pub fn relpath(to: &Path, from: &Path) -> PathBuf {
     let mut suffix_pos = 0;
     for (f, t) in from.components().zip(to.components()) {
@@ -466,7 +571,8 @@ pub fn relpath(to: &Path, from: &Path) -> PathBuf {
 
 #[cfg(test)]
 mod test {
-    use crate::auditwheel::audit::relpath;
+    use crate::auditwheel::audit::{relpath, with_custom_policies};
+    use crate::auditwheel::{Policy, MANYLINUX_POLICIES};
     use pretty_assertions::assert_eq;
     use std::path::Path;
 
@@ -484,4 +590,31 @@ mod test {
             assert_eq!(result, Path::new(expected));
         }
     }
+
+    #[test]
+    fn test_with_custom_policies() {
+        let mut stricter = Policy::from_name("manylinux_2_17").unwrap();
+        stricter.lib_whitelist.clear();
+        let acme = Policy {
+            name: "acme_2_28".to_string(),
+            priority: 70,
+            ..Policy::default()
+        };
+        let custom = [stricter, acme];
+
+        let policies = with_custom_policies(MANYLINUX_POLICIES.clone(), &custom, false);
+        assert_eq!(policies.len(), MANYLINUX_POLICIES.len());
+        let manylinux2014 = policies
+            .iter()
+            .find(|p| p.name == "manylinux_2_17")
+            .unwrap();
+        assert!(manylinux2014.lib_whitelist.is_empty());
+
+        let policies = with_custom_policies(MANYLINUX_POLICIES.clone(), &custom, true);
+        assert_eq!(policies.len(), MANYLINUX_POLICIES.len() + 1);
+        let priorities: Vec<_> = policies.iter().map(|p| p.priority).collect();
+        let mut sorted = priorities.clone();
+        sorted.sort_by_key(|priority| -priority);
+        assert_eq!(priorities, sorted);
+    }
 }
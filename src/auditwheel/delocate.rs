@@ -0,0 +1,637 @@
+//! Bundling of external dylibs into macOS wheels, similar to what
+//! [delocate](https://github.com/matthew-brett/delocate) does
+//!
+//! Only goblin's Mach-O parser is used, so this also works when cross compiling from linux.
+//! The install names are rewritten in place, which requires enough padding after the load
+//! commands, just like `install_name_tool`.
+
+use super::audit::relpath;
+use crate::module_writer::{ModuleWriter, WheelWriter};
+use anyhow::{bail, Context, Result};
+use fs_err as fs;
+use goblin::mach::load_command::CommandVariant;
+use goblin::mach::{Mach, MachO};
+use lddtree::Library;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Libraries in these locations are part of every macOS installation
+fn is_system_dylib(install_name: &str) -> bool {
+    install_name.starts_with("/usr/lib/") || install_name.starts_with("/System/")
+}
+
+/// The interpreter that loads the extension module provides libpython, bundling another copy
+/// would load a second interpreter into the process
+fn is_python_dylib(install_name: &str) -> bool {
+    let file_name = install_name.rsplit('/').next().unwrap_or(install_name);
+    install_name.contains("Python.framework/") || file_name.starts_with("libpython")
+}
+
+/// Returns the slices of a Mach-O file, one for thin files and one per architecture for
+/// universal2 files
+fn macho_slices(data: &[u8]) -> Result<Vec<(usize, usize)>> {
+    match Mach::parse(data)? {
+        Mach::Binary(_) => Ok(vec![(0, data.len())]),
+        Mach::Fat(fat) => Ok(fat
+            .arches()?
+            .into_iter()
+            .map(|arch| (arch.offset as usize, arch.size as usize))
+            .collect()),
+    }
+}
+
+/// Returns the install names of the dylibs a Mach-O file links and its `LC_RPATH` entries,
+/// merged over all architectures
+fn read_dylib_deps(path: &Path) -> Result<(Vec<String>, Vec<String>)> {
+    let data = fs::read(path)?;
+    let mut needed = Vec::new();
+    let mut rpaths = Vec::new();
+    for (offset, size) in macho_slices(&data)
+        .with_context(|| format!("Failed to parse Mach-O file at '{}'", path.display()))?
+    {
+        let macho = MachO::parse(&data[offset..offset + size], 0)
+            .with_context(|| format!("Failed to parse Mach-O file at '{}'", path.display()))?;
+        // The first entry is the library itself
+        for lib in macho.libs.iter().skip(1) {
+            if !needed.iter().any(|name| name == lib) {
+                needed.push(lib.to_string());
+            }
+        }
+        for rpath in macho.rpaths {
+            if !rpaths.iter().any(|name| name == rpath) {
+                rpaths.push(rpath.to_string());
+            }
+        }
+    }
+    Ok((needed, rpaths))
+}
+
+/// Finds the file an install name refers to, falling back to the library search paths of
+/// the build so that dylibs for another machine can be found when cross compiling
+///
+/// When cross compiling, absolute paths refer to the target machine and are only looked up in
+/// the library search paths.
+fn resolve_install_name(
+    install_name: &str,
+    loader_dir: &Path,
+    rpaths: &[String],
+    ld_paths: &[PathBuf],
+    cross_compiling: bool,
+) -> Option<PathBuf> {
+    let expand = |path: &str| -> Option<PathBuf> {
+        if let Some(rest) = path.strip_prefix("@loader_path") {
+            Some(loader_dir.join(rest.trim_start_matches('/')))
+        } else if path.starts_with('@') || cross_compiling {
+            // @executable_path depends on the python interpreter that loads us, and absolute
+            // paths of another machine don't exist on this one
+            None
+        } else {
+            Some(PathBuf::from(path))
+        }
+    };
+    let mut candidates: Vec<PathBuf> = match install_name.strip_prefix("@rpath/") {
+        Some(rest) => rpaths
+            .iter()
+            .filter_map(|rpath| expand(rpath))
+            .map(|rpath| rpath.join(rest))
+            .collect(),
+        None => expand(install_name).into_iter().collect(),
+    };
+    if let Some(file_name) = Path::new(install_name).file_name() {
+        candidates.extend(ld_paths.iter().map(|dir| dir.join(file_name)));
+    }
+    candidates.into_iter().find(|path| path.is_file())
+}
+
+/// Finds the dylibs outside of the system locations that a Mach-O file links, including the
+/// ones those dylibs link themselves
+///
+/// libpython and the Python framework are never bundled. Dylibs that can't be found are
+/// skipped with a warning, since they may be provided by the machine the wheel is installed on.
+pub fn find_external_dylibs(
+    artifact: impl AsRef<Path>,
+    ld_paths: &[PathBuf],
+    cross_compiling: bool,
+) -> Result<Vec<Library>> {
+    let artifact = artifact.as_ref();
+    let (needed, rpaths) = read_dylib_deps(artifact)?;
+    let mut pending = vec![(artifact.to_path_buf(), needed, rpaths)];
+    let mut ext_libs: Vec<Library> = Vec::new();
+    while let Some((loader, needed, rpaths)) = pending.pop() {
+        let loader_dir = loader.parent().unwrap_or_else(|| Path::new(""));
+        for install_name in needed {
+            if is_system_dylib(&install_name) || ext_libs.iter().any(|lib| lib.name == install_name)
+            {
+                continue;
+            }
+            if is_python_dylib(&install_name) {
+                println!(
+                    "⚠️  Warning: {} links the python library {}, it won't be bundled",
+                    loader.display(),
+                    install_name
+                );
+                continue;
+            }
+            let path = match resolve_install_name(
+                &install_name,
+                loader_dir,
+                &rpaths,
+                ld_paths,
+                cross_compiling,
+            ) {
+                Some(path) => path,
+                None => {
+                    println!(
+                        "⚠️  Warning: Couldn't find {} linked by {}, it won't be bundled",
+                        install_name,
+                        loader.display()
+                    );
+                    continue;
+                }
+            };
+            let realpath = path.canonicalize()?;
+            let (lib_needed, lib_rpaths) = read_dylib_deps(&realpath)?;
+            ext_libs.push(Library {
+                name: install_name,
+                path,
+                realpath: Some(realpath.clone()),
+                needed: lib_needed.clone(),
+                rpath: lib_rpaths.clone(),
+                runpath: Vec::new(),
+            });
+            pending.push((realpath, lib_needed, lib_rpaths));
+        }
+    }
+    Ok(ext_libs)
+}
+
+/// Rewrites the `LC_ID_DYLIB` and the `LC_LOAD_DYLIB` (and its weak, reexport, lazy and upward
+/// variants) install names of a single architecture Mach-O file
+///
+/// Returns whether the file has a code signature, which is invalid after changing it
+fn change_install_names_in_slice(
+    data: &mut [u8],
+    new_id: Option<&str>,
+    changes: &HashMap<String, String>,
+) -> Result<bool> {
+    let macho = MachO::parse(data, 0)?;
+    let le = macho.little_endian;
+    let (header_size, align) = if macho.is_64 { (32, 8) } else { (28, 4) };
+    let old_size = macho.header.sizeofcmds as usize;
+    // The load commands may grow until the first section
+    let mut limit = data.len();
+    for segment in macho.segments.iter() {
+        for (section, _) in segment.sections()? {
+            if section.offset > 0 {
+                limit = limit.min(section.offset as usize);
+            }
+        }
+    }
+
+    let write_u32 = |value: u32| {
+        if le {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        }
+    };
+
+    let mut has_signature = false;
+    let mut load_commands = Vec::with_capacity(old_size);
+    for load_command in &macho.load_commands {
+        let raw = &data[load_command.offset..load_command.offset + load_command.command.cmdsize()];
+        let new_name = match load_command.command {
+            CommandVariant::LoadDylib(command)
+            | CommandVariant::LoadWeakDylib(command)
+            | CommandVariant::ReexportDylib(command)
+            | CommandVariant::LazyLoadDylib(command)
+            | CommandVariant::LoadUpwardDylib(command) => {
+                let name = &raw[command.dylib.name as usize..];
+                let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+                std::str::from_utf8(name)
+                    .ok()
+                    .and_then(|name| changes.get(name))
+                    .map(String::as_str)
+            }
+            CommandVariant::IdDylib(_) => new_id,
+            CommandVariant::CodeSignature(_) => {
+                has_signature = true;
+                None
+            }
+            _ => None,
+        };
+        match new_name {
+            Some(new_name) => {
+                // struct dylib_command: cmd, cmdsize, name offset, timestamp, current_version
+                // and compatibility_version followed by the name
+                let cmdsize = (24 + new_name.len() + 1 + align - 1) / align * align;
+                let start = load_commands.len();
+                load_commands.extend_from_slice(&raw[..24]);
+                load_commands[start + 4..start + 8].copy_from_slice(&write_u32(cmdsize as u32));
+                load_commands[start + 8..start + 12].copy_from_slice(&write_u32(24));
+                load_commands.extend_from_slice(new_name.as_bytes());
+                load_commands.resize(start + cmdsize, 0);
+            }
+            None => load_commands.extend_from_slice(raw),
+        }
+    }
+
+    if header_size + load_commands.len() > limit {
+        bail!(
+            "Not enough space to change the install names, \
+            try linking with `-C link-arg=-Wl,-headerpad_max_install_names`"
+        );
+    }
+    let new_size = load_commands.len();
+    data[header_size..header_size + new_size].copy_from_slice(&load_commands);
+    if new_size < old_size {
+        data[header_size + new_size..header_size + old_size].fill(0);
+    }
+    data[20..24].copy_from_slice(&write_u32(new_size as u32));
+    Ok(has_signature)
+}
+
+/// Changes the install name of a dylib and the install names of the dylibs it links
+pub fn change_install_names(
+    path: &Path,
+    new_id: Option<&str>,
+    changes: &HashMap<String, String>,
+) -> Result<()> {
+    let mut data = fs::read(path)?;
+    let mut has_signature = false;
+    for (offset, size) in macho_slices(&data)? {
+        has_signature |=
+            change_install_names_in_slice(&mut data[offset..offset + size], new_id, changes)
+                .with_context(|| {
+                    format!("Failed to change install names of '{}'", path.display())
+                })?;
+    }
+    fs::write(path, data)?;
+    if has_signature {
+        resign(path);
+    }
+    Ok(())
+}
+
+/// Changing the load commands invalidates the code signature, which arm64 macOS requires,
+/// so we replace it with an ad-hoc signature if we can
+fn resign(path: &Path) {
+    let output = Command::new("codesign")
+        .args(["--force", "--sign", "-"])
+        .arg(path)
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {}
+        _ => println!(
+            "⚠️  Warning: Failed to ad-hoc sign {}, run `codesign --force --sign - {}` on macOS",
+            path.display(),
+            path.display()
+        ),
+    }
+}
+
+/// Copies the external dylibs into `libs_dir` inside of the wheel and points the install
+/// names of the artifacts to them using `@loader_path`
+///
+/// `artifacts` are pairs of the path of the artifact on disk and the directory it is placed in
+/// inside of the wheel, `ext_libs` are the external dylibs of each artifact.
+pub fn graft_external_dylibs(
+    writer: &mut WheelWriter,
+    libs_dir: &Path,
+    artifacts: &[(&Path, &Path)],
+    ext_libs: &[Vec<Library>],
+) -> Result<()> {
+    writer.add_directory(libs_dir)?;
+
+    // Maps install names to the file name in `libs_dir`
+    let mut file_names = HashMap::new();
+    let mut libs = Vec::new();
+    for lib in ext_libs.iter().flatten() {
+        if file_names.contains_key(&lib.name) {
+            continue;
+        }
+        let realpath = lib.realpath.clone().with_context(|| {
+            format!(
+                "Cannot repair wheel, because required library {} could not be located.",
+                lib.path.display()
+            )
+        })?;
+        let file_name = realpath
+            .file_name()
+            .context("Invalid library path")?
+            .to_string_lossy()
+            .to_string();
+        if libs.iter().any(
+            |(_, other_path, other_name): &(&Library, PathBuf, String)| {
+                *other_name == file_name && *other_path != realpath
+            },
+        ) {
+            bail!(
+                "Cannot repair wheel, because there are two different libraries named {}",
+                file_name
+            );
+        }
+        file_names.insert(lib.name.clone(), file_name.clone());
+        libs.push((lib, realpath, file_name));
+    }
+
+    let temp_dir = tempfile::tempdir()?;
+    let mut libs_copied = HashSet::new();
+    for (lib, realpath, file_name) in &libs {
+        let dest_path = temp_dir.path().join(file_name);
+        if libs_copied.insert(file_name.clone()) {
+            fs::copy(realpath, &dest_path)?;
+        }
+        let changes = lib
+            .needed
+            .iter()
+            .filter_map(|name| {
+                let file_name = file_names.get(name)?;
+                Some((name.clone(), format!("@loader_path/{}", file_name)))
+            })
+            .collect();
+        let id = format!("@loader_path/{}", file_name);
+        change_install_names(&dest_path, Some(&id), &changes)?;
+    }
+    for file_name in &libs_copied {
+        writer.add_file_with_permissions(
+            libs_dir.join(file_name),
+            temp_dir.path().join(file_name),
+            0o755,
+        )?;
+    }
+
+    println!(
+        "🖨  Copied external shared libraries to package {} directory:",
+        libs_dir.display()
+    );
+    for (_, realpath, _) in &libs {
+        println!("    {}", realpath.display());
+    }
+
+    for ((artifact, artifact_dir), artifact_ext_libs) in artifacts.iter().zip(ext_libs) {
+        let loader_path = relpath(libs_dir, artifact_dir);
+        let changes: HashMap<_, _> = artifact_ext_libs
+            .iter()
+            .map(|lib| {
+                let target = Path::new("@loader_path")
+                    .join(&loader_path)
+                    .join(&file_names[&lib.name]);
+                (
+                    lib.name.clone(),
+                    target.to_string_lossy().replace('\\', "/"),
+                )
+            })
+            .collect();
+        if !changes.is_empty() {
+            change_install_names(artifact, None, &changes)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    const LC_ID_DYLIB: u32 = 0xd;
+    const LC_LOAD_DYLIB: u32 = 0xc;
+    const LC_RPATH: u32 = 0x8000_001c;
+
+    fn dylib_command(cmd: u32, name: &str) -> Vec<u8> {
+        let cmdsize = (24 + name.len() + 1 + 7) / 8 * 8;
+        let mut command = Vec::new();
+        for value in [cmd, cmdsize as u32, 24, 0, 0x10000, 0x10000] {
+            command.extend_from_slice(&value.to_le_bytes());
+        }
+        command.extend_from_slice(name.as_bytes());
+        command.resize(cmdsize, 0);
+        command
+    }
+
+    fn rpath_command(path: &str) -> Vec<u8> {
+        let cmdsize = (12 + path.len() + 1 + 7) / 8 * 8;
+        let mut command = Vec::new();
+        for value in [LC_RPATH, cmdsize as u32, 12] {
+            command.extend_from_slice(&value.to_le_bytes());
+        }
+        command.extend_from_slice(path.as_bytes());
+        command.resize(cmdsize, 0);
+        command
+    }
+
+    /// Writes a minimal x86_64 dylib whose only section starts at 0x1000, which leaves
+    /// room to grow the load commands
+    fn write_dylib(path: &Path, id: &str, needed: &[&str], rpaths: &[&str]) {
+        let mut segment = Vec::new();
+        segment.extend_from_slice(&0x19u32.to_le_bytes()); // LC_SEGMENT_64
+        segment.extend_from_slice(&(72u32 + 80).to_le_bytes());
+        segment.extend_from_slice(b"__TEXT\0\0\0\0\0\0\0\0\0\0");
+        for value in [0u64, 0x2000, 0, 0x2000] {
+            segment.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [5u32, 5, 1, 0] {
+            segment.extend_from_slice(&value.to_le_bytes());
+        }
+        segment.extend_from_slice(b"__text\0\0\0\0\0\0\0\0\0\0");
+        segment.extend_from_slice(b"__TEXT\0\0\0\0\0\0\0\0\0\0");
+        for value in [0x1000u64, 0x10] {
+            segment.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [0x1000u32, 0, 0, 0, 0, 0, 0, 0] {
+            segment.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut commands = vec![segment, dylib_command(LC_ID_DYLIB, id)];
+        commands.extend(needed.iter().map(|name| dylib_command(LC_LOAD_DYLIB, name)));
+        commands.extend(rpaths.iter().map(|rpath| rpath_command(rpath)));
+        let sizeofcmds: usize = commands.iter().map(Vec::len).sum();
+
+        let mut data = Vec::new();
+        // MH_MAGIC_64, CPU_TYPE_X86_64, CPU_SUBTYPE_X86_64_ALL, MH_DYLIB
+        for value in [0xfeedfacfu32, 0x0100_0007, 3, 6] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [commands.len() as u32, sizeofcmds as u32, 0, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend(commands.into_iter().flatten());
+        data.resize(0x2000, 0);
+        fs::write(path, data).unwrap();
+    }
+
+    fn libs(data: &[u8]) -> Vec<String> {
+        let macho = MachO::parse(data, 0).unwrap();
+        macho.libs.iter().map(|lib| lib.to_string()).collect()
+    }
+
+    /// An extension module linking homebrew's libfoo, which links libbar next to it
+    fn write_fixtures(dir: &Path) -> (PathBuf, PathBuf) {
+        let lib_dir = dir.join("lib");
+        fs::create_dir(&lib_dir).unwrap();
+        write_dylib(
+            &lib_dir.join("libfoo.dylib"),
+            "/opt/homebrew/lib/libfoo.dylib",
+            &["/usr/lib/libSystem.B.dylib", "@rpath/libbar.dylib"],
+            &["@loader_path"],
+        );
+        write_dylib(
+            &lib_dir.join("libbar.dylib"),
+            "@rpath/libbar.dylib",
+            &["/usr/lib/libSystem.B.dylib"],
+            &[],
+        );
+        let artifact = dir.join("foo.so");
+        write_dylib(
+            &artifact,
+            "@rpath/foo.so",
+            &[
+                "/usr/lib/libSystem.B.dylib",
+                "/opt/homebrew/lib/libfoo.dylib",
+            ],
+            &[],
+        );
+        (artifact, lib_dir)
+    }
+
+    #[test]
+    fn test_find_external_dylibs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (artifact, lib_dir) = write_fixtures(temp_dir.path());
+
+        let ext_libs =
+            find_external_dylibs(&artifact, std::slice::from_ref(&lib_dir), false).unwrap();
+        let names: Vec<_> = ext_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["/opt/homebrew/lib/libfoo.dylib", "@rpath/libbar.dylib"]
+        );
+        assert_eq!(
+            ext_libs[1].realpath,
+            Some(lib_dir.join("libbar.dylib").canonicalize().unwrap())
+        );
+
+        // Without the search path, the homebrew library is missing on this machine
+        let ext_libs = find_external_dylibs(&artifact, &[], false).unwrap();
+        assert!(ext_libs.is_empty());
+    }
+
+    #[test]
+    fn test_find_external_dylibs_skips_python() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lib_dir = temp_dir.path().join("lib");
+        fs::create_dir(&lib_dir).unwrap();
+        write_dylib(
+            &lib_dir.join("libpython3.11.dylib"),
+            "@rpath/libpython3.11.dylib",
+            &[],
+            &[],
+        );
+        let artifact = temp_dir.path().join("foo.so");
+        write_dylib(
+            &artifact,
+            "@rpath/foo.so",
+            &[
+                "/Library/Frameworks/Python.framework/Versions/3.11/Python",
+                "@rpath/libpython3.11.dylib",
+            ],
+            &["@loader_path/lib"],
+        );
+
+        let ext_libs = find_external_dylibs(&artifact, &[lib_dir], false).unwrap();
+        assert!(ext_libs.is_empty());
+    }
+
+    #[test]
+    fn test_find_external_dylibs_cross_compiling() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lib_dir = temp_dir.path().join("lib");
+        fs::create_dir(&lib_dir).unwrap();
+        let host_lib = lib_dir.join("libfoo.dylib");
+        write_dylib(&host_lib, "libfoo.dylib", &[], &[]);
+        let artifact = temp_dir.path().join("foo.so");
+        let install_name = host_lib.to_str().unwrap();
+        write_dylib(&artifact, "@rpath/foo.so", &[install_name], &[]);
+
+        // Absolute install names refer to the target machine, not to the build host
+        let ext_libs = find_external_dylibs(&artifact, &[], true).unwrap();
+        assert!(ext_libs.is_empty());
+        // The library search paths of the build still apply
+        let ext_libs = find_external_dylibs(&artifact, &[lib_dir], true).unwrap();
+        assert_eq!(ext_libs.len(), 1);
+        assert_eq!(ext_libs[0].name, install_name);
+    }
+
+    #[test]
+    fn test_change_install_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("libfoo.dylib");
+        write_dylib(&path, "short", &["/usr/lib/libSystem.B.dylib", "a"], &[]);
+
+        let long_name = format!("@loader_path/{}.dylib", "x".repeat(100));
+        let changes = HashMap::from([("a".to_string(), long_name.clone())]);
+        change_install_names(&path, Some("@loader_path/libfoo.dylib"), &changes).unwrap();
+        assert_eq!(
+            libs(&fs::read(&path).unwrap()),
+            [
+                "@loader_path/libfoo.dylib",
+                "/usr/lib/libSystem.B.dylib",
+                &long_name
+            ]
+        );
+
+        // The load commands must not overwrite the first section
+        let changes = HashMap::from([(long_name, "y".repeat(0x1000))]);
+        let err = change_install_names(&path, None, &changes).unwrap_err();
+        assert!(format!("{:?}", err).contains("headerpad_max_install_names"));
+    }
+
+    #[test]
+    fn test_graft_external_dylibs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (artifact, lib_dir) = write_fixtures(temp_dir.path());
+        let ext_libs = find_external_dylibs(&artifact, &[lib_dir], false).unwrap();
+
+        let wheel = temp_dir
+            .path()
+            .join("foo-0.1.0-py3-none-macosx_11_0_arm64.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        let artifacts = [(artifact.as_path(), Path::new("foo"))];
+        graft_external_dylibs(&mut writer, Path::new("foo.libs"), &artifacts, &[ext_libs]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            libs(&fs::read(&artifact).unwrap()),
+            [
+                "@rpath/foo.so",
+                "/usr/lib/libSystem.B.dylib",
+                "@loader_path/../foo.libs/libfoo.dylib"
+            ]
+        );
+        let mut archive = zip::ZipArchive::new(fs::File::open(&wheel).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut data = Vec::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        };
+        assert_eq!(
+            libs(&read("foo.libs/libfoo.dylib")),
+            [
+                "@loader_path/libfoo.dylib",
+                "/usr/lib/libSystem.B.dylib",
+                "@loader_path/libbar.dylib"
+            ]
+        );
+        assert_eq!(
+            libs(&read("foo.libs/libbar.dylib")),
+            ["@loader_path/libbar.dylib", "/usr/lib/libSystem.B.dylib"]
+        );
+    }
+}
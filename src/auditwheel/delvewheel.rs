@@ -0,0 +1,728 @@
+//! Bundling of external DLLs into windows wheels, similar to what
+//! [delvewheel](https://github.com/adang1345/delvewheel) does
+//!
+//! Only goblin's PE parser is used, so this also works when cross compiling from linux,
+//! e.g. with cargo-xwin. The new import names don't fit in place since they contain a hash,
+//! so they are written to a new section which the import descriptors then point to.
+
+use super::audit::relpath;
+use crate::build_context::hash_file;
+use crate::module_writer::{ModuleWriter, WheelWriter};
+use anyhow::{bail, Context, Result};
+use fs_err as fs;
+use goblin::pe::PE;
+use lddtree::Library;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+/// DLLs that are part of every windows installation, in lowercase
+const SYSTEM_DLLS: &[&str] = &[
+    "advapi32.dll",
+    "bcrypt.dll",
+    "bcryptprimitives.dll",
+    "cfgmgr32.dll",
+    "comctl32.dll",
+    "comdlg32.dll",
+    "crypt32.dll",
+    "d3d11.dll",
+    "d3d12.dll",
+    "dbghelp.dll",
+    "dnsapi.dll",
+    "dwmapi.dll",
+    "dwrite.dll",
+    "dxgi.dll",
+    "gdi32.dll",
+    "hid.dll",
+    "imm32.dll",
+    "iphlpapi.dll",
+    "kernel32.dll",
+    "kernelbase.dll",
+    "msvcrt.dll",
+    "mswsock.dll",
+    "ncrypt.dll",
+    "netapi32.dll",
+    "normaliz.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "opengl32.dll",
+    "powrprof.dll",
+    "propsys.dll",
+    "psapi.dll",
+    "rpcrt4.dll",
+    "secur32.dll",
+    "setupapi.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "ucrtbase.dll",
+    "user32.dll",
+    "userenv.dll",
+    "uxtheme.dll",
+    "version.dll",
+    "winhttp.dll",
+    "wininet.dll",
+    "winmm.dll",
+    "winspool.drv",
+    "wintrust.dll",
+    "wldap32.dll",
+    "ws2_32.dll",
+    // Shipped with every python installation
+    "vcruntime140.dll",
+    "vcruntime140_1.dll",
+];
+
+/// Whether a DLL is provided by windows or python itself
+fn is_system_dll(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("api-ms-win-")
+        || name.starts_with("ext-ms-")
+        || name.starts_with("python")
+        || name.starts_with("libpython")
+        || SYSTEM_DLLS.contains(&name.as_str())
+}
+
+/// A reference to the name of an imported DLL
+struct DllImport {
+    /// The file offset of the field with the address of the name
+    name_field: usize,
+    /// The file offset of the time stamp, which is set when the import is bound
+    time_stamp_field: usize,
+    /// Old style delay load descriptors use virtual addresses instead of RVAs
+    is_va: bool,
+    name: String,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32> {
+    let bytes = data
+        .get(offset..offset + 4)
+        .context("Unexpected end of PE file")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn rva_to_offset(pe: &PE, rva: u32) -> Option<usize> {
+    pe.sections
+        .iter()
+        .find(|section| {
+            let size = section.virtual_size.max(section.size_of_raw_data);
+            rva >= section.virtual_address && rva < section.virtual_address + size
+        })
+        .map(|section| (rva - section.virtual_address + section.pointer_to_raw_data) as usize)
+}
+
+fn read_dll_name(data: &[u8], pe: &PE, rva: u32) -> Result<String> {
+    let offset = rva_to_offset(pe, rva).context("Invalid address of an imported DLL name")?;
+    let name = data.get(offset..).context("Unexpected end of PE file")?;
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+    Ok(String::from_utf8_lossy(name).to_string())
+}
+
+/// Reads the DLL names of both the import table and the delay load import table, which
+/// goblin doesn't parse
+fn dll_imports(data: &[u8], pe: &PE) -> Result<Vec<DllImport>> {
+    let mut imports = Vec::new();
+    let optional_header = match pe.header.optional_header {
+        Some(optional_header) => optional_header,
+        None => return Ok(imports),
+    };
+    let image_base = optional_header.windows_fields.image_base;
+    let data_directories = &optional_header.data_directories;
+
+    if let Some(import_table) = data_directories.get_import_table() {
+        if import_table.virtual_address != 0 {
+            let mut offset = rva_to_offset(pe, import_table.virtual_address)
+                .context("Invalid import table address")?;
+            // IMAGE_IMPORT_DESCRIPTOR: OriginalFirstThunk, TimeDateStamp, ForwarderChain, Name
+            // and FirstThunk
+            loop {
+                let name_rva = read_u32(data, offset + 12)?;
+                if name_rva == 0 {
+                    break;
+                }
+                imports.push(DllImport {
+                    name_field: offset + 12,
+                    time_stamp_field: offset + 4,
+                    is_va: false,
+                    name: read_dll_name(data, pe, name_rva)?,
+                });
+                offset += 20;
+            }
+        }
+    }
+
+    if let Some(delay_import_table) = data_directories.get_delay_import_descriptor() {
+        if delay_import_table.virtual_address != 0 {
+            let mut offset = rva_to_offset(pe, delay_import_table.virtual_address)
+                .context("Invalid delay load import table address")?;
+            // IMAGE_DELAYLOAD_DESCRIPTOR: Attributes, DllNameRVA, ModuleHandleRVA,
+            // ImportAddressTableRVA, ImportNameTableRVA, BoundImportAddressTableRVA,
+            // UnloadInformationTableRVA and TimeDateStamp
+            loop {
+                let name_address = read_u32(data, offset + 4)?;
+                if name_address == 0 {
+                    break;
+                }
+                let is_va = read_u32(data, offset)? & 1 == 0;
+                let name_rva = if is_va {
+                    u64::from(name_address)
+                        .checked_sub(image_base)
+                        .and_then(|rva| u32::try_from(rva).ok())
+                        .context("Invalid delay load DLL name address")?
+                } else {
+                    name_address
+                };
+                imports.push(DllImport {
+                    name_field: offset + 4,
+                    time_stamp_field: offset + 28,
+                    is_va,
+                    name: read_dll_name(data, pe, name_rva)?,
+                });
+                offset += 32;
+            }
+        }
+    }
+    Ok(imports)
+}
+
+fn read_imports(path: &Path) -> Result<Vec<String>> {
+    let data = fs::read(path)?;
+    let pe = PE::parse(&data)
+        .with_context(|| format!("Failed to parse PE file at '{}'", path.display()))?;
+    let imports = dll_imports(&data, &pe)
+        .with_context(|| format!("Failed to read the imports of '{}'", path.display()))?;
+    Ok(imports.into_iter().map(|import| import.name).collect())
+}
+
+/// Looks for a DLL in the given directories, ignoring case like windows does
+fn find_dll(name: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    for dir in dirs {
+        let path = dir.join(name);
+        if path.is_file() {
+            return Some(path);
+        }
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                if entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(name)
+                    && entry.path().is_file()
+                {
+                    return Some(entry.path());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Finds the DLLs that are neither part of windows nor of python that a PE file imports,
+/// including the ones those DLLs import themselves
+///
+/// The DLLs are searched next to the importing file and in the library search paths of the
+/// build, and on windows additionally in `PATH`.
+pub fn find_external_dlls(
+    artifact: impl AsRef<Path>,
+    ld_paths: &[PathBuf],
+) -> Result<Vec<Library>> {
+    let artifact = artifact.as_ref();
+    let mut path_dirs = Vec::new();
+    if cfg!(windows) {
+        if let Some(path) = env::var_os("PATH") {
+            path_dirs.extend(env::split_paths(&path));
+        }
+    }
+    let system_root = env::var_os("SystemRoot").map(PathBuf::from);
+
+    let mut pending = vec![(artifact.to_path_buf(), read_imports(artifact)?)];
+    let mut seen = HashSet::new();
+    let mut ext_libs = Vec::new();
+    while let Some((loader, needed)) = pending.pop() {
+        let mut search_dirs = vec![loader
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .to_path_buf()];
+        search_dirs.extend_from_slice(ld_paths);
+        search_dirs.extend_from_slice(&path_dirs);
+        for name in needed {
+            if is_system_dll(&name) || !seen.insert(name.to_ascii_lowercase()) {
+                continue;
+            }
+            let path = match find_dll(&name, &search_dirs) {
+                Some(path) => path,
+                None => {
+                    println!(
+                        "⚠️  Warning: Couldn't find {} imported by {}, it won't be bundled",
+                        name,
+                        loader.display()
+                    );
+                    continue;
+                }
+            };
+            if let Some(system_root) = &system_root {
+                if path.starts_with(system_root) {
+                    continue;
+                }
+            }
+            let realpath = path.canonicalize()?;
+            let lib_needed = read_imports(&realpath)?;
+            ext_libs.push(Library {
+                name,
+                path,
+                realpath: Some(realpath.clone()),
+                needed: lib_needed.clone(),
+                rpath: Vec::new(),
+                runpath: Vec::new(),
+            });
+            pending.push((realpath, lib_needed));
+        }
+    }
+    Ok(ext_libs)
+}
+
+fn align(value: usize, alignment: usize) -> usize {
+    let alignment = alignment.max(1);
+    (value + alignment - 1) / alignment * alignment
+}
+
+/// Renames the imported DLLs of a PE file, with `changes` mapping lowercase DLL names to their
+/// new names
+///
+/// The new names are stored in an additional section. This invalidates a code signature,
+/// so it is removed.
+pub fn rename_imports(path: &Path, changes: &HashMap<String, String>) -> Result<()> {
+    let mut data = fs::read(path)?;
+    let pe = PE::parse(&data)
+        .with_context(|| format!("Failed to parse PE file at '{}'", path.display()))?;
+    let imports: Vec<_> = dll_imports(&data, &pe)
+        .with_context(|| format!("Failed to read the imports of '{}'", path.display()))?
+        .into_iter()
+        .filter_map(|import| {
+            let new_name = changes.get(&import.name.to_ascii_lowercase())?;
+            Some((import, new_name))
+        })
+        .collect();
+    if imports.is_empty() {
+        return Ok(());
+    }
+
+    let optional_header = pe
+        .header
+        .optional_header
+        .context("PE file without optional header")?;
+    let windows_fields = optional_header.windows_fields;
+    let file_alignment = windows_fields.file_alignment as usize;
+    let section_alignment = windows_fields.section_alignment as usize;
+    let image_base = windows_fields.image_base;
+    let certificate_table = *optional_header.data_directories.get_certificate_table();
+
+    let pe_pointer = pe.header.dos_header.pe_pointer as usize;
+    let optional_header_offset = pe_pointer + 24;
+    let data_directories_offset = optional_header_offset + if pe.is_64 { 112 } else { 96 };
+    let section_table_offset =
+        optional_header_offset + pe.header.coff_header.size_of_optional_header as usize;
+    let section_header_offset = section_table_offset + 40 * pe.sections.len();
+    let first_section = pe
+        .sections
+        .iter()
+        .filter(|section| section.size_of_raw_data > 0)
+        .map(|section| section.pointer_to_raw_data as usize)
+        .min()
+        .unwrap_or(data.len());
+    if section_header_offset + 40 > first_section.min(windows_fields.size_of_headers as usize) {
+        bail!(
+            "Not enough space in the headers of '{}' to rename the imported DLLs",
+            path.display()
+        );
+    }
+    let virtual_address = pe
+        .sections
+        .iter()
+        .map(|section| {
+            (section.virtual_address + section.virtual_size.max(section.size_of_raw_data)) as usize
+        })
+        .max()
+        .unwrap_or(section_alignment);
+    let virtual_address = align(virtual_address, section_alignment);
+    let number_of_sections = pe.sections.len() as u16 + 1;
+
+    // The certificate table comes last and its address is a file offset
+    if let Some(certificate_table) = certificate_table {
+        let start = certificate_table.virtual_address as usize;
+        if certificate_table.size > 0 && start + certificate_table.size as usize >= data.len() {
+            data.truncate(start);
+        }
+        data[data_directories_offset + 4 * 8..data_directories_offset + 5 * 8].fill(0);
+    }
+    // Bound imports precompute addresses for the original DLLs
+    data[data_directories_offset + 11 * 8..data_directories_offset + 12 * 8].fill(0);
+
+    let mut names = Vec::new();
+    for (import, new_name) in &imports {
+        let rva = (virtual_address + names.len()) as u32;
+        let address = if import.is_va {
+            u32::try_from(u64::from(rva) + image_base).with_context(|| {
+                format!(
+                    "The address of the DLL name '{}' doesn't fit into the delay load descriptor",
+                    new_name
+                )
+            })?
+        } else {
+            rva
+        };
+        write_u32(&mut data, import.name_field, address);
+        write_u32(&mut data, import.time_stamp_field, 0);
+        names.extend_from_slice(new_name.as_bytes());
+        names.push(0);
+    }
+
+    let pointer_to_raw_data = align(data.len(), file_alignment);
+    let size_of_raw_data = align(names.len(), file_alignment);
+    data.resize(pointer_to_raw_data, 0);
+    data.extend_from_slice(&names);
+    data.resize(pointer_to_raw_data + size_of_raw_data, 0);
+
+    let mut section_header = Vec::with_capacity(40);
+    section_header.extend_from_slice(b".maturin");
+    for value in [
+        names.len(),
+        virtual_address,
+        size_of_raw_data,
+        pointer_to_raw_data,
+        0,
+        0,
+    ] {
+        section_header.extend_from_slice(&(value as u32).to_le_bytes());
+    }
+    // Number of relocations and line numbers
+    section_header.extend_from_slice(&[0; 4]);
+    // IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ
+    section_header.extend_from_slice(&0x4000_0040u32.to_le_bytes());
+    data[section_header_offset..section_header_offset + 40].copy_from_slice(&section_header);
+
+    data[pe_pointer + 6..pe_pointer + 8].copy_from_slice(&number_of_sections.to_le_bytes());
+    let size_of_image = align(virtual_address + names.len(), section_alignment);
+    write_u32(&mut data, optional_header_offset + 56, size_of_image as u32);
+    // The checksum is only verified for drivers
+    write_u32(&mut data, optional_header_offset + 64, 0);
+
+    fs::write(path, data)?;
+    Ok(())
+}
+
+/// Copies the external DLLs into `libs_dir` inside of the wheel, renaming them with a
+/// short hash of their content, and renames the imports of the artifacts accordingly
+///
+/// `artifacts` are pairs of the path of the artifact on disk and the directory it is placed in
+/// inside of the wheel, `ext_libs` are the external DLLs of each artifact. Unlike on linux and
+/// macOS, the loader won't look into `libs_dir`, see [dll_directory_hook].
+pub fn graft_external_dlls(
+    writer: &mut WheelWriter,
+    libs_dir: &Path,
+    artifacts: &[(&Path, &Path)],
+    ext_libs: &[Vec<Library>],
+) -> Result<()> {
+    writer.add_directory(libs_dir)?;
+
+    let mut new_names = HashMap::new();
+    let mut libs = Vec::new();
+    for lib in ext_libs.iter().flatten() {
+        let key = lib.name.to_ascii_lowercase();
+        if new_names.contains_key(&key) {
+            continue;
+        }
+        let realpath = lib.realpath.clone().with_context(|| {
+            format!(
+                "Cannot repair wheel, because required library {} could not be located.",
+                lib.path.display()
+            )
+        })?;
+        // Generate a new name with a short hash so we don't clash with a different version
+        // of the same DLL that is already loaded
+        let short_hash = &hash_file(&realpath)?[..8];
+        let name = Path::new(&lib.name);
+        let file_stem = name.file_stem().unwrap_or_default().to_string_lossy();
+        let new_name = match name.extension() {
+            Some(extension) => format!(
+                "{}-{}.{}",
+                file_stem,
+                short_hash,
+                extension.to_string_lossy()
+            ),
+            None => format!("{}-{}", file_stem, short_hash),
+        };
+        new_names.insert(key, new_name.clone());
+        libs.push((realpath, new_name));
+    }
+
+    let temp_dir = tempfile::tempdir()?;
+    for (realpath, new_name) in &libs {
+        let dest_path = temp_dir.path().join(new_name);
+        fs::copy(realpath, &dest_path)?;
+        // The DLLs may import each other
+        rename_imports(&dest_path, &new_names)?;
+        writer.add_file_with_permissions(libs_dir.join(new_name), &dest_path, 0o755)?;
+    }
+
+    println!(
+        "🖨  Copied external shared libraries to package {} directory:",
+        libs_dir.display()
+    );
+    for (realpath, _) in &libs {
+        println!("    {}", realpath.display());
+    }
+
+    for (artifact, _) in artifacts {
+        rename_imports(artifact, &new_names)?;
+    }
+    Ok(())
+}
+
+/// Python code for the `__init__.py` of `package` that adds `libs_dir` to the DLL search path,
+/// so that the extension modules in the package find the bundled DLLs
+pub fn dll_directory_hook(package: &Path, libs_dir: &Path) -> String {
+    let components: Vec<_> = relpath(libs_dir, package)
+        .components()
+        .map(|component| match component {
+            Component::ParentDir => "os.pardir".to_string(),
+            component => format!("{:?}", component.as_os_str().to_string_lossy()),
+        })
+        .collect();
+    format!(
+        r#"def _maturin_add_dll_directory():
+    import os
+
+    libs_dir = os.path.abspath(os.path.join(os.path.dirname(__file__), {}))
+    if os.path.isdir(libs_dir):
+        if hasattr(os, "add_dll_directory"):
+            os.add_dll_directory(libs_dir)
+        else:
+            os.environ["PATH"] = libs_dir + os.pathsep + os.environ.get("PATH", "")
+
+
+_maturin_add_dll_directory()
+del _maturin_add_dll_directory
+"#,
+        components.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+
+    /// Writes a minimal x86_64 DLL with a single section containing the import tables
+    fn write_dll(path: &Path, imports: &[&str], delay_imports: &[&str]) {
+        let mut data = vec![0u8; 0x400];
+        data[..2].copy_from_slice(b"MZ");
+        write_u32(&mut data, 0x3c, 0x80);
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+        // COFF header: machine, number of sections, size of optional header and characteristics
+        data[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+        data[0x86..0x88].copy_from_slice(&1u16.to_le_bytes());
+        data[0x94..0x96].copy_from_slice(&240u16.to_le_bytes());
+        data[0x96..0x98].copy_from_slice(&0x2022u16.to_le_bytes());
+        // PE32+ optional header
+        let optional_header = 0x98;
+        data[optional_header..optional_header + 2].copy_from_slice(&0x20bu16.to_le_bytes());
+        data[optional_header + 24..optional_header + 32]
+            .copy_from_slice(&0x1_8000_0000u64.to_le_bytes());
+        write_u32(&mut data, optional_header + 32, 0x1000);
+        write_u32(&mut data, optional_header + 36, 0x200);
+        data[optional_header + 48..optional_header + 50].copy_from_slice(&6u16.to_le_bytes());
+        write_u32(&mut data, optional_header + 56, 0x2000);
+        write_u32(&mut data, optional_header + 60, 0x200);
+        data[optional_header + 68..optional_header + 70].copy_from_slice(&3u16.to_le_bytes());
+        write_u32(&mut data, optional_header + 108, 16);
+        // A .rdata section at 0x1000 with its content at 0x200
+        let section_table = optional_header + 240;
+        data[section_table..section_table + 8].copy_from_slice(b".rdata\0\0");
+        for (index, value) in [0x200u32, 0x1000, 0x200, 0x200].iter().enumerate() {
+            write_u32(&mut data, section_table + 8 + 4 * index, *value);
+        }
+        write_u32(&mut data, section_table + 36, 0x4000_0040);
+
+        let rva_offset = 0x1000 - 0x200;
+        let import_table = 0x200;
+        let delay_import_table = import_table + 20 * (imports.len() + 1);
+        // An empty thunk array that all descriptors share
+        let thunks = delay_import_table + 32 * (delay_imports.len() + 1);
+        let mut name = thunks + 8;
+        for (index, import) in imports.iter().enumerate() {
+            let descriptor = import_table + 20 * index;
+            write_u32(&mut data, descriptor, (thunks + rva_offset) as u32);
+            write_u32(&mut data, descriptor + 12, (name + rva_offset) as u32);
+            write_u32(&mut data, descriptor + 16, (thunks + rva_offset) as u32);
+            data[name..name + import.len()].copy_from_slice(import.as_bytes());
+            name += import.len() + 1;
+        }
+        for (index, import) in delay_imports.iter().enumerate() {
+            let descriptor = delay_import_table + 32 * index;
+            write_u32(&mut data, descriptor, 1);
+            write_u32(&mut data, descriptor + 4, (name + rva_offset) as u32);
+            data[name..name + import.len()].copy_from_slice(import.as_bytes());
+            name += import.len() + 1;
+        }
+        let data_directories = optional_header + 112;
+        write_u32(
+            &mut data,
+            data_directories + 8,
+            (import_table + rva_offset) as u32,
+        );
+        write_u32(
+            &mut data,
+            data_directories + 12,
+            20 * (imports.len() as u32 + 1),
+        );
+        if !delay_imports.is_empty() {
+            let delay_import_table = (delay_import_table + rva_offset) as u32;
+            write_u32(&mut data, data_directories + 13 * 8, delay_import_table);
+            write_u32(
+                &mut data,
+                data_directories + 13 * 8 + 4,
+                32 * (delay_imports.len() as u32 + 1),
+            );
+        }
+        fs::write(path, data).unwrap();
+    }
+
+    fn imports(data: &[u8]) -> Vec<String> {
+        let pe = PE::parse(data).unwrap();
+        dll_imports(data, &pe)
+            .unwrap()
+            .into_iter()
+            .map(|import| import.name)
+            .collect()
+    }
+
+    /// An extension module importing libfoo, which imports libbar next to it
+    fn write_fixtures(dir: &Path) -> (PathBuf, PathBuf) {
+        let lib_dir = dir.join("lib");
+        fs::create_dir(&lib_dir).unwrap();
+        write_dll(&lib_dir.join("foo.dll"), &["KERNEL32.dll", "Bar.dll"], &[]);
+        write_dll(&lib_dir.join("bar.dll"), &["kernel32.dll"], &[]);
+        let artifact = dir.join("foo.pyd");
+        write_dll(&artifact, &["python3.dll", "foo.dll"], &["missing.dll"]);
+        (artifact, lib_dir)
+    }
+
+    #[test]
+    fn test_find_external_dlls() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (artifact, lib_dir) = write_fixtures(temp_dir.path());
+
+        let ext_libs = find_external_dlls(&artifact, std::slice::from_ref(&lib_dir)).unwrap();
+        let names: Vec<_> = ext_libs.iter().map(|lib| lib.name.as_str()).collect();
+        assert_eq!(names, ["foo.dll", "Bar.dll"]);
+        assert_eq!(
+            ext_libs[1].realpath,
+            Some(lib_dir.join("bar.dll").canonicalize().unwrap())
+        );
+        assert_eq!(ext_libs[0].needed, ["KERNEL32.dll", "Bar.dll"]);
+    }
+
+    #[test]
+    fn test_delay_import_virtual_address() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo.dll");
+        write_dll(&path, &[], &["c.dll"]);
+        let mut data = fs::read(&path).unwrap();
+        // Turn the descriptor into an old style one with a virtual address truncated to 32 bit,
+        // which can't point into an image based above 4 GiB
+        let descriptor = 0x200 + 20;
+        let name_rva = read_u32(&data, descriptor + 4).unwrap();
+        write_u32(&mut data, descriptor, 0);
+        write_u32(&mut data, descriptor + 4, 0x8000_0000 + name_rva);
+        let pe = PE::parse(&data).unwrap();
+        assert!(dll_imports(&data, &pe).is_err());
+    }
+
+    #[test]
+    fn test_rename_imports() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo.dll");
+        write_dll(&path, &["A.dll", "b.dll"], &["c.dll"]);
+
+        let changes = HashMap::from([
+            ("a.dll".to_string(), "a-01234567.dll".to_string()),
+            ("c.dll".to_string(), "c-01234567.dll".to_string()),
+        ]);
+        rename_imports(&path, &changes).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(
+            imports(&data),
+            ["a-01234567.dll", "b.dll", "c-01234567.dll"]
+        );
+        let pe = PE::parse(&data).unwrap();
+        assert_eq!(pe.sections.len(), 2);
+        assert_eq!(pe.libraries, ["a-01234567.dll", "b.dll"]);
+    }
+
+    #[test]
+    fn test_graft_external_dlls() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let (artifact, lib_dir) = write_fixtures(temp_dir.path());
+        let ext_libs = find_external_dlls(&artifact, std::slice::from_ref(&lib_dir)).unwrap();
+        let foo_name = format!(
+            "foo-{}.dll",
+            &hash_file(lib_dir.join("foo.dll")).unwrap()[..8]
+        );
+        let bar_name = format!(
+            "Bar-{}.dll",
+            &hash_file(lib_dir.join("bar.dll")).unwrap()[..8]
+        );
+
+        let wheel = temp_dir.path().join("foo-0.1.0-py3-none-win_amd64.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        let (package, libs_dir) = (Path::new("foo"), Path::new("foo.libs"));
+        graft_external_dlls(&mut writer, libs_dir, &[(&artifact, package)], &[ext_libs]).unwrap();
+        writer.insert_into_init(
+            &package.join("__init__.py"),
+            dll_directory_hook(package, libs_dir),
+        );
+        writer
+            .add_bytes(
+                package.join("__init__.py"),
+                b"\"\"\"Docs\"\"\"\nfrom __future__ import annotations\nfrom .foo import *\n",
+            )
+            .unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(
+            imports(&fs::read(&artifact).unwrap()),
+            ["python3.dll", foo_name.as_str(), "missing.dll"]
+        );
+        let mut archive = zip::ZipArchive::new(fs::File::open(&wheel).unwrap()).unwrap();
+        let mut read = |name: &str| {
+            let mut data = Vec::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_end(&mut data)
+                .unwrap();
+            data
+        };
+        assert_eq!(
+            imports(&read(&format!("foo.libs/{}", foo_name))),
+            ["KERNEL32.dll", bar_name.as_str()]
+        );
+        assert_eq!(
+            imports(&read(&format!("foo.libs/{}", bar_name))),
+            ["kernel32.dll"]
+        );
+        let init = String::from_utf8(read("foo/__init__.py")).unwrap();
+        let expected = format!(
+            "\"\"\"Docs\"\"\"\nfrom __future__ import annotations\n{}\nfrom .foo import *\n",
+            dll_directory_hook(package, libs_dir)
+        );
+        assert_eq!(init, expected);
+        assert!(init.contains(r#"os.path.join(os.path.dirname(__file__), os.pardir, "foo.libs")"#));
+    }
+}
@@ -1,11 +1,18 @@
 mod audit;
+mod delocate;
+mod delvewheel;
 mod musllinux;
 pub mod patchelf;
 mod platform_tag;
 mod policy;
 mod repair;
+mod wheel;
 
 pub use audit::*;
+pub use delocate::{find_external_dylibs, graft_external_dylibs};
+pub use delvewheel::{dll_directory_hook, find_external_dlls, graft_external_dlls};
+pub(crate) use platform_tag::format_linux_platform_tag;
 pub use platform_tag::PlatformTag;
 pub use policy::{Policy, MANYLINUX_POLICIES, MUSLLINUX_POLICIES};
-pub use repair::find_external_libs;
+pub use repair::{find_external_libs, graft_external_libs};
+pub use wheel::{audit_wheels, repair_wheel};
@@ -1,80 +1,506 @@
+//! Editing of the dynamic section of ELF files, i.e. what we used to call the `patchelf`
+//! binary for
+//!
+//! Strings are overwritten in place in `.dynstr` when the new value fits and nothing else
+//! references the old one. Otherwise `.dynstr` (and `.dynamic` if it needs an additional entry)
+//! are moved to a new `PT_LOAD` segment at the end of the file, together with the program
+//! headers which have to grow by one for that segment.
+
 use anyhow::{bail, Context, Result};
+use goblin::container::{Container, Ctx, Endian};
+use goblin::elf::dynamic::{
+    Dyn, DT_AUDIT, DT_CONFIG, DT_DEPAUDIT, DT_NEEDED, DT_NULL, DT_RPATH, DT_RUNPATH, DT_SONAME,
+    DT_STRSZ, DT_STRTAB, DT_VERDEF, DT_VERDEFNUM, DT_VERNEED, DT_VERNEEDNUM,
+};
+use goblin::elf::program_header::{ProgramHeader, PF_R, PF_W, PT_DYNAMIC, PT_LOAD, PT_PHDR};
+use goblin::elf::section_header::{SectionHeader, SHT_DYNAMIC, SHT_STRTAB};
+use goblin::elf::{Elf, Header};
+use scroll::ctx::IntoCtx;
+use scroll::{Pread, Pwrite};
 use std::ffi::OsStr;
 use std::path::Path;
-use std::process::Command;
+
+/// Dynamic entries whose value is an offset into the dynamic string table
+const STRING_TAGS: [u64; 7] = [
+    DT_NEEDED,
+    DT_SONAME,
+    DT_RPATH,
+    DT_RUNPATH,
+    DT_CONFIG,
+    DT_DEPAUDIT,
+    DT_AUDIT,
+];
+
+/// Something that points into the dynamic string table and that we may rewrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StrRef {
+    /// The value of the dynamic entry with this index
+    Dynamic(usize),
+    /// The `vn_file` of the verneed entry with this index
+    VerneedFile(usize),
+}
+
+/// An ELF file with its dynamic section loaded for editing
+struct DynamicElf {
+    data: Vec<u8>,
+    ctx: Ctx,
+    header: Header,
+    program_headers: Vec<ProgramHeader>,
+    section_headers: Vec<SectionHeader>,
+    /// Index of the `PT_DYNAMIC` program header
+    dynamic_index: usize,
+    /// The dynamic entries, including the `DT_NULL` padding at the end
+    dynamic: Vec<Dyn>,
+    /// Number of dynamic entries that fit in the original `.dynamic`
+    dynamic_capacity: usize,
+    dynstr: Vec<u8>,
+    /// File offset of the original `.dynstr`
+    dynstr_offset: usize,
+    /// Size of the original `.dynstr`, strings beyond that have been appended
+    dynstr_capacity: usize,
+    /// File offset and value of `vn_file` for each verneed entry
+    verneed_files: Vec<(usize, u32)>,
+    /// String offsets referenced by the symbol table or symbol versions, which we never modify
+    foreign_refs: Vec<u32>,
+}
+
+impl DynamicElf {
+    fn parse(data: Vec<u8>) -> Result<Self> {
+        let elf = Elf::parse(&data).context("Failed to parse ELF file")?;
+        let ctx = Ctx::new(
+            if elf.is_64 {
+                Container::Big
+            } else {
+                Container::Little
+            },
+            if elf.little_endian {
+                Endian::Little
+            } else {
+                Endian::Big
+            },
+        );
+        let header = elf.header;
+        let program_headers = elf.program_headers.clone();
+        let section_headers = elf.section_headers.clone();
+        let mut foreign_refs: Vec<u32> = elf.dynsyms.iter().map(|sym| sym.st_name as u32).collect();
+
+        let dynamic_index = program_headers
+            .iter()
+            .position(|ph| ph.p_type == PT_DYNAMIC)
+            .context("ELF file has no dynamic section")?;
+        let dynamic_header = &program_headers[dynamic_index];
+        let dyn_size = Dyn::size(ctx.container);
+        let dynamic_capacity = dynamic_header.p_filesz as usize / dyn_size;
+        let mut dynamic = Vec::with_capacity(dynamic_capacity);
+        for i in 0..dynamic_capacity {
+            let offset = dynamic_header.p_offset as usize + i * dyn_size;
+            dynamic.push(data.pread_with::<Dyn>(offset, ctx)?);
+        }
+
+        let find_tag = |tag: u64| dynamic.iter().find(|d| d.d_tag == tag).map(|d| d.d_val);
+        let strtab = find_tag(DT_STRTAB).context("ELF file has no dynamic string table")?;
+        let dynstr_offset = vaddr_to_offset(&program_headers, strtab)?;
+        let dynstr_capacity = find_tag(DT_STRSZ).context("ELF file has no DT_STRSZ")? as usize;
+        let dynstr = data
+            .get(dynstr_offset..dynstr_offset + dynstr_capacity)
+            .context("Dynamic string table is out of bounds")?
+            .to_vec();
+
+        // Elf_Verneed and Elf_Vernaux have the same layout in 32 and 64 bit files
+        let mut verneed_files = Vec::new();
+        if let (Some(verneed), Some(count)) = (find_tag(DT_VERNEED), find_tag(DT_VERNEEDNUM)) {
+            let mut offset = vaddr_to_offset(&program_headers, verneed)?;
+            for _ in 0..count {
+                let vn_cnt: u16 = data.pread_with(offset + 2, ctx.le)?;
+                let vn_file: u32 = data.pread_with(offset + 4, ctx.le)?;
+                let vn_aux: u32 = data.pread_with(offset + 8, ctx.le)?;
+                let vn_next: u32 = data.pread_with(offset + 12, ctx.le)?;
+                verneed_files.push((offset + 4, vn_file));
+                let mut aux_offset = offset + vn_aux as usize;
+                for _ in 0..vn_cnt {
+                    foreign_refs.push(data.pread_with(aux_offset + 8, ctx.le)?);
+                    let vna_next: u32 = data.pread_with(aux_offset + 12, ctx.le)?;
+                    aux_offset += vna_next as usize;
+                }
+                offset += vn_next as usize;
+            }
+        }
+        if let (Some(verdef), Some(count)) = (find_tag(DT_VERDEF), find_tag(DT_VERDEFNUM)) {
+            let mut offset = vaddr_to_offset(&program_headers, verdef)?;
+            for _ in 0..count {
+                let vd_cnt: u16 = data.pread_with(offset + 6, ctx.le)?;
+                let vd_aux: u32 = data.pread_with(offset + 12, ctx.le)?;
+                let vd_next: u32 = data.pread_with(offset + 16, ctx.le)?;
+                let mut aux_offset = offset + vd_aux as usize;
+                for _ in 0..vd_cnt {
+                    foreign_refs.push(data.pread_with(aux_offset, ctx.le)?);
+                    let vda_next: u32 = data.pread_with(aux_offset + 4, ctx.le)?;
+                    aux_offset += vda_next as usize;
+                }
+                offset += vd_next as usize;
+            }
+        }
+
+        Ok(Self {
+            data,
+            ctx,
+            header,
+            program_headers,
+            section_headers,
+            dynamic_index,
+            dynamic,
+            dynamic_capacity,
+            dynstr,
+            dynstr_offset,
+            dynstr_capacity,
+            verneed_files,
+            foreign_refs,
+        })
+    }
+
+    fn get_string(&self, offset: u32) -> Result<&str> {
+        let bytes = self
+            .dynstr
+            .get(offset as usize..)
+            .context("String offset is out of bounds")?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        std::str::from_utf8(&bytes[..end]).context("String table entry is not valid utf-8")
+    }
+
+    fn str_ref_offset(&self, str_ref: StrRef) -> u32 {
+        match str_ref {
+            StrRef::Dynamic(index) => self.dynamic[index].d_val as u32,
+            StrRef::VerneedFile(index) => self.verneed_files[index].1,
+        }
+    }
+
+    fn set_str_ref_offset(&mut self, str_ref: StrRef, offset: u32) {
+        match str_ref {
+            StrRef::Dynamic(index) => self.dynamic[index].d_val = offset as u64,
+            StrRef::VerneedFile(index) => self.verneed_files[index].1 = offset,
+        }
+    }
+
+    /// All string references in the file that we know about, excluding the foreign ones
+    fn str_refs(&self) -> Vec<StrRef> {
+        let dynamic = self
+            .dynamic
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| STRING_TAGS.contains(&d.d_tag))
+            .map(|(index, _)| StrRef::Dynamic(index));
+        let verneed = (0..self.verneed_files.len()).map(StrRef::VerneedFile);
+        dynamic.chain(verneed).collect()
+    }
+
+    /// Points all of `targets` to a string with the value `value`
+    fn set_string(&mut self, targets: &[StrRef], value: &str) -> Result<()> {
+        if value.as_bytes().contains(&0) {
+            bail!("{:?} contains a nul byte", value);
+        }
+        let mut remaining = targets.to_vec();
+        while let Some(first) = remaining.first().copied() {
+            let old_offset = self.str_ref_offset(first);
+            let (group, rest): (Vec<_>, Vec<_>) = remaining
+                .into_iter()
+                .partition(|str_ref| self.str_ref_offset(*str_ref) == old_offset);
+            remaining = rest;
+
+            let old_len = self.get_string(old_offset)?.len();
+            let old_range = old_offset..old_offset + old_len as u32;
+            // Another string may be a suffix of the one we want to overwrite
+            let shared = self
+                .str_refs()
+                .into_iter()
+                .filter(|str_ref| !group.contains(str_ref))
+                .map(|str_ref| self.str_ref_offset(str_ref))
+                .chain(self.foreign_refs.iter().copied())
+                .any(|offset| old_range.contains(&offset));
+            let new_offset = if value.len() <= old_len && !shared {
+                let start = old_offset as usize;
+                self.dynstr[start..start + old_len].fill(0);
+                self.dynstr[start..start + value.len()].copy_from_slice(value.as_bytes());
+                old_offset
+            } else {
+                let offset = self.dynstr.len() as u32;
+                self.dynstr.extend_from_slice(value.as_bytes());
+                self.dynstr.push(0);
+                offset
+            };
+            for str_ref in group {
+                self.set_str_ref_offset(str_ref, new_offset);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a dynamic entry before the terminating `DT_NULL`
+    fn add_dynamic(&mut self, d_tag: u64) -> usize {
+        let index = self
+            .dynamic
+            .iter()
+            .position(|d| d.d_tag == DT_NULL)
+            .unwrap_or(self.dynamic.len());
+        self.dynamic.insert(index, Dyn { d_tag, d_val: 0 });
+        index
+    }
+
+    fn remove_dynamic(&mut self, tags: &[u64]) {
+        self.dynamic.retain(|d| !tags.contains(&d.d_tag));
+    }
+
+    /// Writes the changes back, moving `.dynstr` and `.dynamic` to a new segment if they
+    /// outgrew their original location
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let ctx = self.ctx;
+        let dyn_size = Dyn::size(ctx.container);
+        while self.dynamic.last().map(|d| d.d_tag) == Some(DT_NULL) {
+            self.dynamic.pop();
+        }
+        self.dynamic.push(Dyn {
+            d_tag: DT_NULL,
+            d_val: 0,
+        });
+        let move_dynstr = self.dynstr.len() > self.dynstr_capacity;
+        let move_dynamic = self.dynamic.len() > self.dynamic_capacity;
+
+        let mut dynstr_offset = self.dynstr_offset;
+        let mut dynamic_offset = self.program_headers[self.dynamic_index].p_offset as usize;
+        if move_dynstr || move_dynamic {
+            let align = self
+                .program_headers
+                .iter()
+                .filter(|ph| ph.p_type == PT_LOAD)
+                .map(|ph| ph.p_align)
+                .max()
+                .unwrap_or(0)
+                .max(0x1000);
+            let offset = align_up(self.data.len() as u64, align);
+            let vaddr = align_up(
+                self.program_headers
+                    .iter()
+                    .filter(|ph| ph.p_type == PT_LOAD)
+                    .map(|ph| ph.p_vaddr + ph.p_memsz)
+                    .max()
+                    .unwrap_or(0),
+                align,
+            );
+
+            // Lay out the new segment: program headers, then strings, then dynamic entries
+            let phdrs_size = (self.program_headers.len() + 1) * ProgramHeader::size(ctx);
+            let mut size = phdrs_size as u64;
+            if move_dynstr {
+                dynstr_offset = (offset + size) as usize;
+                let strtab = vaddr + size;
+                for d in &mut self.dynamic {
+                    match d.d_tag {
+                        DT_STRTAB => d.d_val = strtab,
+                        DT_STRSZ => d.d_val = self.dynstr.len() as u64,
+                        _ => {}
+                    }
+                }
+                for sh in &mut self.section_headers {
+                    if sh.sh_type == SHT_STRTAB && sh.sh_offset as usize == self.dynstr_offset {
+                        sh.sh_offset = offset + size;
+                        sh.sh_addr = strtab;
+                        sh.sh_size = self.dynstr.len() as u64;
+                    }
+                }
+                size += self.dynstr.len() as u64;
+            }
+            if move_dynamic {
+                size = align_up(size, 8);
+                let old_offset = dynamic_offset as u64;
+                dynamic_offset = (offset + size) as usize;
+                let dynamic_size = (self.dynamic.len() * dyn_size) as u64;
+                let ph = &mut self.program_headers[self.dynamic_index];
+                ph.p_offset = offset + size;
+                ph.p_vaddr = vaddr + size;
+                ph.p_paddr = vaddr + size;
+                ph.p_filesz = dynamic_size;
+                ph.p_memsz = dynamic_size;
+                for sh in &mut self.section_headers {
+                    if sh.sh_type == SHT_DYNAMIC && sh.sh_offset == old_offset {
+                        sh.sh_offset = offset + size;
+                        sh.sh_addr = vaddr + size;
+                        sh.sh_size = dynamic_size;
+                    }
+                }
+                size += dynamic_size;
+            }
+
+            for ph in &mut self.program_headers {
+                if ph.p_type == PT_PHDR {
+                    ph.p_offset = offset;
+                    ph.p_vaddr = vaddr;
+                    ph.p_paddr = vaddr;
+                    ph.p_filesz = phdrs_size as u64;
+                    ph.p_memsz = phdrs_size as u64;
+                }
+            }
+            // Loadable segments must be sorted by their virtual address
+            let position = self
+                .program_headers
+                .iter()
+                .rposition(|ph| ph.p_type == PT_LOAD)
+                .map_or(self.program_headers.len(), |index| index + 1);
+            self.program_headers.insert(
+                position,
+                ProgramHeader {
+                    p_type: PT_LOAD,
+                    p_flags: PF_R | PF_W,
+                    p_offset: offset,
+                    p_vaddr: vaddr,
+                    p_paddr: vaddr,
+                    p_filesz: size,
+                    p_memsz: size,
+                    p_align: align,
+                },
+            );
+            if position <= self.dynamic_index {
+                self.dynamic_index += 1;
+            }
+            self.header.e_phoff = offset;
+            self.header.e_phnum += 1;
+            self.data.resize((offset + size) as usize, 0);
+        } else {
+            self.dynamic.resize(
+                self.dynamic_capacity,
+                Dyn {
+                    d_tag: DT_NULL,
+                    d_val: 0,
+                },
+            );
+        }
+
+        let data = &mut self.data;
+        data[dynstr_offset..dynstr_offset + self.dynstr.len()].copy_from_slice(&self.dynstr);
+        for (i, d) in self.dynamic.into_iter().enumerate() {
+            data.pwrite_with(d, dynamic_offset + i * dyn_size, ctx)?;
+        }
+        for (offset, vn_file) in self.verneed_files {
+            data.pwrite_with(vn_file, offset, ctx.le)?;
+        }
+        let phdr_size = ProgramHeader::size(ctx);
+        for (i, ph) in self.program_headers.into_iter().enumerate() {
+            data.pwrite_with(ph, self.header.e_phoff as usize + i * phdr_size, ctx)?;
+        }
+        let shdr_size = SectionHeader::size(ctx);
+        for (i, sh) in self.section_headers.into_iter().enumerate() {
+            data.pwrite_with(sh, self.header.e_shoff as usize + i * shdr_size, ctx)?;
+        }
+        self.header.into_ctx(data, ctx);
+        Ok(self.data)
+    }
+}
+
+fn align_up(value: u64, align: u64) -> u64 {
+    (value + align - 1) / align * align
+}
+
+fn vaddr_to_offset(program_headers: &[ProgramHeader], vaddr: u64) -> Result<usize> {
+    program_headers
+        .iter()
+        .find(|ph| ph.p_type == PT_LOAD && ph.p_vaddr <= vaddr && vaddr < ph.p_vaddr + ph.p_filesz)
+        .map(|ph| (ph.p_offset + vaddr - ph.p_vaddr) as usize)
+        .with_context(|| format!("Address {:#x} isn't part of any segment", vaddr))
+}
+
+/// Reads an ELF file, applies `edit` to its dynamic section and writes it back
+fn modify_elf(file: &Path, edit: impl FnOnce(&mut DynamicElf) -> Result<()>) -> Result<()> {
+    let data = fs_err::read(file)?;
+    let mut elf = DynamicElf::parse(data)
+        .with_context(|| format!("Failed to parse ELF file at '{}'", file.display()))?;
+    edit(&mut elf)?;
+    let data = elf
+        .finish()
+        .with_context(|| format!("Failed to write ELF file at '{}'", file.display()))?;
+    fs_err::write(file, data)?;
+    Ok(())
+}
+
+fn os_str_to_str(value: &OsStr) -> Result<&str> {
+    value
+        .to_str()
+        .with_context(|| format!("{:?} is not valid utf-8", value))
+}
 
 /// Replace a declared dependency on a dynamic library with another one (`DT_NEEDED`)
 pub fn replace_needed<O: AsRef<OsStr>, N: AsRef<OsStr>>(
     file: impl AsRef<Path>,
     old_new_pairs: &[(O, N)],
 ) -> Result<()> {
-    let mut cmd = Command::new("patchelf");
-    for (old, new) in old_new_pairs {
-        cmd.arg("--replace-needed").arg(old).arg(new);
-    }
-    cmd.arg(file.as_ref());
-    let output = cmd
-        .output()
-        .context("Failed to execute 'patchelf', did you install it? Hint: Try `pip install maturin[patchelf]` (or just `pip install patchelf`)")?;
-    if !output.status.success() {
-        bail!(
-            "patchelf --replace-needed failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
-    Ok(())
+    modify_elf(file.as_ref(), |elf| {
+        for (old, new) in old_new_pairs {
+            let old = os_str_to_str(old.as_ref())?;
+            let new = os_str_to_str(new.as_ref())?;
+            let mut targets = Vec::new();
+            for str_ref in elf.str_refs() {
+                let is_needed = match str_ref {
+                    StrRef::Dynamic(index) => elf.dynamic[index].d_tag == DT_NEEDED,
+                    // The version requirements name the library they're from, too
+                    StrRef::VerneedFile(_) => true,
+                };
+                if is_needed && elf.get_string(elf.str_ref_offset(str_ref))? == old {
+                    targets.push(str_ref);
+                }
+            }
+            elf.set_string(&targets, new)?;
+        }
+        Ok(())
+    })
 }
 
 /// Change `SONAME` of a dynamic library
 pub fn set_soname<S: AsRef<OsStr>>(file: impl AsRef<Path>, soname: &S) -> Result<()> {
-    let mut cmd = Command::new("patchelf");
-    cmd.arg("--set-soname").arg(soname).arg(file.as_ref());
-    let output = cmd
-        .output()
-        .context("Failed to execute 'patchelf', did you install it?")?;
-    if !output.status.success() {
-        bail!(
-            "patchelf --set-soname failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
-    Ok(())
+    let soname = os_str_to_str(soname.as_ref())?;
+    modify_elf(file.as_ref(), |elf| {
+        let index = match elf.dynamic.iter().position(|d| d.d_tag == DT_SONAME) {
+            Some(index) => index,
+            None => elf.add_dynamic(DT_SONAME),
+        };
+        elf.set_string(&[StrRef::Dynamic(index)], soname)
+    })
 }
 
 /// Remove a `RPATH` from executables and libraries
 pub fn remove_rpath(file: impl AsRef<Path>) -> Result<()> {
-    let mut cmd = Command::new("patchelf");
-    cmd.arg("--remove-rpath").arg(file.as_ref());
-    let output = cmd
-        .output()
-        .context("Failed to execute 'patchelf', did you install it?")?;
-    if !output.status.success() {
-        bail!(
-            "patchelf --remove-rpath failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
-    }
-    Ok(())
+    modify_elf(file.as_ref(), |elf| {
+        elf.remove_dynamic(&[DT_RPATH, DT_RUNPATH]);
+        Ok(())
+    })
 }
 
 /// Change the `RPATH` of executables and libraries
+///
+/// A `RUNPATH` is converted to a `RPATH`, so that it also applies to the dependencies.
+/// An empty `rpath` removes it.
 pub fn set_rpath<S: AsRef<OsStr>>(file: impl AsRef<Path>, rpath: &S) -> Result<()> {
-    remove_rpath(&file)?;
-    let mut cmd = Command::new("patchelf");
-    cmd.arg("--force-rpath")
-        .arg("--set-rpath")
-        .arg(rpath)
-        .arg(file.as_ref());
-    let output = cmd
-        .output()
-        .context("Failed to execute 'patchelf', did you install it?")?;
-    if !output.status.success() {
-        bail!(
-            "patchelf --set-rpath failed: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+    let rpath = os_str_to_str(rpath.as_ref())?;
+    if rpath.is_empty() {
+        return remove_rpath(file);
     }
-    Ok(())
+    modify_elf(file.as_ref(), |elf| {
+        let existing = elf
+            .dynamic
+            .iter()
+            .position(|d| d.d_tag == DT_RPATH || d.d_tag == DT_RUNPATH);
+        let index = match existing {
+            Some(index) => {
+                elf.dynamic[index].d_tag = DT_RPATH;
+                // Drop any further entries, only the first one would be used
+                let (before, after) = elf.dynamic.split_at(index + 1);
+                let after = after
+                    .iter()
+                    .filter(|d| d.d_tag != DT_RPATH && d.d_tag != DT_RUNPATH)
+                    .cloned();
+                elf.dynamic = before.iter().cloned().chain(after).collect();
+                index
+            }
+            None => elf.add_dynamic(DT_RPATH),
+        };
+        elf.set_string(&[StrRef::Dynamic(index)], rpath)
+    })
 }
 
 /// Get the `RPATH` of executables and libraries
@@ -94,3 +520,110 @@ pub fn get_rpath(file: impl AsRef<Path>) -> Result<Vec<String>> {
         Err(e) => bail!("Failed to parse ELF file at '{}': {}", file.display(), e),
     }
 }
+
+#[cfg(test)]
+#[cfg(target_os = "linux")]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Copies the test binary, which is a dynamically linked ELF file with symbol versions
+    fn copy_test_binary(dir: &Path) -> PathBuf {
+        let path = dir.join("binary");
+        fs_err::copy(std::env::current_exe().unwrap(), &path).unwrap();
+        path
+    }
+
+    fn parse(path: &Path, check: impl FnOnce(&Elf)) {
+        let data = fs_err::read(path).unwrap();
+        check(&Elf::parse(&data).unwrap())
+    }
+
+    #[test]
+    fn test_set_soname_and_rpath() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = copy_test_binary(tmp_dir.path());
+        let mut libraries = Vec::new();
+        parse(&path, |elf| {
+            libraries = elf.libraries.iter().map(ToString::to_string).collect();
+        });
+
+        set_soname(&path, &"libfoo-0123abcd.so").unwrap();
+        set_rpath(&path, &"$ORIGIN/../foo.libs").unwrap();
+        parse(&path, |elf| {
+            assert_eq!(elf.soname, Some("libfoo-0123abcd.so"));
+            assert_eq!(elf.rpaths, ["$ORIGIN/../foo.libs"]);
+            assert!(elf.runpaths.is_empty());
+            assert_eq!(elf.libraries, libraries);
+        });
+        assert_eq!(get_rpath(&path).unwrap(), ["$ORIGIN/../foo.libs"]);
+
+        // Shorter values are written in place
+        let len = fs_err::metadata(&path).unwrap().len();
+        set_soname(&path, &"libfoo.so").unwrap();
+        set_rpath(&path, &"$ORIGIN").unwrap();
+        assert_eq!(fs_err::metadata(&path).unwrap().len(), len);
+        parse(&path, |elf| {
+            assert_eq!(elf.soname, Some("libfoo.so"));
+            assert_eq!(elf.rpaths, ["$ORIGIN"]);
+        });
+
+        set_rpath(&path, &"").unwrap();
+        assert!(get_rpath(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace_needed() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = copy_test_binary(tmp_dir.path());
+
+        replace_needed(&path, &[("libc.so.6", "libc-0123abcd.so.6")]).unwrap();
+        parse(&path, |elf| {
+            assert!(elf.libraries.contains(&"libc-0123abcd.so.6"));
+            assert!(!elf.libraries.contains(&"libc.so.6"));
+            // The version requirements must point to the renamed library, too
+            let verneed = elf.verneed.as_ref().unwrap();
+            let files: Vec<_> = verneed
+                .iter()
+                .map(|need| elf.dynstrtab.get_at(need.vn_file).unwrap())
+                .collect();
+            assert!(files.contains(&"libc-0123abcd.so.6"));
+            assert!(!files.contains(&"libc.so.6"));
+            // Symbol names are untouched
+            assert!(elf
+                .dynsyms
+                .iter()
+                .any(|sym| elf.dynstrtab.get_at(sym.st_name) == Some("malloc")));
+        });
+    }
+
+    #[test]
+    fn test_grown_binary_runs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = copy_test_binary(tmp_dir.path());
+        let mut phnum = 0;
+        parse(&path, |elf| phnum = elf.program_headers.len());
+
+        // Too long to fit into `.dynstr`, and new dynamic entries don't fit into `.dynamic`,
+        // so both are moved to a new segment
+        let rpath = format!("$ORIGIN/{}", "x".repeat(4096));
+        set_soname(&path, &"libfoo-0123abcd.so").unwrap();
+        set_rpath(&path, &rpath).unwrap();
+        parse(&path, |elf| {
+            assert!(elf.program_headers.len() > phnum);
+            assert_eq!(elf.rpaths, [rpath.as_str()]);
+        });
+
+        // The dynamic loader must accept the rewritten program headers and dynamic section
+        let output = std::process::Command::new(&path)
+            .args(["--list", "test_grown_binary_runs"])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("test_grown_binary_runs"));
+    }
+}
@@ -4,7 +4,7 @@ use std::fmt;
 use std::str::FromStr;
 
 /// Decides how to handle manylinux and musllinux compliance
-#[derive(Serialize, Debug, Clone, Eq, PartialEq, Copy, Ord, PartialOrd)]
+#[derive(Serialize, Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum PlatformTag {
     /// Use the manylinux_x_y tag
     Manylinux {
@@ -22,6 +22,8 @@ pub enum PlatformTag {
     },
     /// Use the native linux tag
     Linux,
+    /// Use the tag of a custom auditwheel policy, see `--auditwheel-policy`
+    Custom(String),
 }
 
 impl PlatformTag {
@@ -52,12 +54,13 @@ impl PlatformTag {
             }
             PlatformTag::Musllinux { .. } => Vec::new(),
             PlatformTag::Linux => Vec::new(),
+            PlatformTag::Custom(_) => Vec::new(),
         }
     }
 
     /// Is this a portable linux platform tag
     ///
-    /// Only manylinux and musllinux are portable
+    /// Only manylinux, musllinux and custom policies are portable
     pub fn is_portable(&self) -> bool {
         !matches!(self, PlatformTag::Linux)
     }
@@ -78,16 +81,33 @@ impl PlatformTag {
             PlatformTag::Manylinux { x, y } => (*x, *y) >= (2, 17),
             PlatformTag::Musllinux { .. } => true,
             PlatformTag::Linux => true,
+            PlatformTag::Custom(_) => true,
         }
     }
 }
 
+/// Formats the platform part of a linux wheel filename, listing the aliases after each tag,
+/// e.g. `manylinux_2_17_x86_64.manylinux2014_x86_64`
+pub(crate) fn format_linux_platform_tag(platform_tags: &[PlatformTag], arch: &str) -> String {
+    let mut platform_tags = platform_tags.to_vec();
+    platform_tags.sort();
+    let mut tags = vec![];
+    for platform_tag in platform_tags {
+        tags.push(format!("{}_{}", platform_tag, arch));
+        for alias in platform_tag.aliases() {
+            tags.push(format!("{}_{}", alias, arch));
+        }
+    }
+    tags.join(".")
+}
+
 impl fmt::Display for PlatformTag {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
+        match self {
             PlatformTag::Manylinux { x, y } => write!(f, "manylinux_{}_{}", x, y),
             PlatformTag::Musllinux { x, y } => write!(f, "musllinux_{}_{}", x, y),
             PlatformTag::Linux => write!(f, "linux"),
+            PlatformTag::Custom(name) => write!(f, "{}", name),
         }
     }
 }
@@ -114,6 +134,14 @@ impl FromStr for PlatformTag {
                         .and_then(|y| y.parse::<u16>().ok())
                         .ok_or("invalid musllinux option")?;
                     Ok(PlatformTag::Musllinux { x, y })
+                } else if !value.starts_with("manylinux")
+                    && !value.starts_with(|c: char| c.is_ascii_digit())
+                {
+                    if is_custom_tag_name(&value) {
+                        Ok(PlatformTag::Custom(value))
+                    } else {
+                        Err("invalid platform tag")
+                    }
                 } else {
                     let value = value.strip_prefix("manylinux_").unwrap_or(&value);
                     let mut parts = value.split('_');
@@ -132,6 +160,15 @@ impl FromStr for PlatformTag {
     }
 }
 
+/// Whether `name` can be used as the tag of a custom policy, which like all platform tags
+/// consists of lowercase letters, digits and underscores
+pub(crate) fn is_custom_tag_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
 impl<'de> Deserialize<'de> for PlatformTag {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
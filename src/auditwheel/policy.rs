@@ -1,11 +1,15 @@
+use super::platform_tag::is_custom_tag_name;
 use crate::auditwheel::PlatformTag;
 use crate::target::Arch;
+use anyhow::{bail, Context, Result};
+use fs_err as fs;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use std::cmp::{Ordering, PartialOrd};
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::path::Path;
 
 /// The policies (allowed symbols) for the different manylinux tags, sorted from highest
 /// priority to lowest
@@ -33,6 +37,7 @@ pub struct Policy {
     /// platform tag name
     pub name: String,
     /// platform tag aliases
+    #[serde(default)]
     pub aliases: Vec<String>,
     /// policy priority. Tags supporting more platforms have higher priority
     pub priority: i64,
@@ -43,6 +48,7 @@ pub struct Policy {
     #[serde(rename = "lib_whitelist")]
     pub lib_whitelist: HashSet<String>,
     /// blacklisted symbols of whitelisted libraries
+    #[serde(default)]
     pub blacklist: HashMap<String, HashSet<String>>,
 }
 
@@ -92,6 +98,48 @@ impl Policy {
             .cloned()
     }
 
+    /// Reads custom policies from a json file in the format of auditwheel's policy files,
+    /// which contains either a list of policies or a single one
+    ///
+    /// A custom policy with the name of a built-in policy replaces it.
+    pub fn from_file(path: &Path) -> Result<Vec<Self>> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum PolicyFile {
+            Many(Vec<Policy>),
+            One(Policy),
+        }
+
+        let contents = fs::read(path)?;
+        let policies = match serde_json::from_slice(&contents)
+            .with_context(|| format!("Invalid auditwheel policy file {}", path.display()))?
+        {
+            PolicyFile::Many(policies) => policies,
+            PolicyFile::One(policy) => vec![policy],
+        };
+        for policy in &policies {
+            if policy.name == "linux" || !is_custom_tag_name(&policy.name) {
+                bail!(
+                    "Invalid policy name '{}' in {}, it must be a platform tag other than linux \
+                    consisting of lowercase letters, digits and underscores",
+                    policy.name,
+                    path.display()
+                );
+            }
+        }
+        Ok(policies)
+    }
+
+    /// Get policy by it's platform tag name, preferring the given custom policies over the
+    /// built-in ones
+    pub(crate) fn from_name_with_custom(name: &str, custom_policies: &[Policy]) -> Option<Self> {
+        custom_policies
+            .iter()
+            .find(|p| p.name == name || p.aliases.iter().any(|alias| alias == name))
+            .cloned()
+            .or_else(|| Self::from_name(name))
+    }
+
     pub(crate) fn fixup_musl_libc_so_name(&mut self, target_arch: Arch) {
         // Fixup musl libc lib_whitelist
         if self.name.starts_with("musllinux") && self.lib_whitelist.remove("libc.so") {
@@ -116,6 +164,8 @@ impl Policy {
 #[cfg(test)]
 mod test {
     use super::{Arch, Policy, MANYLINUX_POLICIES, MUSLLINUX_POLICIES};
+    use crate::auditwheel::PlatformTag;
+    use fs_err as fs;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -154,4 +204,40 @@ mod test {
         policy.fixup_musl_libc_so_name(Arch::Aarch64);
         assert!(policy.lib_whitelist.contains("libc.musl-aarch64.so.1"));
     }
+
+    #[test]
+    fn test_custom_policy_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("policy.json");
+        fs::write(
+            &path,
+            r#"{
+                "name": "acme_2_28",
+                "priority": 70,
+                "symbol_versions": {"x86_64": {"GLIBC": ["2.2.5", "2.28"]}},
+                "lib_whitelist": ["libc.so.6", "libacme.so.1"]
+            }"#,
+        )
+        .unwrap();
+        let policies = Policy::from_file(&path).unwrap();
+        assert_eq!(policies.len(), 1);
+        assert!(policies[0].lib_whitelist.contains("libacme.so.1"));
+        assert_eq!(
+            policies[0].platform_tag(),
+            PlatformTag::Custom("acme_2_28".to_string())
+        );
+
+        let policy = Policy::from_name_with_custom("acme_2_28", &policies).unwrap();
+        assert_eq!(policy.priority, 70);
+        let policy = Policy::from_name_with_custom("manylinux2014", &policies).unwrap();
+        assert_eq!(policy.name, "manylinux_2_17");
+
+        // Custom policies need a valid platform tag
+        fs::write(
+            &path,
+            r#"[{"name": "Acme-2.28", "priority": 70, "symbol_versions": {}, "lib_whitelist": []}]"#,
+        )
+        .unwrap();
+        assert!(Policy::from_file(&path).is_err());
+    }
 }
@@ -1,7 +1,12 @@
-use super::audit::AuditWheelError;
+use super::audit::{relpath, AuditWheelError};
+use super::patchelf;
 use crate::auditwheel::Policy;
-use anyhow::Result;
-use lddtree::DependencyAnalyzer;
+use crate::build_context::hash_file;
+use crate::module_writer::{ModuleWriter, WheelWriter};
+use anyhow::{Context, Result};
+use fs_err as fs;
+use lddtree::{DependencyAnalyzer, Library};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 pub fn find_external_libs(
@@ -32,3 +37,167 @@ pub fn find_external_libs(
     }
     Ok(ext_libs)
 }
+
+/// Copies the external libraries into `libs_dir` inside of the wheel, renaming them with a
+/// short hash of their content, and patches the artifacts to load them from there
+///
+/// `artifacts` are pairs of the path of the artifact on disk and the directory it is placed in
+/// inside of the wheel, `ext_libs` are the external libraries of each artifact.
+pub fn graft_external_libs(
+    writer: &mut WheelWriter,
+    libs_dir: &Path,
+    artifacts: &[(&Path, &Path)],
+    ext_libs: &[Vec<Library>],
+) -> Result<()> {
+    writer.add_directory(libs_dir)?;
+
+    let temp_dir = tempfile::tempdir()?;
+    let mut soname_map = HashMap::new();
+    let mut libs_copied = HashSet::new();
+    for lib in ext_libs.iter().flatten() {
+        let lib_path = lib.realpath.clone().with_context(|| {
+            format!(
+                "Cannot repair wheel, because required library {} could not be located.",
+                lib.path.display()
+            )
+        })?;
+        // Generate a new soname with a short hash
+        let short_hash = &hash_file(&lib_path)?[..8];
+        let (file_stem, file_ext) = lib.name.split_once('.').unwrap();
+        let new_soname = if !file_stem.ends_with(&format!("-{}", short_hash)) {
+            format!("{}-{}.{}", file_stem, short_hash, file_ext)
+        } else {
+            format!("{}.{}", file_stem, file_ext)
+        };
+
+        // Copy the original lib to a tmpdir and modify some of its properties
+        // for example soname and rpath
+        let dest_path = temp_dir.path().join(&new_soname);
+        fs::copy(&lib_path, &dest_path)?;
+        libs_copied.insert(lib_path);
+
+        patchelf::set_soname(&dest_path, &new_soname)?;
+        if !lib.rpath.is_empty() || !lib.runpath.is_empty() {
+            patchelf::set_rpath(&dest_path, &libs_dir)?;
+        }
+        soname_map.insert(
+            lib.name.clone(),
+            (new_soname.clone(), dest_path.clone(), lib.needed.clone()),
+        );
+    }
+
+    for ((artifact, _), artifact_ext_libs) in artifacts.iter().zip(ext_libs) {
+        let artifact_deps: HashSet<_> = artifact_ext_libs.iter().map(|lib| &lib.name).collect();
+        let replacements = soname_map
+            .iter()
+            .filter_map(|(k, v)| {
+                if artifact_deps.contains(k) {
+                    Some((k, v.0.clone()))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        if !replacements.is_empty() {
+            patchelf::replace_needed(artifact, &replacements[..])?;
+        }
+    }
+
+    // we grafted in a bunch of libraries and modified their sonames, but
+    // they may have internal dependencies (DT_NEEDED) on one another, so
+    // we need to update those records so each now knows about the new
+    // name of the other.
+    for (new_soname, path, needed) in soname_map.values() {
+        let mut replacements = Vec::new();
+        for n in needed {
+            if soname_map.contains_key(n) {
+                replacements.push((n, soname_map[n].0.clone()));
+            }
+        }
+        if !replacements.is_empty() {
+            patchelf::replace_needed(path, &replacements[..])?;
+        }
+        writer.add_file_with_permissions(libs_dir.join(new_soname), path, 0o755)?;
+    }
+
+    println!(
+        "🖨  Copied external shared libraries to package {} directory:",
+        libs_dir.display()
+    );
+    for lib_path in libs_copied {
+        println!("    {}", lib_path.display());
+    }
+
+    for (artifact, artifact_dir) in artifacts {
+        let mut new_rpaths = patchelf::get_rpath(artifact)?;
+        // TODO: clean existing rpath entries if it's not pointed to a location within the wheel
+        // See https://github.com/pypa/auditwheel/blob/353c24250d66951d5ac7e60b97471a6da76c123f/src/auditwheel/repair.py#L160
+        let new_rpath = Path::new("$ORIGIN").join(relpath(libs_dir, artifact_dir));
+        new_rpaths.push(new_rpath.to_str().unwrap().to_string());
+        let new_rpath = new_rpaths.join(":");
+        patchelf::set_rpath(artifact, &new_rpath)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::build_context::ExtensionModule;
+    use crate::{BridgeModel, Metadata21};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_graft_external_libs_for_extension_module() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        // The test binary is a dynamically linked ELF file that needs libc
+        let artifact = tmp_dir.path().join("_codec.so");
+        fs::copy(std::env::current_exe().unwrap(), &artifact).unwrap();
+        let lib_path = tmp_dir.path().join("libc.so.6");
+        fs::copy(std::env::current_exe().unwrap(), &lib_path).unwrap();
+        let lib = Library {
+            name: "libc.so.6".to_string(),
+            path: lib_path.clone(),
+            realpath: Some(lib_path.clone()),
+            needed: Vec::new(),
+            rpath: Vec::new(),
+            runpath: Vec::new(),
+        };
+        let module = ExtensionModule {
+            crate_name: "codec".to_string(),
+            module: "pkg.codec._codec".to_string(),
+            manifest_path: PathBuf::from("codec/Cargo.toml"),
+            bridge: BridgeModel::Cffi,
+        };
+        let module_dir = module.wheel_dir();
+        assert_eq!(module_dir, Path::new("pkg/codec"));
+
+        let metadata21 = Metadata21 {
+            name: "pkg".to_string(),
+            version: "0.1.0".to_string(),
+            ..Default::default()
+        };
+        let tags = ["py3-none-linux_x86_64".to_string()];
+        let mut writer = WheelWriter::new(&tags[0], tmp_dir.path(), &metadata21, &tags).unwrap();
+        graft_external_libs(
+            &mut writer,
+            Path::new("pkg.libs"),
+            &[(artifact.as_path(), module_dir.as_path())],
+            &[vec![lib]],
+        )
+        .unwrap();
+        let wheel = writer.finish().unwrap();
+
+        let new_soname = format!("libc-{}.so.6", &hash_file(&lib_path).unwrap()[..8]);
+        assert_eq!(
+            patchelf::get_rpath(&artifact).unwrap(),
+            ["$ORIGIN/../../pkg.libs"]
+        );
+        let data = fs::read(&artifact).unwrap();
+        let elf = goblin::elf::Elf::parse(&data).unwrap();
+        assert!(elf.libraries.contains(&new_soname.as_str()));
+        let archive = zip::ZipArchive::new(fs::File::open(wheel).unwrap()).unwrap();
+        let files: BTreeSet<_> = archive.file_names().collect();
+        assert!(files.contains(format!("pkg.libs/{}", new_soname).as_str()));
+    }
+}
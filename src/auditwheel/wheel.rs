@@ -0,0 +1,574 @@
+use super::audit::{audit_elf, AuditWheelError, ElfAudit};
+use super::platform_tag::format_linux_platform_tag;
+use super::policy::{Policy, MANYLINUX_POLICIES, MUSLLINUX_POLICIES};
+use super::repair::{find_external_libs, graft_external_libs};
+use super::PlatformTag;
+use crate::module_writer::{ModuleWriter, WheelWriter};
+use crate::target::Arch;
+use anyhow::{anyhow, bail, Context, Result};
+use fs_err as fs;
+use fs_err::File;
+use goblin::elf::{header, Elf};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The audit result of a single elf file inside a wheel
+#[derive(Debug)]
+pub struct WheelElfAudit {
+    /// Path of the elf file relative to the wheel root
+    pub path: PathBuf,
+    /// The architecture of the elf file
+    pub arch: Arch,
+    /// Whether the elf file was linked against musl libc
+    pub musl: bool,
+    /// The audit of the elf file, where libraries that are already bundled in the wheel don't
+    /// count as external
+    pub audit: ElfAudit,
+}
+
+impl WheelElfAudit {
+    /// The policy the elf file satisfies as it is, i.e. `linux` if it still needs external
+    /// libraries
+    pub fn effective_policy(&self) -> Policy {
+        if self.audit.external_libs.is_empty() {
+            self.audit.policy.clone()
+        } else {
+            Policy::default()
+        }
+    }
+}
+
+/// Maps the elf machine to the architecture used in the platform tags
+fn elf_arch(elf: &Elf) -> Option<Arch> {
+    let arch = match elf.header.e_machine {
+        header::EM_X86_64 => Arch::X86_64,
+        header::EM_386 => Arch::X86,
+        header::EM_AARCH64 => Arch::Aarch64,
+        header::EM_ARM => Arch::Armv7L,
+        header::EM_PPC => Arch::Powerpc,
+        header::EM_PPC64 if elf.little_endian => Arch::Powerpc64Le,
+        header::EM_PPC64 => Arch::Powerpc64,
+        header::EM_S390 => Arch::S390X,
+        header::EM_RISCV => Arch::Riscv64,
+        header::EM_MIPS if elf.is_64 => Arch::Mips64el,
+        header::EM_MIPS => Arch::Mipsel,
+        header::EM_SPARCV9 => Arch::Sparc64,
+        _ => return None,
+    };
+    Some(arch)
+}
+
+/// musl binaries either use the musl loader or link musl's `libc.so`, while glibc binaries
+/// link `libc.so.6`
+fn is_musl_elf(elf: &Elf) -> bool {
+    elf.interpreter
+        .map(|interp| interp.contains("ld-musl"))
+        .unwrap_or(false)
+        || elf.libraries.iter().any(|lib| {
+            *lib == "libc.so" || lib.starts_with("libc.musl-") || lib.starts_with("ld-musl-")
+        })
+}
+
+/// Returns the policies claimed by the platform tags in the wheel filename,
+/// e.g. `manylinux_2_17` for `..-manylinux_2_17_x86_64.manylinux2014_x86_64.whl`
+///
+/// Aliases are merged, `custom_policies` take precedence over the built-in policies and tags
+/// that don't belong to linux are ignored
+pub fn claimed_policies(wheel_name: &str, custom_policies: &[Policy]) -> Vec<Policy> {
+    let stem = wheel_name.strip_suffix(".whl").unwrap_or(wheel_name);
+    let platform = match stem.rsplit('-').next() {
+        Some(platform) => platform,
+        None => return Vec::new(),
+    };
+    let mut claimed = Vec::new();
+    for tag in platform.split('.') {
+        let policy = custom_policies
+            .iter()
+            .chain(MANYLINUX_POLICIES.iter())
+            .chain(MUSLLINUX_POLICIES.iter())
+            .find(|policy| {
+                std::iter::once(&policy.name)
+                    .chain(policy.aliases.iter())
+                    .any(|name| {
+                        tag.strip_prefix(name.as_str())
+                            .map(|arch| arch.starts_with('_'))
+                            .unwrap_or(false)
+                    })
+            });
+        if let Some(policy) = policy.filter(|policy| !claimed.contains(*policy)) {
+            claimed.push(policy.clone());
+        }
+    }
+    claimed
+}
+
+/// Extracts the wheel into `target_dir`, keeping its layout so that rpaths such as
+/// `$ORIGIN/../foo.libs` resolve to the libraries bundled in the wheel
+///
+/// Returns the relative paths and permissions of the extracted files
+fn unpack_wheel(wheel: &Path, target_dir: &Path) -> Result<Vec<(PathBuf, u32)>> {
+    let mut archive = zip::ZipArchive::new(File::open(wheel)?)
+        .with_context(|| format!("Failed to read {} as zip archive", wheel.display()))?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let relative = match file.enclosed_name() {
+            Some(relative) => relative.to_path_buf(),
+            None => bail!(
+                "{} contains an invalid path: {}",
+                wheel.display(),
+                file.name()
+            ),
+        };
+        if file.is_dir() {
+            continue;
+        }
+        let path = target_dir.join(&relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut file, &mut File::create(&path)?)?;
+        files.push((relative, file.unix_mode().unwrap_or(0o644)));
+    }
+    Ok(files)
+}
+
+/// Audits all elf files inside of a wheel against the manylinux and musllinux policies and
+/// `custom_policies`
+pub fn audit_wheel(wheel: &Path, custom_policies: &[Policy]) -> Result<Vec<WheelElfAudit>> {
+    let unpacked = tempfile::tempdir()?;
+    let files = unpack_wheel(wheel, unpacked.path())?;
+    audit_unpacked_wheel(unpacked.path(), &files, custom_policies)
+}
+
+/// Audits the elf files among `files` of a wheel that was extracted to `unpacked`
+fn audit_unpacked_wheel(
+    unpacked: &Path,
+    files: &[(PathBuf, u32)],
+    custom_policies: &[Policy],
+) -> Result<Vec<WheelElfAudit>> {
+    let root = unpacked.canonicalize()?;
+    let mut audits = Vec::new();
+    for (relative, _) in files {
+        let path = unpacked.join(relative);
+        let buffer = fs::read(&path)?;
+        if !buffer.starts_with(b"\x7fELF") {
+            continue;
+        }
+        let elf = Elf::parse(&buffer)
+            .with_context(|| format!("Failed to parse {} as elf file", relative.display()))?;
+        let arch = match elf_arch(&elf) {
+            Some(arch) => arch,
+            None => bail!(
+                "{} has an unsupported architecture ({})",
+                relative.display(),
+                header::machine_to_str(elf.header.e_machine)
+            ),
+        };
+        let musl = is_musl_elf(&elf);
+        let mut audit = audit_elf(&path, arch, musl, Vec::new(), custom_policies)
+            .with_context(|| format!("Failed to audit {}", relative.display()))?;
+        // Libraries that were already grafted into the wheel aren't external
+        audit.external_libs.retain(|lib| {
+            lib.realpath
+                .as_ref()
+                .map(|realpath| !realpath.starts_with(&root))
+                .unwrap_or(true)
+        });
+        audits.push(WheelElfAudit {
+            path: relative.clone(),
+            arch,
+            musl,
+            audit,
+        });
+    }
+    Ok(audits)
+}
+
+/// Audits prebuilt wheels and checks that their platform tags are consistent with the
+/// libraries they contain, failing if any of them claims a policy it doesn't satisfy
+///
+/// Platform tags of `custom_policies` are checked like the built-in ones
+pub fn audit_wheels(wheels: &[PathBuf], custom_policies: &[Policy]) -> Result<()> {
+    let mut failed = Vec::new();
+    for wheel in wheels {
+        let wheel_name = wheel
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        println!("🔍 Auditing {}", wheel_name);
+        let audits = audit_wheel(wheel, custom_policies)?;
+        if audits.is_empty() {
+            println!("    No elf files found, skipping");
+            continue;
+        }
+        let claimed = claimed_policies(&wheel_name, custom_policies);
+        let mut errors = Vec::new();
+        for elf in &audits {
+            let effective = elf.effective_policy();
+            println!(
+                "    {}: {} ({})",
+                elf.path.display(),
+                effective,
+                if elf.musl { "musl" } else { "glibc" }
+            );
+            for rejected in &elf.audit.rejected {
+                println!("      - {}", rejected);
+            }
+            if !elf.audit.external_libs.is_empty() {
+                let libs: Vec<_> = elf
+                    .audit
+                    .external_libs
+                    .iter()
+                    .map(|lib| lib.name.as_str())
+                    .collect();
+                println!(
+                    "      - {} requires external libraries: {}",
+                    elf.audit.policy,
+                    libs.join(", ")
+                );
+            }
+            for policy in &claimed {
+                if policy.name == "linux" {
+                    continue;
+                }
+                if policy.name.starts_with("musllinux") != elf.musl {
+                    errors.push(format!(
+                        "{} is tagged {}, but {} links {}",
+                        wheel_name,
+                        policy,
+                        elf.path.display(),
+                        if elf.musl { "musl" } else { "glibc" }
+                    ));
+                } else if policy.priority > effective.priority {
+                    errors.push(format!(
+                        "{} is tagged {}, but {} only satisfies {}",
+                        wheel_name,
+                        policy,
+                        elf.path.display(),
+                        effective
+                    ));
+                }
+            }
+        }
+        if errors.is_empty() {
+            println!("✨ {} is consistent with its platform tags", wheel_name);
+        } else {
+            for error in &errors {
+                eprintln!("💥 {}", error);
+            }
+            failed.push(wheel_name);
+        }
+    }
+    if !failed.is_empty() {
+        bail!(
+            "{} wheel(s) claim platform tags they don't satisfy: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Replaces the platform of the `Tag` lines in a `WHEEL` file, keeping the python and abi tags
+fn retag_wheel_file(wheel_file: &str, platform: &str) -> String {
+    let mut lines = Vec::new();
+    let mut python_abi_tags = Vec::new();
+    for line in wheel_file.lines() {
+        match line
+            .strip_prefix("Tag: ")
+            .and_then(|tag| tag.rsplit_once('-'))
+        {
+            Some((python_abi, _)) => {
+                if !python_abi_tags.contains(&python_abi) {
+                    python_abi_tags.push(python_abi);
+                    for platform_tag in platform.split('.') {
+                        lines.push(format!("Tag: {}-{}", python_abi, platform_tag));
+                    }
+                }
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Bundles the external shared libraries into an existing wheel like `auditwheel repair` and
+/// re-tags it with `platform_tags`
+///
+/// The repaired wheel is written to `out_dir` with a regenerated `RECORD`. The platform tags
+/// may also name one of `custom_policies`.
+pub fn repair_wheel(
+    wheel: &Path,
+    platform_tags: &[PlatformTag],
+    out_dir: &Path,
+    custom_policies: &[Policy],
+) -> Result<PathBuf> {
+    let wheel_name = wheel
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Invalid wheel path {}", wheel.display()))?;
+    let (untagged, _) = wheel_name
+        .strip_suffix(".whl")
+        .and_then(|stem| stem.rsplit_once('-'))
+        .with_context(|| format!("Invalid wheel filename {}", wheel_name))?;
+    let dist_name = untagged.split('-').next().unwrap_or(untagged);
+
+    let unpacked = tempfile::tempdir()?;
+    let files = unpack_wheel(wheel, unpacked.path())?;
+    let root = unpacked.path().canonicalize()?;
+    let dist_info_dir = files
+        .iter()
+        .find_map(|(relative, _)| {
+            let parent = relative.parent()?;
+            let is_wheel_file = relative.file_name()? == "WHEEL"
+                && parent.extension()? == "dist-info"
+                && parent.parent() == Some(Path::new(""));
+            is_wheel_file.then(|| parent.to_path_buf())
+        })
+        .with_context(|| format!("{} doesn't contain a .dist-info/WHEEL file", wheel_name))?;
+    let audits = audit_unpacked_wheel(unpacked.path(), &files, custom_policies)?;
+    let arch = match audits.first() {
+        Some(audit) => audit.arch,
+        None => bail!(
+            "{} doesn't contain any shared libraries, there's nothing to repair",
+            wheel_name
+        ),
+    };
+
+    // The strictest of the requested policies decides which libraries need to be bundled
+    let mut policy: Option<Policy> = None;
+    for platform_tag in platform_tags.iter().filter(|tag| tag.is_portable()) {
+        let tag = platform_tag.to_string();
+        let requested = Policy::from_name_with_custom(&tag, custom_policies)
+            .ok_or(AuditWheelError::UndefinedPolicy(tag))?;
+        if policy
+            .as_ref()
+            .map_or(true, |policy| requested.priority > policy.priority)
+        {
+            policy = Some(requested);
+        }
+    }
+
+    let mut artifacts = Vec::new();
+    let mut ext_libs = Vec::new();
+    for elf in audits {
+        if elf.arch != arch {
+            bail!(
+                "{} contains shared libraries for both {} and {}",
+                wheel_name,
+                arch,
+                elf.arch
+            );
+        }
+        let mut policy = match policy.clone() {
+            Some(policy) => policy,
+            None => continue,
+        };
+        if policy.name.starts_with("musllinux") != elf.musl {
+            bail!(
+                "Can't tag {} as {} because {} links {}",
+                wheel_name,
+                policy,
+                elf.path.display(),
+                if elf.musl { "musl" } else { "glibc" }
+            );
+        }
+        policy.fixup_musl_libc_so_name(arch);
+        if policy.priority > elf.audit.policy.priority {
+            let context = format!("Error repairing {} for {} compliance", wheel_name, policy);
+            let rejected = elf.audit.rejected.into_iter().find(|err| match err {
+                AuditWheelError::VersionedSymbolTooNewError(rejected, _)
+                | AuditWheelError::BlackListedSymbolsError(rejected, _) => {
+                    rejected.name == policy.name
+                }
+                _ => false,
+            });
+            return match rejected {
+                Some(err) => Err(anyhow::Error::new(err).context(context)),
+                None => Err(
+                    anyhow!("{} only satisfies {}", elf.path.display(), elf.audit.policy)
+                        .context(context),
+                ),
+            };
+        }
+        let path = unpacked.path().join(&elf.path);
+        let mut libs = find_external_libs(&path, &policy, PathBuf::from("/"), Vec::new())?;
+        // Libraries that were already grafted into the wheel don't need to be copied again
+        libs.retain(|lib| {
+            lib.realpath
+                .as_ref()
+                .map(|realpath| !realpath.starts_with(&root))
+                .unwrap_or(true)
+        });
+        if !libs.is_empty() {
+            let artifact_dir = elf
+                .path
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .to_path_buf();
+            artifacts.push((path, artifact_dir));
+            ext_libs.push(libs);
+        }
+    }
+
+    let platform = format_linux_platform_tag(platform_tags, &arch.to_string());
+    fs::create_dir_all(out_dir)?;
+    let wheel_path = out_dir.join(format!("{}-{}.whl", untagged, platform));
+    let mut writer = WheelWriter::new_empty(wheel_path, &dist_info_dir)?;
+    if !ext_libs.is_empty() {
+        let libs_dir = PathBuf::from(format!("{}.libs", dist_name));
+        let artifacts: Vec<_> = artifacts
+            .iter()
+            .map(|(path, artifact_dir)| (path.as_path(), artifact_dir.as_path()))
+            .collect();
+        graft_external_libs(&mut writer, &libs_dir, &artifacts, &ext_libs)?;
+    }
+    for (relative, permissions) in &files {
+        let source = unpacked.path().join(relative);
+        if relative.parent() == Some(dist_info_dir.as_path()) {
+            match relative.file_name().and_then(|name| name.to_str()) {
+                // The RECORD and its signatures are invalid after repairing
+                Some("RECORD" | "RECORD.jws" | "RECORD.p7s") => continue,
+                Some("WHEEL") => {
+                    let wheel_file = retag_wheel_file(&fs::read_to_string(&source)?, &platform);
+                    writer.add_bytes(relative, wheel_file.as_bytes())?;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        writer.add_file_with_permissions(relative, &source, *permissions)?;
+    }
+    Ok(writer.finish()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    #[test]
+    fn test_claimed_policies() {
+        let claimed = claimed_policies(
+            "foo-0.1.0-cp37-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+            &[],
+        );
+        let names: Vec<_> = claimed.iter().map(|policy| policy.name.as_str()).collect();
+        assert_eq!(names, ["manylinux_2_17"]);
+
+        let claimed = claimed_policies("foo-0.1.0-cp37-abi3-musllinux_1_2_aarch64.whl", &[]);
+        assert_eq!(claimed[0].name, "musllinux_1_2");
+
+        let claimed = claimed_policies("foo-0.1.0-cp37-abi3-linux_x86_64.whl", &[]);
+        assert_eq!(claimed[0].name, "linux");
+
+        assert!(claimed_policies("foo-0.1.0-cp37-abi3-win_amd64.whl", &[]).is_empty());
+        assert!(claimed_policies("foo-0.1.0-cp37-abi3-macosx_10_7_x86_64.whl", &[]).is_empty());
+
+        let acme = Policy {
+            name: "acme_2_28".to_string(),
+            priority: 70,
+            ..Policy::default()
+        };
+        let claimed = claimed_policies("foo-0.1.0-cp37-abi3-acme_2_28_x86_64.whl", &[acme]);
+        assert_eq!(claimed[0].name, "acme_2_28");
+    }
+
+    /// Packs the test binary as extension module into a minimal wheel
+    #[cfg(target_os = "linux")]
+    fn native_wheel(dir: &Path) -> PathBuf {
+        let wheel = dir.join("foo-0.1.0-py3-none-linux_x86_64.whl");
+        let mut zip = zip::ZipWriter::new(File::create(&wheel).unwrap());
+        zip.start_file("foo/native.so", FileOptions::default())
+            .unwrap();
+        zip.write_all(&fs::read(std::env::current_exe().unwrap()).unwrap())
+            .unwrap();
+        zip.start_file("foo/__init__.py", FileOptions::default())
+            .unwrap();
+        zip.start_file("foo-0.1.0.dist-info/WHEEL", FileOptions::default())
+            .unwrap();
+        zip.write_all(b"Wheel-Version: 1.0\nRoot-Is-Purelib: false\nTag: py3-none-linux_x86_64\n")
+            .unwrap();
+        zip.start_file("foo-0.1.0.dist-info/RECORD", FileOptions::default())
+            .unwrap();
+        zip.finish().unwrap();
+        wheel
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_audit_wheel() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let wheel = native_wheel(tmp_dir.path());
+
+        let audits = audit_wheel(&wheel, &[]).unwrap();
+        assert_eq!(audits.len(), 1);
+        assert_eq!(audits[0].path, Path::new("foo/native.so"));
+    }
+
+    #[test]
+    fn test_retag_wheel_file() {
+        let wheel_file =
+            "Wheel-Version: 1.0\nRoot-Is-Purelib: false\nTag: cp37-abi3-linux_x86_64\n";
+        assert_eq!(
+            retag_wheel_file(wheel_file, "manylinux_2_17_x86_64.manylinux2014_x86_64"),
+            "Wheel-Version: 1.0\nRoot-Is-Purelib: false\n\
+            Tag: cp37-abi3-manylinux_2_17_x86_64\nTag: cp37-abi3-manylinux2014_x86_64\n"
+        );
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_repair_wheel() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let wheel = native_wheel(tmp_dir.path());
+        let out = tmp_dir.path().join("wheelhouse");
+
+        // The test binary links a glibc that's much newer than 2.5
+        let err = repair_wheel(&wheel, &[PlatformTag::manylinux1()], &out, &[]).unwrap_err();
+        assert!(err.to_string().contains("manylinux_2_5"));
+
+        let repaired = repair_wheel(&wheel, &[PlatformTag::Linux], &out, &[]).unwrap();
+        let mut archive = zip::ZipArchive::new(File::open(&repaired).unwrap()).unwrap();
+        let mut record = String::new();
+        io::Read::read_to_string(
+            &mut archive.by_name("foo-0.1.0.dist-info/RECORD").unwrap(),
+            &mut record,
+        )
+        .unwrap();
+        assert!(record.contains("foo/native.so,sha256="));
+        assert!(record.contains("foo-0.1.0.dist-info/WHEEL,sha256="));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_repair_wheel_custom_policy() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let wheel = native_wheel(tmp_dir.path());
+        let out = tmp_dir.path().join("wheelhouse");
+
+        // A custom policy that allows any glibc, so that the test binary satisfies it
+        let mut acme = Policy::from_name("manylinux_2_31").unwrap();
+        acme.name = "acme_2_99".to_string();
+        acme.aliases.clear();
+        acme.priority = 1;
+        for symbol_versions in acme.symbol_versions.values_mut() {
+            if let Some(glibc) = symbol_versions.get_mut("GLIBC") {
+                glibc.extend((32..=99).map(|minor| format!("2.{}", minor)));
+            }
+        }
+        let platform_tag = PlatformTag::Custom("acme_2_99".to_string());
+
+        let err = repair_wheel(&wheel, &[platform_tag.clone()], &out, &[]).unwrap_err();
+        assert!(err.to_string().contains("acme_2_99"));
+
+        let custom_policies = [acme];
+        let repaired = repair_wheel(&wheel, &[platform_tag], &out, &custom_policies).unwrap();
+        let file_name = repaired.file_name().unwrap().to_str().unwrap();
+        assert!(file_name.starts_with("foo-0.1.0-py3-none-acme_2_99_"));
+        audit_wheels(&[repaired.clone()], &custom_policies).unwrap();
+    }
+}
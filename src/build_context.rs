@@ -1,17 +1,20 @@
-use crate::auditwheel::{get_policy_and_libs, patchelf, relpath};
+use crate::auditwheel::{
+    dll_directory_hook, find_external_dlls, find_external_dylibs, get_policy_and_libs,
+    graft_external_dlls, graft_external_dylibs, graft_external_libs, patchelf,
+};
 use crate::auditwheel::{PlatformTag, Policy};
-use crate::build_options::CargoOptions;
-use crate::compile::warn_missing_py_init;
+use crate::build_options::{with_root_package, CargoOptions};
+use crate::build_report::WheelReport;
+use crate::compile::{compile_with_output, warn_missing_py_init};
 use crate::module_writer::{
-    add_data, write_bin, write_bindings_module, write_cffi_module, write_python_part,
-    write_wasm_launcher, WheelWriter,
+    add_data, write_bin, write_bindings_module, write_cffi_module, write_extension_module,
+    write_python_part, write_uniffi_module, write_wasm_launcher, ModuleWriter, WheelWriter,
 };
 use crate::project_layout::ProjectLayout;
 use crate::python_interpreter::InterpreterKind;
-use crate::source_distribution::source_distribution;
-use crate::{
-    compile, BuildArtifact, Metadata21, ModuleWriter, PyProjectToml, PythonInterpreter, Target,
-};
+use crate::source_distribution::{source_distribution, verify_source_distribution};
+use crate::type_stub::generate_type_stub;
+use crate::{compile, BuildArtifact, Metadata21, PyProjectToml, PythonInterpreter, Target};
 use anyhow::{anyhow, bail, Context, Result};
 use cargo_metadata::Metadata;
 use fs_err as fs;
@@ -22,6 +25,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::io;
 use std::path::{Path, PathBuf};
+use std::thread;
 
 /// The way the rust code is used in the wheel
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -39,6 +43,9 @@ pub enum BridgeModel {
     /// for all cpython versions (pypy still needs multiple versions).
     /// The numbers are the minimum major and minor version
     BindingsAbi3(u8, u8),
+    /// A native module with UniFFI bindings, loaded through the python wrapper that
+    /// uniffi-bindgen generates from the `.udl` file
+    UniFfi,
 }
 
 impl BridgeModel {
@@ -73,10 +80,38 @@ impl Display for BridgeModel {
             BridgeModel::Bin(None) => write!(f, "bin"),
             BridgeModel::Bindings(name, _) => write!(f, "{}", name),
             BridgeModel::BindingsAbi3(..) => write!(f, "pyo3"),
+            BridgeModel::UniFfi => write!(f, "uniffi"),
         }
     }
 }
 
+/// A native module from another member of the cargo workspace that is built into the same wheel
+/// as the main module, see `[[tool.maturin.extension-modules]]`
+#[derive(Clone, Debug)]
+pub struct ExtensionModule {
+    /// The name of the cargo package
+    pub crate_name: String,
+    /// The dotted path of the module in the python package, e.g. `my_project._simd`
+    pub module: String,
+    /// The path to the Cargo.toml of the package
+    pub manifest_path: PathBuf,
+    /// The bindings of the package
+    pub bridge: BridgeModel,
+}
+
+impl ExtensionModule {
+    /// The name of the module itself, i.e. the last part of the dotted path
+    pub fn extension_name(&self) -> &str {
+        self.module.rsplit('.').next().unwrap_or(&self.module)
+    }
+
+    /// The directory of the module inside the wheel, i.e. the package of the dotted path
+    pub fn wheel_dir(&self) -> PathBuf {
+        let parts: Vec<&str> = self.module.split('.').collect();
+        parts[..parts.len() - 1].iter().collect()
+    }
+}
+
 /// Insert wasm launcher scripts as entrypoints and the wasmtime dependency
 fn bin_wasi_helper(
     artifacts_and_files: &[(&BuildArtifact, String)],
@@ -168,14 +203,26 @@ pub struct BuildContext {
     pub zig: bool,
     /// Whether to use the the manylinux/musllinux or use the native linux tag (off)
     pub platform_tag: Vec<PlatformTag>,
+    /// Custom auditwheel policies that are checked alongside the built-in ones
+    pub auditwheel_policies: Vec<Policy>,
     /// The available python interpreter
     pub interpreter: Vec<PythonInterpreter>,
     /// Cargo.toml as resolved by [cargo_metadata]
     pub cargo_metadata: Metadata,
     /// Whether to use universal2 or use the native macOS tag (off)
     pub universal2: bool,
+    /// Compile the wheels for the different python interpreters concurrently
+    pub parallel: bool,
+    /// Native modules from other workspace members that go into the same wheel
+    pub extension_modules: Vec<ExtensionModule>,
+    /// Put the binaries of the crate into the wheel next to the native module
+    pub include_bins: bool,
+    /// Vendor the cargo dependencies into the source distribution
+    pub sdist_vendor: bool,
     /// Build editable wheels
     pub editable: bool,
+    /// Generate the type stub of the native module by importing it after compiling
+    pub generate_stubs: bool,
     /// Cargo build options
     pub cargo_options: CargoOptions,
 }
@@ -190,6 +237,12 @@ impl BuildContext {
     /// Checks which kind of bindings we have (pyo3/rust-cypthon or cffi or bin) and calls the
     /// correct builder.
     pub fn build_wheels(&self) -> Result<Vec<BuiltWheelMetadata>> {
+        let (wheels, _) = self.build_wheels_with_report()?;
+        Ok(wheels)
+    }
+
+    /// Same as [BuildContext::build_wheels], but also returns what went into each wheel
+    pub fn build_wheels_with_report(&self) -> Result<(Vec<BuiltWheelMetadata>, Vec<WheelReport>)> {
         use itertools::Itertools;
 
         fs::create_dir_all(&self.out)
@@ -197,6 +250,7 @@ impl BuildContext {
 
         let wheels = match &self.bridge {
             BridgeModel::Cffi => self.build_cffi_wheel()?,
+            BridgeModel::UniFfi => self.build_uniffi_wheel()?,
             BridgeModel::Bin(None) => self.build_bin_wheel(None)?,
             BridgeModel::Bin(Some(..)) => self.build_bin_wheels(&self.interpreter)?,
             BridgeModel::Bindings(..) => self.build_binding_wheels(&self.interpreter)?,
@@ -239,7 +293,31 @@ impl BuildContext {
             }
         };
 
-        Ok(wheels)
+        Ok(wheels.into_iter().unzip())
+    }
+
+    /// Collects the report of a wheel, including the auditwheel policy on linux
+    fn wheel_report(
+        &self,
+        wheel_path: &Path,
+        python_interpreter: Option<&PythonInterpreter>,
+        policy: &Policy,
+        ext_libs: &[Library],
+    ) -> Result<WheelReport> {
+        let policy = if self.target.is_linux() && !self.skip_auditwheel {
+            Some(policy)
+        } else {
+            None
+        };
+        // Editable installs link the libraries in place instead of bundling them
+        let ext_libs = if self.editable { &[] } else { ext_libs };
+        WheelReport::new(
+            wheel_path,
+            python_interpreter,
+            self.bridge.to_string(),
+            policy,
+            ext_libs,
+        )
     }
 
     /// Builds a source distribution and returns the same metadata as [BuildContext::build_wheels]
@@ -257,6 +335,12 @@ impl BuildContext {
         }
     }
 
+    /// Unpacks the source distribution and builds a wheel from it offline to check that no
+    /// files are missing
+    pub fn verify_source_distribution(&self, sdist_path: &Path) -> Result<()> {
+        verify_source_distribution(self, sdist_path)
+    }
+
     fn auditwheel(
         &self,
         artifact: &BuildArtifact,
@@ -280,25 +364,47 @@ impl BuildContext {
             }
         }
 
+        if self.target.is_macos() {
+            let ld_paths: Vec<_> = artifact.linked_paths.iter().map(PathBuf::from).collect();
+            let ext_libs =
+                find_external_dylibs(&artifact.path, &ld_paths, self.target.cross_compiling())
+                    .context("Failed to find the dylibs linked by the macOS artifact")?;
+            return Ok((Policy::default(), ext_libs));
+        }
+
+        if self.target.is_windows() {
+            // Binaries are installed as scripts, which we can't bundle DLLs for
+            if self.bridge.is_bin() {
+                return Ok((Policy::default(), Vec::new()));
+            }
+            let ld_paths: Vec<_> = artifact.linked_paths.iter().map(PathBuf::from).collect();
+            let ext_libs = find_external_dlls(&artifact.path, &ld_paths)
+                .context("Failed to find the DLLs imported by the windows artifact")?;
+            return Ok((Policy::default(), ext_libs));
+        }
+
         let mut musllinux: Vec<_> = platform_tag
             .iter()
             .filter(|tag| tag.is_musllinux())
-            .copied()
             .collect();
         musllinux.sort();
         let mut others: Vec<_> = platform_tag
             .iter()
             .filter(|tag| !tag.is_musllinux())
-            .copied()
             .collect();
         others.sort();
 
         if self.bridge.is_bin() && !musllinux.is_empty() {
-            return get_policy_and_libs(artifact, Some(musllinux[0]), &self.target);
+            return get_policy_and_libs(
+                artifact,
+                Some(musllinux[0]),
+                &self.target,
+                &self.auditwheel_policies,
+            );
         }
 
         let tag = others.get(0).or_else(|| musllinux.get(0)).copied();
-        get_policy_and_libs(artifact, tag, &self.target)
+        get_policy_and_libs(artifact, tag, &self.target, &self.auditwheel_policies)
     }
 
     /// Add library search paths in Cargo target directory rpath when building in editable mode
@@ -331,116 +437,81 @@ impl BuildContext {
     fn add_external_libs(
         &self,
         writer: &mut WheelWriter,
-        artifacts: &[&BuildArtifact],
+        artifacts: &[(&BuildArtifact, PathBuf)],
         ext_libs: &[Vec<Library>],
     ) -> Result<()> {
         if self.editable {
-            return self.add_rpath(artifacts);
+            let artifacts: Vec<_> = artifacts.iter().map(|(artifact, _)| *artifact).collect();
+            return self.add_rpath(&artifacts);
         }
         if ext_libs.iter().all(|libs| libs.is_empty()) {
             return Ok(());
         }
         // Put external libs to ${module_name}.libs directory
         // See https://github.com/pypa/auditwheel/issues/89
-        let mut libs_dir = self
+        let package = self
             .project_layout
             .python_module
             .as_ref()
             .and_then(|py| py.file_name().map(|s| s.to_os_string()))
             .unwrap_or_else(|| self.module_name.clone().into());
+        let mut libs_dir = package.clone();
         libs_dir.push(".libs");
         let libs_dir = PathBuf::from(libs_dir);
-        writer.add_directory(&libs_dir)?;
-
-        let temp_dir = tempfile::tempdir()?;
-        let mut soname_map = HashMap::new();
-        let mut libs_copied = HashSet::new();
-        for lib in ext_libs.iter().flatten() {
-            let lib_path = lib.realpath.clone().with_context(|| {
-                format!(
-                    "Cannot repair wheel, because required library {} could not be located.",
-                    lib.path.display()
-                )
-            })?;
-            // Generate a new soname with a short hash
-            let short_hash = &hash_file(&lib_path)?[..8];
-            let (file_stem, file_ext) = lib.name.split_once('.').unwrap();
-            let new_soname = if !file_stem.ends_with(&format!("-{}", short_hash)) {
-                format!("{}-{}.{}", file_stem, short_hash, file_ext)
-            } else {
-                format!("{}.{}", file_stem, file_ext)
-            };
 
-            // Copy the original lib to a tmpdir and modify some of its properties
-            // for example soname and rpath
-            let dest_path = temp_dir.path().join(&new_soname);
-            fs::copy(&lib_path, &dest_path)?;
-            libs_copied.insert(lib_path);
-
-            patchelf::set_soname(&dest_path, &new_soname)?;
-            if !lib.rpath.is_empty() || !lib.runpath.is_empty() {
-                patchelf::set_rpath(&dest_path, &libs_dir)?;
-            }
-            soname_map.insert(
-                lib.name.clone(),
-                (new_soname.clone(), dest_path.clone(), lib.needed.clone()),
+        let artifacts: Vec<_> = artifacts
+            .iter()
+            .map(|(artifact, artifact_dir)| (artifact.path.as_path(), artifact_dir.as_path()))
+            .collect();
+        if self.target.is_macos() {
+            graft_external_dylibs(writer, &libs_dir, &artifacts, ext_libs)
+        } else if self.target.is_windows() {
+            graft_external_dlls(writer, &libs_dir, &artifacts, ext_libs)?;
+            // The windows loader doesn't search `libs_dir`, so the package has to add it
+            let package = Path::new(&package);
+            writer.insert_into_init(
+                &package.join("__init__.py"),
+                dll_directory_hook(package, &libs_dir),
             );
+            Ok(())
+        } else {
+            graft_external_libs(writer, &libs_dir, &artifacts, ext_libs)
         }
+    }
 
-        for (artifact, artifact_ext_libs) in artifacts.iter().zip(ext_libs) {
-            let artifact_deps: HashSet<_> = artifact_ext_libs.iter().map(|lib| &lib.name).collect();
-            let replacements = soname_map
-                .iter()
-                .filter_map(|(k, v)| {
-                    if artifact_deps.contains(k) {
-                        Some((k, v.0.clone()))
-                    } else {
-                        None
-                    }
-                })
-                .collect::<Vec<_>>();
-            if !replacements.is_empty() {
-                patchelf::replace_needed(&artifact.path, &replacements[..])?;
-            }
-        }
+    /// The directory of the main native module inside the wheel
+    fn module_dir(&self) -> PathBuf {
+        // Currently artifact .so file always resides at ${module_name}/${module_name}.so
+        PathBuf::from(&self.module_name)
+    }
 
-        // we grafted in a bunch of libraries and modified their sonames, but
-        // they may have internal dependencies (DT_NEEDED) on one another, so
-        // we need to update those records so each now knows about the new
-        // name of the other.
-        for (new_soname, path, needed) in soname_map.values() {
-            let mut replacements = Vec::new();
-            for n in needed {
-                if soname_map.contains_key(n) {
-                    replacements.push((n, soname_map[n].0.clone()));
-                }
-            }
-            if !replacements.is_empty() {
-                patchelf::replace_needed(path, &replacements[..])?;
-            }
-            writer.add_file_with_permissions(libs_dir.join(new_soname), path, 0o755)?;
-        }
+    /// The directory of the binaries inside the wheel
+    fn scripts_dir(&self) -> PathBuf {
+        self.metadata21.get_data_dir().join("scripts")
+    }
 
-        println!(
-            "🖨  Copied external shared libraries to package {} directory:",
-            libs_dir.display()
-        );
-        for lib_path in libs_copied {
-            println!("    {}", lib_path.display());
+    /// Collects the artifacts of the main native module, the extension modules and the binaries
+    /// with the directories they are written to in the wheel and their external libraries, so
+    /// that the libraries are grafted into the wheel together
+    #[allow(clippy::type_complexity)]
+    fn with_extra_artifacts<'a>(
+        &self,
+        artifact: &'a BuildArtifact,
+        ext_libs: Vec<Library>,
+        extension_modules: &'a [(BuildArtifact, Vec<Library>)],
+        bins: &'a [(BuildArtifact, Vec<Library>)],
+    ) -> (Vec<(&'a BuildArtifact, PathBuf)>, Vec<Vec<Library>>) {
+        let mut artifacts = vec![(artifact, self.module_dir())];
+        let mut libs = vec![ext_libs];
+        for (module, (artifact, ext_libs)) in self.extension_modules.iter().zip(extension_modules) {
+            artifacts.push((artifact, module.wheel_dir()));
+            libs.push(ext_libs.clone());
         }
-
-        // Currently artifact .so file always resides at ${module_name}/${module_name}.so
-        let artifact_dir = Path::new(&self.module_name);
-        for artifact in artifacts {
-            let mut new_rpaths = patchelf::get_rpath(&artifact.path)?;
-            // TODO: clean existing rpath entries if it's not pointed to a location within the wheel
-            // See https://github.com/pypa/auditwheel/blob/353c24250d66951d5ac7e60b97471a6da76c123f/src/auditwheel/repair.py#L160
-            let new_rpath = Path::new("$ORIGIN").join(relpath(&libs_dir, artifact_dir));
-            new_rpaths.push(new_rpath.to_str().unwrap().to_string());
-            let new_rpath = new_rpaths.join(":");
-            patchelf::set_rpath(&artifact.path, &new_rpath)?;
+        for (artifact, ext_libs) in bins {
+            artifacts.push((artifact, self.scripts_dir()));
+            libs.push(ext_libs.clone());
         }
-        Ok(())
+        (artifacts, libs)
     }
 
     fn add_pth(&self, writer: &mut WheelWriter) -> Result<()> {
@@ -450,9 +521,70 @@ impl BuildContext {
         Ok(())
     }
 
+    /// Generates the type stub of the native module if `[tool.maturin] generate-stubs` is set
+    ///
+    /// This needs to import the module, so it only works when the wheel runs on the host. If the
+    /// stub can't be generated, a warning is printed and the module is packaged without it.
+    fn type_stub(
+        &self,
+        artifact: &BuildArtifact,
+        python_interpreter: Option<&PythonInterpreter>,
+    ) -> Result<Option<String>> {
+        if !self.generate_stubs {
+            return Ok(None);
+        }
+        if self.target.cross_compiling() {
+            println!(
+                "⚠️  Warning: Can't generate a type stub when cross compiling to {}",
+                self.target.target_triple()
+            );
+            return Ok(None);
+        }
+        // abi3 wheels don't come with an interpreter, so we use the one on the host
+        let interpreter = match python_interpreter.filter(|interpreter| interpreter.runnable) {
+            Some(interpreter) => Some(interpreter.clone()),
+            None => PythonInterpreter::check_executable(
+                self.target.get_python(),
+                &self.target,
+                &self.bridge,
+            )?,
+        };
+        let interpreter = match interpreter {
+            Some(interpreter) => interpreter,
+            None => {
+                println!(
+                    "⚠️  Warning: Can't generate a type stub without a python interpreter to \
+                    import the module"
+                );
+                return Ok(None);
+            }
+        };
+        match generate_type_stub(
+            &interpreter,
+            &artifact.path,
+            &self.project_layout.extension_name,
+        ) {
+            Ok(type_stub) => {
+                println!(
+                    "📖 Generated type stub for {} with {}",
+                    self.project_layout.extension_name, interpreter
+                );
+                Ok(Some(type_stub))
+            }
+            Err(err) => {
+                println!("⚠️  Warning: {:#}", err);
+                Ok(None)
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn write_binding_wheel_abi3(
         &self,
         artifact: BuildArtifact,
+        extension_modules: &[(BuildArtifact, Vec<Library>)],
+        bins: &[(BuildArtifact, Vec<Library>)],
+        python_interpreter: Option<&PythonInterpreter>,
         platform_tags: &[PlatformTag],
         ext_libs: Vec<Library>,
         major: u8,
@@ -464,18 +596,25 @@ impl BuildContext {
         let tag = format!("cp{}{}-abi3-{}", major, min_minor, platform);
 
         let mut writer = WheelWriter::new(&tag, &self.out, &self.metadata21, &[tag.clone()])?;
-        self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
+        let (artifacts, ext_libs) =
+            self.with_extra_artifacts(&artifact, ext_libs, extension_modules, bins);
+        self.add_external_libs(&mut writer, &artifacts, &ext_libs)?;
 
+        let type_stub = self.type_stub(&artifact, python_interpreter)?;
         write_bindings_module(
             &mut writer,
             &self.project_layout,
+            self.pyproject_toml.as_ref(),
             &self.module_name,
             &artifact.path,
             None,
             &self.target,
             self.editable,
+            type_stub.as_deref(),
         )
         .context("Failed to add the files to the wheel")?;
+        self.write_extension_modules(&mut writer, extension_modules, None)?;
+        self.write_bins(&mut writer, bins)?;
 
         self.add_pth(&mut writer)?;
         add_data(&mut writer, self.project_layout.data.as_deref())?;
@@ -490,7 +629,7 @@ impl BuildContext {
         interpreters: &[PythonInterpreter],
         major: u8,
         min_minor: u8,
-    ) -> Result<Vec<BuiltWheelMetadata>> {
+    ) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
         let mut wheels = Vec::new();
         // On windows, we have picked an interpreter to set the location of python.lib,
         // otherwise it's none
@@ -501,6 +640,13 @@ impl BuildContext {
         )?;
         let (policy, external_libs) =
             self.auditwheel(&artifact, &self.platform_tag, python_interpreter)?;
+        let (extension_modules, policy) =
+            self.build_extension_modules(python_interpreter, policy)?;
+        let (bins, bins_policy) = self.build_bins()?;
+        let policy = match bins_policy {
+            Some(bins_policy) if bins_policy.priority < policy.priority => bins_policy,
+            _ => policy,
+        };
         let platform_tags = if self.platform_tag.is_empty() {
             vec![policy.platform_tag()]
         } else {
@@ -508,11 +654,17 @@ impl BuildContext {
         };
         let (wheel_path, tag) = self.write_binding_wheel_abi3(
             artifact,
+            &extension_modules,
+            &bins,
+            python_interpreter,
             &platform_tags,
-            external_libs,
+            external_libs.clone(),
             major,
             min_minor,
         )?;
+        let external_libs =
+            all_external_libs(&external_libs, extension_modules.iter().chain(&bins));
+        let report = self.wheel_report(&wheel_path, None, &policy, &external_libs)?;
 
         println!(
             "📦 Built wheel for abi3 Python ≥ {}.{} to {}",
@@ -520,7 +672,7 @@ impl BuildContext {
             min_minor,
             wheel_path.display()
         );
-        wheels.push((wheel_path, tag));
+        wheels.push(((wheel_path, tag), report));
 
         Ok(wheels)
     }
@@ -529,24 +681,33 @@ impl BuildContext {
         &self,
         python_interpreter: &PythonInterpreter,
         artifact: BuildArtifact,
+        extension_modules: &[(BuildArtifact, Vec<Library>)],
+        bins: &[(BuildArtifact, Vec<Library>)],
         platform_tags: &[PlatformTag],
         ext_libs: Vec<Library>,
     ) -> Result<BuiltWheelMetadata> {
         let tag = python_interpreter.get_tag(&self.target, platform_tags, self.universal2)?;
 
         let mut writer = WheelWriter::new(&tag, &self.out, &self.metadata21, &[tag.clone()])?;
-        self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
+        let (artifacts, ext_libs) =
+            self.with_extra_artifacts(&artifact, ext_libs, extension_modules, bins);
+        self.add_external_libs(&mut writer, &artifacts, &ext_libs)?;
 
+        let type_stub = self.type_stub(&artifact, Some(python_interpreter))?;
         write_bindings_module(
             &mut writer,
             &self.project_layout,
+            self.pyproject_toml.as_ref(),
             &self.module_name,
             &artifact.path,
             Some(python_interpreter),
             &self.target,
             self.editable,
+            type_stub.as_deref(),
         )
         .context("Failed to add the files to the wheel")?;
+        self.write_extension_modules(&mut writer, extension_modules, Some(python_interpreter))?;
+        self.write_bins(&mut writer, bins)?;
 
         self.add_pth(&mut writer)?;
         add_data(&mut writer, self.project_layout.data.as_deref())?;
@@ -567,15 +728,38 @@ impl BuildContext {
     pub fn build_binding_wheels(
         &self,
         interpreters: &[PythonInterpreter],
-    ) -> Result<Vec<BuiltWheelMetadata>> {
+    ) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
         let mut wheels = Vec::new();
+        let mut parallel_builds = if self.parallel && interpreters.len() > 1 {
+            self.compile_parallel(interpreters)?
+        } else {
+            Vec::new()
+        }
+        .into_iter();
+        // The binaries don't depend on the python version, so they're only built once
+        let (bins, bins_policy) = self.build_bins()?;
         for python_interpreter in interpreters {
-            let artifact = self.compile_cdylib(
-                Some(python_interpreter),
-                Some(&self.project_layout.extension_name),
-            )?;
+            let artifact = match parallel_builds.next() {
+                Some((artifacts, output)) => {
+                    println!("🔨 Cargo output for {}", python_interpreter);
+                    print!("{}", output);
+                    let artifacts =
+                        artifacts.context("Failed to build a native library through cargo")?;
+                    self.cdylib_artifact(artifacts, Some(&self.project_layout.extension_name))?
+                }
+                None => self.compile_cdylib(
+                    Some(python_interpreter),
+                    Some(&self.project_layout.extension_name),
+                )?,
+            };
             let (policy, external_libs) =
                 self.auditwheel(&artifact, &self.platform_tag, Some(python_interpreter))?;
+            let (extension_modules, policy) =
+                self.build_extension_modules(Some(python_interpreter), policy)?;
+            let policy = match &bins_policy {
+                Some(bins_policy) if bins_policy.priority < policy.priority => bins_policy.clone(),
+                _ => policy,
+            };
             let platform_tags = if self.platform_tag.is_empty() {
                 vec![policy.platform_tag()]
             } else {
@@ -584,8 +768,18 @@ impl BuildContext {
             let (wheel_path, tag) = self.write_binding_wheel(
                 python_interpreter,
                 artifact,
+                &extension_modules,
+                &bins,
                 &platform_tags,
-                external_libs,
+                external_libs.clone(),
+            )?;
+            let external_libs =
+                all_external_libs(&external_libs, extension_modules.iter().chain(&bins));
+            let report = self.wheel_report(
+                &wheel_path,
+                Some(python_interpreter),
+                &policy,
+                &external_libs,
             )?;
             println!(
                 "📦 Built wheel for {} {}.{}{} to {}",
@@ -596,12 +790,124 @@ impl BuildContext {
                 wheel_path.display()
             );
 
-            wheels.push((wheel_path, tag));
+            wheels.push(((wheel_path, tag), report));
         }
 
         Ok(wheels)
     }
 
+    /// Compiles the native modules of the other workspace members listed in
+    /// `[[tool.maturin.extension-modules]]` and runs auditwheel on each of them
+    ///
+    /// Returns the artifacts with their external libraries and the policy that the main module
+    /// and all extension modules satisfy together
+    #[allow(clippy::type_complexity)]
+    fn build_extension_modules(
+        &self,
+        python_interpreter: Option<&PythonInterpreter>,
+        mut policy: Policy,
+    ) -> Result<(Vec<(BuildArtifact, Vec<Library>)>, Policy)> {
+        let mut extension_modules = Vec::new();
+        for module in &self.extension_modules {
+            println!(
+                "🔨 Building extension module {} from {}",
+                module.module, module.crate_name
+            );
+            let mut context = self.clone();
+            context.cargo_metadata = with_root_package(&self.cargo_metadata, &module.crate_name)?;
+            context.cargo_options.manifest_path = Some(module.manifest_path.clone());
+            context.manifest_path = module.manifest_path.clone();
+            context.crate_name = module.crate_name.clone();
+            context.bridge = module.bridge.clone();
+            context.extension_modules = Vec::new();
+            // The features from the command line and pyproject.toml belong to the main crate
+            context.cargo_options.features = Vec::new();
+            context.cargo_options.all_features = false;
+            context.cargo_options.no_default_features = false;
+            let artifact = context
+                .compile_cdylib(python_interpreter, Some(module.extension_name()))
+                .with_context(|| format!("Failed to build extension module {}", module.module))?;
+            let (module_policy, external_libs) =
+                context.auditwheel(&artifact, &self.platform_tag, python_interpreter)?;
+            if module_policy.priority < policy.priority {
+                policy = module_policy;
+            }
+            extension_modules.push((artifact, external_libs));
+        }
+        Ok((extension_modules, policy))
+    }
+
+    /// Adds the compiled extension modules next to the main native module
+    fn write_extension_modules(
+        &self,
+        writer: &mut impl ModuleWriter,
+        extension_modules: &[(BuildArtifact, Vec<Library>)],
+        python_interpreter: Option<&PythonInterpreter>,
+    ) -> Result<()> {
+        for (module, (artifact, _)) in self.extension_modules.iter().zip(extension_modules) {
+            write_extension_module(
+                writer,
+                &self.project_layout,
+                &module.module,
+                &artifact.path,
+                python_interpreter,
+                &self.target,
+                self.editable,
+            )
+            .with_context(|| format!("Failed to add extension module {}", module.module))?;
+        }
+        Ok(())
+    }
+
+    /// Compiles the `[[bin]]` targets of the crate if `[tool.maturin] include-bins` is set and
+    /// runs auditwheel on each of them
+    ///
+    /// Returns the binaries with their external libraries and the policy that all of them
+    /// satisfy, if there are any
+    #[allow(clippy::type_complexity)]
+    fn build_bins(&self) -> Result<(Vec<(BuildArtifact, Vec<Library>)>, Option<Policy>)> {
+        if !self.include_bins {
+            return Ok((Vec::new(), None));
+        }
+        let mut policy: Option<Policy> = None;
+        let artifacts = compile(self, None, &BridgeModel::Bin(None))
+            .context("Failed to build the binaries through cargo")?;
+        let mut bins = Vec::with_capacity(artifacts.len());
+        for artifact in artifacts {
+            let artifact = artifact
+                .get("bin")
+                .cloned()
+                .ok_or_else(|| anyhow!("Cargo didn't build a binary"))?;
+            let (bin_policy, external_libs) =
+                self.auditwheel(&artifact, &self.platform_tag, None)?;
+            match &policy {
+                Some(policy) if policy.priority <= bin_policy.priority => {}
+                _ => policy = Some(bin_policy),
+            }
+            bins.push((artifact, external_libs));
+        }
+        Ok((bins, policy))
+    }
+
+    /// Adds the binaries to the scripts of the wheel next to the native module
+    fn write_bins(
+        &self,
+        writer: &mut impl ModuleWriter,
+        bins: &[(BuildArtifact, Vec<Library>)],
+    ) -> Result<()> {
+        for (artifact, _) in bins {
+            let bin_name = bin_name(artifact)?;
+            if self.metadata21.scripts.contains_key(&bin_name) {
+                bail!(
+                    "The binary {} clashes with the script of the same name in pyproject.toml",
+                    bin_name
+                );
+            }
+            write_bin(writer, &artifact.path, &self.metadata21, &bin_name)?;
+        }
+        Ok(())
+    }
+
     /// Runs cargo build, extracts the cdylib from the output and returns the path to it
     ///
     /// The module name is used to warn about missing a `PyInit_<module name>` function for
@@ -613,6 +919,71 @@ impl BuildContext {
     ) -> Result<BuildArtifact> {
         let artifacts = compile(self, python_interpreter, &self.bridge)
             .context("Failed to build a native library through cargo")?;
+        self.cdylib_artifact(artifacts, extension_name)
+    }
+
+    /// Compiles the native library for each interpreter concurrently, each in its own target
+    /// directory so that the cargo builds don't wait for each other's lock
+    ///
+    /// Returns the artifacts and the cargo output of each build in the order of the interpreters
+    #[allow(clippy::type_complexity)]
+    fn compile_parallel(
+        &self,
+        interpreters: &[PythonInterpreter],
+    ) -> Result<Vec<(Result<Vec<HashMap<String, BuildArtifact>>>, String)>> {
+        println!(
+            "🚀 Building for {} python interpreters in parallel",
+            interpreters.len()
+        );
+        let builds: Vec<_> = interpreters
+            .iter()
+            .map(|python_interpreter| {
+                let target_dir = self
+                    .target_dir
+                    .join("maturin")
+                    .join("parallel")
+                    .join(format!(
+                        "{}-{}.{}{}",
+                        python_interpreter
+                            .interpreter_kind
+                            .to_string()
+                            .to_lowercase(),
+                        python_interpreter.major,
+                        python_interpreter.minor,
+                        python_interpreter.abiflags
+                    ));
+                let mut context = self.clone();
+                context.cargo_options.target_dir = Some(target_dir.clone());
+                context.target_dir = target_dir;
+                let python_interpreter = python_interpreter.clone();
+                thread::spawn(move || {
+                    let mut output = String::new();
+                    let artifacts = compile_with_output(
+                        &context,
+                        Some(&python_interpreter),
+                        &context.bridge,
+                        Some(&mut output),
+                    );
+                    (artifacts, output)
+                })
+            })
+            .collect();
+        builds
+            .into_iter()
+            .map(|build| {
+                build
+                    .join()
+                    .map_err(|_| anyhow!("A parallel cargo build panicked"))
+            })
+            .collect()
+    }
+
+    /// Picks the cdylib from the cargo artifacts and copies it so auditwheel can modify it
+    fn cdylib_artifact(
+        &self,
+        artifacts: Vec<HashMap<String, BuildArtifact>>,
+        extension_name: Option<&str>,
+    ) -> Result<BuildArtifact> {
         let error_msg = "Cargo didn't build a cdylib. Did you miss crate-type = [\"cdylib\"] \
                  in the lib section of your Cargo.toml?";
         let artifacts = artifacts.get(0).context(error_msg)?;
@@ -652,11 +1023,12 @@ impl BuildContext {
             .get_universal_tags(platform_tags, self.universal2)?;
 
         let mut writer = WheelWriter::new(&tag, &self.out, &self.metadata21, &tags)?;
-        self.add_external_libs(&mut writer, &[&artifact], &[ext_libs])?;
+        self.add_external_libs(&mut writer, &[(&artifact, self.module_dir())], &[ext_libs])?;
 
         write_cffi_module(
             &mut writer,
             &self.project_layout,
+            self.pyproject_toml.as_ref(),
             self.manifest_path.parent().unwrap(),
             &self.target_dir,
             &self.module_name,
@@ -672,7 +1044,7 @@ impl BuildContext {
     }
 
     /// Builds a wheel with cffi bindings
-    pub fn build_cffi_wheel(&self) -> Result<Vec<BuiltWheelMetadata>> {
+    pub fn build_cffi_wheel(&self) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
         let mut wheels = Vec::new();
         let artifact = self.compile_cdylib(None, None)?;
         let (policy, external_libs) = self.auditwheel(&artifact, &self.platform_tag, None)?;
@@ -681,7 +1053,9 @@ impl BuildContext {
         } else {
             self.platform_tag.clone()
         };
-        let (wheel_path, tag) = self.write_cffi_wheel(artifact, &platform_tags, external_libs)?;
+        let (wheel_path, tag) =
+            self.write_cffi_wheel(artifact, &platform_tags, external_libs.clone())?;
+        let report = self.wheel_report(&wheel_path, None, &policy, &external_libs)?;
 
         // Warn if cffi isn't specified in the requirements
         if !self
@@ -697,7 +1071,57 @@ impl BuildContext {
         }
 
         println!("📦 Built wheel to {}", wheel_path.display());
-        wheels.push((wheel_path, tag));
+        wheels.push(((wheel_path, tag), report));
+
+        Ok(wheels)
+    }
+
+    fn write_uniffi_wheel(
+        &self,
+        artifact: BuildArtifact,
+        platform_tags: &[PlatformTag],
+        ext_libs: Vec<Library>,
+    ) -> Result<BuiltWheelMetadata> {
+        let (tag, tags) = self
+            .target
+            .get_universal_tags(platform_tags, self.universal2)?;
+
+        let mut writer = WheelWriter::new(&tag, &self.out, &self.metadata21, &tags)?;
+        self.add_external_libs(&mut writer, &[(&artifact, self.module_dir())], &[ext_libs])?;
+
+        write_uniffi_module(
+            &mut writer,
+            &self.project_layout,
+            self.pyproject_toml.as_ref(),
+            self.manifest_path.parent().unwrap(),
+            &self.module_name,
+            &artifact.path,
+            &self.target,
+            self.editable,
+        )?;
+
+        self.add_pth(&mut writer)?;
+        add_data(&mut writer, self.project_layout.data.as_deref())?;
+        let wheel_path = writer.finish()?;
+        Ok((wheel_path, "py3".to_string()))
+    }
+
+    /// Builds a wheel with UniFFI bindings
+    pub fn build_uniffi_wheel(&self) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
+        let mut wheels = Vec::new();
+        let artifact = self.compile_cdylib(None, None)?;
+        let (policy, external_libs) = self.auditwheel(&artifact, &self.platform_tag, None)?;
+        let platform_tags = if self.platform_tag.is_empty() {
+            vec![policy.platform_tag()]
+        } else {
+            self.platform_tag.clone()
+        };
+        let (wheel_path, tag) =
+            self.write_uniffi_wheel(artifact, &platform_tags, external_libs.clone())?;
+        let report = self.wheel_report(&wheel_path, None, &policy, &external_libs)?;
+
+        println!("📦 Built wheel to {}", wheel_path.display());
+        wheels.push(((wheel_path, tag), report));
 
         Ok(wheels)
     }
@@ -727,15 +1151,7 @@ impl BuildContext {
 
         let mut artifacts_and_files = Vec::new();
         for artifact in artifacts {
-            // I wouldn't know of any case where this would be the wrong (and neither do
-            // I know a better alternative)
-            let bin_name = artifact
-                .path
-                .file_name()
-                .context("Couldn't get the filename from the binary produced by cargo")?
-                .to_str()
-                .context("binary produced by cargo has non-utf8 filename")?
-                .to_string();
+            let bin_name = bin_name(artifact)?;
 
             // From https://packaging.python.org/en/latest/specifications/entry-points/
             // > The name may contain any characters except =, but it cannot start or end with any
@@ -756,21 +1172,23 @@ impl BuildContext {
 
         let mut writer = WheelWriter::new(&tag, &self.out, &metadata21, &tags)?;
 
-        if let Some(python_module) = &self.project_layout.python_module {
-            if self.target.is_wasi() {
-                // TODO: Can we have python code and the wasm launchers coexisting
-                // without clashes?
-                bail!("Sorry, adding python code to a wasm binary is currently not supported")
-            }
-            if !self.editable {
-                write_python_part(&mut writer, python_module)
-                    .context("Failed to add the python module to the package")?;
-            }
+        if self.project_layout.python_module.is_some() && self.target.is_wasi() {
+            // TODO: Can we have python code and the wasm launchers coexisting
+            // without clashes?
+            bail!("Sorry, adding python code to a wasm binary is currently not supported")
+        }
+        if !self.editable {
+            write_python_part(
+                &mut writer,
+                &self.project_layout,
+                self.pyproject_toml.as_ref(),
+            )
+            .context("Failed to add the python module to the package")?;
         }
 
         let mut artifacts_ref = Vec::with_capacity(artifacts.len());
         for (artifact, bin_name) in &artifacts_and_files {
-            artifacts_ref.push(*artifact);
+            artifacts_ref.push((*artifact, self.scripts_dir()));
             write_bin(&mut writer, &artifact.path, &self.metadata21, bin_name)?;
             if self.target.is_wasi() {
                 write_wasm_launcher(&mut writer, &self.metadata21, bin_name)?;
@@ -790,7 +1208,7 @@ impl BuildContext {
     pub fn build_bin_wheel(
         &self,
         python_interpreter: Option<&PythonInterpreter>,
-    ) -> Result<Vec<BuiltWheelMetadata>> {
+    ) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
         let mut wheels = Vec::new();
         let artifacts = compile(self, python_interpreter, &self.bridge)
             .context("Failed to build a native library through cargo")?;
@@ -825,8 +1243,10 @@ impl BuildContext {
             &platform_tags,
             &ext_libs,
         )?;
+        let ext_libs: Vec<_> = ext_libs.into_iter().flatten().collect();
+        let report = self.wheel_report(&wheel_path, python_interpreter, policy, &ext_libs)?;
         println!("📦 Built wheel to {}", wheel_path.display());
-        wheels.push((wheel_path, tag));
+        wheels.push(((wheel_path, tag), report));
 
         Ok(wheels)
     }
@@ -837,7 +1257,7 @@ impl BuildContext {
     pub fn build_bin_wheels(
         &self,
         interpreters: &[PythonInterpreter],
-    ) -> Result<Vec<BuiltWheelMetadata>> {
+    ) -> Result<Vec<(BuiltWheelMetadata, WheelReport)>> {
         let mut wheels = Vec::new();
         for python_interpreter in interpreters {
             wheels.extend(self.build_bin_wheel(Some(python_interpreter))?);
@@ -854,3 +1274,28 @@ pub fn hash_file(path: impl AsRef<Path>) -> Result<String, io::Error> {
     let hex = format!("{:x}", hasher.finalize());
     Ok(hex)
 }
+
+/// The name of a binary in the scripts of the wheel
+fn bin_name(artifact: &BuildArtifact) -> Result<String> {
+    // I wouldn't know of any case where this would be the wrong (and neither do
+    // I know a better alternative)
+    Ok(artifact
+        .path
+        .file_name()
+        .context("Couldn't get the filename from the binary produced by cargo")?
+        .to_str()
+        .context("binary produced by cargo has non-utf8 filename")?
+        .to_string())
+}
+
+/// The external libraries of the main native module and of all extension modules and binaries
+fn all_external_libs<'a>(
+    ext_libs: &'a [Library],
+    extra_artifacts: impl Iterator<Item = &'a (BuildArtifact, Vec<Library>)>,
+) -> Vec<Library> {
+    ext_libs
+        .iter()
+        .chain(extra_artifacts.flat_map(|(_, libs)| libs))
+        .cloned()
+        .collect()
+}
@@ -1,8 +1,8 @@
-use crate::auditwheel::PlatformTag;
-use crate::build_context::BridgeModel;
+use crate::auditwheel::{PlatformTag, Policy};
+use crate::build_context::{BridgeModel, ExtensionModule};
 use crate::cross_compile::{find_sysconfigdata, parse_sysconfigdata};
-use crate::project_layout::ProjectResolver;
-use crate::pyproject_toml::ToolMaturin;
+use crate::project_layout::{ProjectLayout, ProjectResolver};
+use crate::pyproject_toml::{ExtensionModuleConfig, ToolMaturin};
 use crate::python_interpreter::{InterpreterConfig, InterpreterKind, MINIMUM_PYTHON_MINOR};
 use crate::{BuildContext, Metadata21, PythonInterpreter, Target};
 use anyhow::{bail, format_err, Context, Result};
@@ -124,6 +124,12 @@ pub struct CargoOptions {
     /// Rustc flags
     #[arg(num_args = 0.., trailing_var_arg = true)]
     pub args: Vec<String>,
+
+    /// The working directory of cargo, which decides the `.cargo/config.toml` files it reads.
+    /// Defaults to the current directory
+    #[arg(skip)]
+    #[serde(skip)]
+    pub current_dir: Option<PathBuf>,
 }
 
 /// High level API for building wheels from a crate which is also used for the CLI
@@ -162,7 +168,7 @@ pub struct BuildOptions {
     pub find_interpreter: bool,
 
     /// Which kind of bindings to use.
-    #[arg(short, long, value_parser = ["pyo3", "pyo3-ffi", "rust-cpython", "cffi", "bin"])]
+    #[arg(short, long, value_parser = ["pyo3", "pyo3-ffi", "rust-cpython", "cffi", "uniffi", "bin"])]
     pub bindings: Option<String>,
 
     /// The directory to store the built wheels in. Defaults to a new "wheels"
@@ -174,6 +180,13 @@ pub struct BuildOptions {
     #[arg(long = "skip-auditwheel")]
     pub skip_auditwheel: bool,
 
+    /// A json file with custom auditwheel policies in the format of auditwheel's policy files
+    ///
+    /// They are checked alongside the built-in manylinux/musllinux policies and replace those
+    /// with the same name. Use `--compatibility <name>` to check only a custom policy.
+    #[arg(long = "auditwheel-policy", value_name = "FILE")]
+    pub auditwheel_policy: Option<PathBuf>,
+
     /// For manylinux targets, use zig to ensure compliance for the chosen manylinux version
     ///
     /// Default to manylinux2014/manylinux_2_17 if you do not specify an `--compatibility`
@@ -187,6 +200,13 @@ pub struct BuildOptions {
     #[arg(long)]
     pub universal2: bool,
 
+    /// Compile the wheels for the different python interpreters concurrently
+    ///
+    /// Each interpreter gets its own cargo target directory, so the dependencies are compiled once
+    /// per interpreter. The cargo output is printed grouped by interpreter once all builds finished.
+    #[arg(long)]
+    pub parallel: bool,
+
     /// Cargo build options
     #[command(flatten)]
     pub cargo: CargoOptions,
@@ -347,7 +367,7 @@ impl BuildOptions {
                 println!("🐍 Using {} to generate the cffi bindings", interpreter);
                 Ok(vec![interpreter])
             }
-            BridgeModel::Bin(None) => Ok(vec![]),
+            BridgeModel::Bin(None) | BridgeModel::UniFfi => Ok(vec![]),
             BridgeModel::BindingsAbi3(major, minor) => {
                 if target.is_windows() {
                     // Ideally, we wouldn't want to use any python interpreter without abi3 at all.
@@ -483,6 +503,43 @@ impl BuildOptions {
         }
     }
 
+    /// Finds the python interpreters to build for, either the ones given with `-i` or all
+    /// matching the requires-python of the project with `--find-interpreter`
+    pub(crate) fn discover_interpreters(
+        &self,
+        bridge: &BridgeModel,
+        target: &Target,
+        metadata21: &Metadata21,
+        cargo_metadata: &Metadata,
+    ) -> Result<Vec<PythonInterpreter>> {
+        let generate_import_lib = is_generating_import_lib(cargo_metadata)?;
+        if self.find_interpreter {
+            // Auto-detect interpreters
+            self.find_interpreters(
+                bridge,
+                &[],
+                target,
+                get_min_python_minor(metadata21),
+                generate_import_lib,
+            )
+        } else {
+            // User given list of interpreters
+            let interpreter = if self.interpreter.is_empty() && !target.cross_compiling() {
+                if cfg!(test) {
+                    match env::var_os("MATURIN_TEST_PYTHON") {
+                        Some(python) => vec![python.into()],
+                        None => vec![PathBuf::from("python3")],
+                    }
+                } else {
+                    vec![PathBuf::from("python3")]
+                }
+            } else {
+                self.interpreter.clone()
+            };
+            self.find_interpreters(bridge, &interpreter, target, None, generate_import_lib)
+        }
+    }
+
     /// Tries to fill the missing metadata for a BuildContext by querying cargo and python
     pub fn into_build_context(
         self,
@@ -503,6 +560,11 @@ impl BuildOptions {
             mut pyproject_toml_maturin_options,
         } = ProjectResolver::resolve(self.manifest_path.clone(), self.cargo.clone())?;
         let pyproject = pyproject_toml.as_ref();
+        cargo_toml.warn_removed_python_metadata();
+        if let Some(pyproject) = pyproject {
+            pyproject.warn_missing_maturin_version();
+            pyproject.warn_missing_build_backend();
+        }
 
         let bridge = find_bridge(
             &cargo_metadata,
@@ -523,6 +585,37 @@ impl BuildOptions {
             );
         }
 
+        let extension_modules = resolve_extension_modules(
+            pyproject.map(|x| x.extension_modules()).unwrap_or_default(),
+            &bridge,
+            &project_layout,
+            &cargo_metadata,
+        )?;
+
+        let include_bins = pyproject.map(|x| x.include_bins()).unwrap_or_default();
+        let sdist_vendor = pyproject.map(|x| x.sdist_vendor()).unwrap_or_default();
+        if include_bins {
+            if !matches!(
+                bridge,
+                BridgeModel::Bindings(..) | BridgeModel::BindingsAbi3(..)
+            ) {
+                bail!(
+                    "include-bins in pyproject.toml can only be used with pyo3 or rust-cpython \
+                    bindings, but the project uses {} bindings",
+                    bridge
+                );
+            }
+            let has_bins = cargo_metadata
+                .root_package()
+                .context("Expected cargo to return metadata with root_package")?
+                .targets
+                .iter()
+                .any(|target| target.kind.iter().any(|kind| kind == "bin"));
+            if !has_bins {
+                bail!("include-bins is set in pyproject.toml, but the crate has no binaries");
+            }
+        }
+
         let mut target_triple = self.target.clone();
 
         let mut universal2 = self.universal2;
@@ -558,32 +651,8 @@ impl BuildOptions {
             None => PathBuf::from(&cargo_metadata.target_directory).join("wheels"),
         };
 
-        let generate_import_lib = is_generating_import_lib(&cargo_metadata)?;
-        let interpreter = if self.find_interpreter {
-            // Auto-detect interpreters
-            self.find_interpreters(
-                &bridge,
-                &[],
-                &target,
-                get_min_python_minor(&metadata21),
-                generate_import_lib,
-            )?
-        } else {
-            // User given list of interpreters
-            let interpreter = if self.interpreter.is_empty() && !target.cross_compiling() {
-                if cfg!(test) {
-                    match env::var_os("MATURIN_TEST_PYTHON") {
-                        Some(python) => vec![python.into()],
-                        None => vec![PathBuf::from("python3")],
-                    }
-                } else {
-                    vec![PathBuf::from("python3")]
-                }
-            } else {
-                self.interpreter.clone()
-            };
-            self.find_interpreters(&bridge, &interpreter, &target, None, generate_import_lib)?
-        };
+        let interpreter =
+            self.discover_interpreters(&bridge, &target, &metadata21, &cargo_metadata)?;
 
         if cargo_options.args.is_empty() {
             // if not supplied on command line, try pyproject.toml
@@ -597,6 +666,22 @@ impl BuildOptions {
         let strip = pyproject.map(|x| x.strip()).unwrap_or_default() || strip;
         let skip_auditwheel =
             pyproject.map(|x| x.skip_auditwheel()).unwrap_or_default() || self.skip_auditwheel;
+        let auditwheel_policy = match &self.auditwheel_policy {
+            Some(auditwheel_policy) => Some(auditwheel_policy.clone()),
+            None => pyproject
+                .and_then(|x| x.auditwheel_policy())
+                .map(|auditwheel_policy| {
+                    pyproject_toml_maturin_options.push("auditwheel-policy");
+                    pyproject_toml_path
+                        .parent()
+                        .unwrap()
+                        .join(auditwheel_policy)
+                }),
+        };
+        let auditwheel_policies = match auditwheel_policy {
+            Some(auditwheel_policy) => Policy::from_file(&auditwheel_policy)?,
+            None => Vec::new(),
+        };
         let platform_tags = if self.platform_tag.is_empty() {
             let compatibility = pyproject
                 .and_then(|x| {
@@ -632,6 +717,14 @@ impl BuildOptions {
         };
 
         for platform_tag in &platform_tags {
+            if let PlatformTag::Custom(name) = platform_tag {
+                if Policy::from_name_with_custom(name, &auditwheel_policies).is_none() {
+                    bail!(
+                        "Unknown platform tag {}, custom tags need a policy from --auditwheel-policy",
+                        name
+                    );
+                }
+            }
             if !platform_tag.is_supported() {
                 eprintln!(
                     "⚠️  Warning: {} is unsupported by the Rust compiler.",
@@ -699,6 +792,8 @@ impl BuildOptions {
             .unwrap_or_else(|| cargo_metadata.target_directory.clone().into_std_path_buf());
         let crate_name = cargo_toml.package.name;
 
+        let generate_stubs = pyproject.map(|x| x.generate_stubs()).unwrap_or_default();
+
         Ok(BuildContext {
             target,
             bridge,
@@ -716,18 +811,114 @@ impl BuildOptions {
             skip_auditwheel,
             zig: self.zig,
             platform_tag: platform_tags,
+            auditwheel_policies,
             interpreter,
             cargo_metadata,
             universal2,
+            parallel: self.parallel,
+            extension_modules,
+            include_bins,
+            sdist_vendor,
             editable,
+            generate_stubs,
             cargo_options,
         })
     }
 }
 
+/// Finds the workspace members of `[[tool.maturin.extension-modules]]` and checks that their
+/// modules can go into the same wheel as the main module
+fn resolve_extension_modules(
+    configs: &[ExtensionModuleConfig],
+    bridge: &BridgeModel,
+    project_layout: &ProjectLayout,
+    cargo_metadata: &Metadata,
+) -> Result<Vec<ExtensionModule>> {
+    if configs.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !matches!(
+        bridge,
+        BridgeModel::Bindings(..) | BridgeModel::BindingsAbi3(..)
+    ) {
+        bail!(
+            "extension-modules in pyproject.toml can only be used with pyo3 or rust-cpython \
+            bindings, but the project uses {} bindings",
+            bridge
+        );
+    }
+    let python_module = project_layout.python_module.as_ref().context(
+        "extension-modules in pyproject.toml require a mixed python/rust project, \
+        but no python package was found",
+    )?;
+    let package_name = python_module.file_name().unwrap().to_string_lossy();
+
+    let mut extension_modules = Vec::new();
+    for config in configs {
+        let parts: Vec<&str> = config.module.split('.').collect();
+        if parts.len() < 2 || parts[0] != package_name || parts.iter().any(|x| x.is_empty()) {
+            bail!(
+                "The extension module `{}` of {} must be inside the python package, \
+                e.g. `{}.{}`",
+                config.module,
+                config.package,
+                package_name,
+                config.package.replace('-', "_")
+            );
+        }
+        let member_metadata = with_root_package(cargo_metadata, &config.package)?;
+        let member = member_metadata.root_package().unwrap();
+        let member_bridge = find_bridge(&member_metadata, None).with_context(|| {
+            format!(
+                "Failed to detect the bindings of the extension module {}",
+                config.package
+            )
+        })?;
+        match (bridge, &member_bridge) {
+            (BridgeModel::BindingsAbi3(..), BridgeModel::BindingsAbi3(..))
+            | (BridgeModel::Bindings(..), BridgeModel::Bindings(..))
+            | (BridgeModel::Bindings(..), BridgeModel::BindingsAbi3(..)) => {}
+            (BridgeModel::BindingsAbi3(..), BridgeModel::Bindings(..)) => bail!(
+                "The extension module {} isn't abi3, so it can't go into the abi3 wheel",
+                config.package
+            ),
+            _ => bail!(
+                "The extension module {} must use pyo3 or rust-cpython bindings, found {}",
+                config.package,
+                member_bridge
+            ),
+        }
+        extension_modules.push(ExtensionModule {
+            crate_name: member.name.clone(),
+            module: config.module.clone(),
+            manifest_path: member.manifest_path.clone().into_std_path_buf(),
+            bridge: member_bridge,
+        });
+    }
+    Ok(extension_modules)
+}
+
+/// Returns the cargo metadata of the workspace with the given workspace member as root package,
+/// which is the package that gets compiled
+pub(crate) fn with_root_package(cargo_metadata: &Metadata, package: &str) -> Result<Metadata> {
+    let member = cargo_metadata
+        .workspace_members
+        .iter()
+        .find(|id| cargo_metadata[*id].name == package)
+        .with_context(|| format!("{} is not a member of the cargo workspace", package))?
+        .clone();
+    let mut metadata = cargo_metadata.clone();
+    metadata
+        .resolve
+        .as_mut()
+        .context("Expected to get a dependency graph from cargo")?
+        .root = Some(member);
+    Ok(metadata)
+}
+
 /// Uses very simple PEP 440 subset parsing to determine the
 /// minimum supported python minor version for interpreter search
-fn get_min_python_minor(metadata21: &Metadata21) -> Option<usize> {
+pub(crate) fn get_min_python_minor(metadata21: &Metadata21) -> Option<usize> {
     if let Some(requires_python) = &metadata21.requires_python {
         let regex = Regex::new(r#">=3\.(\d+)(?:\.\d)?"#).unwrap();
         if let Some(captures) = regex.captures(requires_python) {
@@ -885,6 +1076,8 @@ pub fn find_bridge(cargo_metadata: &Metadata, bridge: Option<&str>) -> Result<Br
     let bridge = if let Some(bindings) = bridge {
         if bindings == "cffi" {
             BridgeModel::Cffi
+        } else if bindings == "uniffi" {
+            BridgeModel::UniFfi
         } else if bindings == "bin" {
             BridgeModel::Bin(find_bindings(&deps, &packages))
         } else {
@@ -903,6 +1096,8 @@ pub fn find_bridge(cargo_metadata: &Metadata, bridge: Option<&str>) -> Result<Br
         } else {
             BridgeModel::Bindings(bindings, minor)
         }
+    } else if deps.contains_key("uniffi") && targets.contains(&"cdylib") {
+        BridgeModel::UniFfi
     } else if targets.contains(&"cdylib") {
         BridgeModel::Cffi
     } else if targets.contains(&"bin") {
@@ -1367,4 +1562,70 @@ mod test {
                 .unwrap();
         assert_eq!(get_min_python_minor(&metadata21), None);
     }
+
+    #[test]
+    fn test_resolve_extension_modules() {
+        let project_dir = Path::new("test-crates/pyo3-mixed-extension-modules");
+        let cargo_metadata = MetadataCommand::new()
+            .manifest_path(project_dir.join("Cargo.toml"))
+            .exec()
+            .unwrap();
+        let bridge = find_bridge(&cargo_metadata, None).unwrap();
+        let python_module = project_dir.join("pyo3_mixed_extension_modules");
+        let project_layout = ProjectLayout {
+            python_module: Some(python_module.clone()),
+            rust_module: python_module,
+            extension_name: "pyo3_mixed_extension_modules".to_string(),
+            data: None,
+            project_root: project_dir.to_path_buf(),
+        };
+        let config = |package: &str, module: &str| ExtensionModuleConfig {
+            package: package.to_string(),
+            module: module.to_string(),
+        };
+        let resolve = |configs: &[ExtensionModuleConfig], bridge, project_layout| {
+            resolve_extension_modules(configs, bridge, project_layout, &cargo_metadata)
+        };
+
+        let configs = [
+            config("pyo3-mixed-simd", "pyo3_mixed_extension_modules._simd"),
+            config(
+                "pyo3-mixed-codec",
+                "pyo3_mixed_extension_modules.codec._codec",
+            ),
+        ];
+        let modules = resolve(&configs, &bridge, &project_layout).unwrap();
+        let names: Vec<_> = modules
+            .iter()
+            .map(|module| (module.crate_name.as_str(), module.extension_name()))
+            .collect();
+        assert_eq!(
+            names,
+            [("pyo3-mixed-simd", "_simd"), ("pyo3-mixed-codec", "_codec")]
+        );
+        assert_eq!(
+            modules[1].manifest_path,
+            project_dir
+                .join("codec")
+                .join("Cargo.toml")
+                .canonicalize()
+                .unwrap()
+        );
+        assert!(matches!(modules[0].bridge, BridgeModel::Bindings(..)));
+
+        // The modules must be inside the python package
+        let outside = [config("pyo3-mixed-simd", "_simd")];
+        assert!(resolve(&outside, &bridge, &project_layout).is_err());
+        // Only workspace members can be extension modules
+        let unknown = [config("pyo3-mixed-io", "pyo3_mixed_extension_modules._io")];
+        assert!(resolve(&unknown, &bridge, &project_layout).is_err());
+        // Only pyo3 and rust-cpython modules can be combined
+        assert!(resolve(&configs, &BridgeModel::Cffi, &project_layout).is_err());
+        // Pure Rust projects have no python package for the modules
+        let pure_rust = ProjectLayout {
+            python_module: None,
+            ..project_layout.clone()
+        };
+        assert!(resolve(&configs, &bridge, &pure_rust).is_err());
+    }
 }
@@ -0,0 +1,174 @@
+use crate::project_layout::ProjectLayout;
+use crate::{BridgeModel, BuildContext, CargoOptions, Metadata21, PythonInterpreter};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// The bindings that were detected or selected for the crate
+#[derive(Debug, Clone, Serialize)]
+pub struct BridgePlan {
+    /// `cffi`, `bin` or the name of the bindings crate, e.g. `pyo3`
+    pub bindings: String,
+    /// Whether the crate is a binary instead of a native module
+    pub bin: bool,
+    /// Whether a single abi3 wheel is built for all python versions
+    pub abi3: bool,
+    /// The minimum python version of the bindings crate or the abi3 wheel, e.g. `3.7`
+    pub minimum_python: Option<String>,
+}
+
+impl BridgePlan {
+    fn new(bridge: &BridgeModel) -> Self {
+        let (bindings, minimum_python) = match bridge {
+            BridgeModel::Cffi => ("cffi".to_string(), None),
+            BridgeModel::UniFfi => ("uniffi".to_string(), None),
+            BridgeModel::Bin(None) => ("bin".to_string(), None),
+            BridgeModel::Bin(Some((name, minor))) | BridgeModel::Bindings(name, minor) => {
+                (name.clone(), Some(format!("3.{}", minor)))
+            }
+            BridgeModel::BindingsAbi3(major, minor) => {
+                ("pyo3".to_string(), Some(format!("{}.{}", major, minor)))
+            }
+        };
+        BridgePlan {
+            bindings,
+            bin: bridge.is_bin(),
+            abi3: matches!(bridge, BridgeModel::BindingsAbi3(..)),
+            minimum_python,
+        }
+    }
+}
+
+/// The platform the wheels are built for
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetPlan {
+    /// The rust target triple, e.g. `x86_64-unknown-linux-gnu`
+    pub triple: String,
+    /// The operating system
+    pub os: String,
+    /// The architecture
+    pub arch: String,
+    /// Whether the target is different from the host
+    pub cross_compiling: bool,
+    /// The manylinux/musllinux platform tags the wheels are built for, empty for the default
+    pub platform_tags: Vec<String>,
+    /// Whether a universal2 wheel is built on macOS
+    pub universal2: bool,
+}
+
+/// The fully resolved build configuration that `maturin inspect` prints
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildPlan<'a> {
+    /// The name of the crate
+    pub crate_name: &'a str,
+    /// The name of the native module
+    pub module_name: &'a str,
+    /// The path to Cargo.toml
+    pub manifest_path: &'a PathBuf,
+    /// The path to pyproject.toml, if it exists
+    pub pyproject_toml_path: Option<&'a PathBuf>,
+    /// Where the python and rust parts of the project are
+    pub project_layout: &'a ProjectLayout,
+    /// The bindings of the crate
+    pub bridge: BridgePlan,
+    /// The platform the wheels are built for
+    pub target: TargetPlan,
+    /// The python interpreters wheels are built for
+    pub interpreters: &'a [PythonInterpreter],
+    /// The python package metadata after merging Cargo.toml and pyproject.toml
+    pub metadata: &'a Metadata21,
+    /// The cargo options after merging the `[tool.maturin]` options from pyproject.toml
+    pub cargo_options: &'a CargoOptions,
+    /// The directory for all generated artifacts
+    pub target_dir: &'a PathBuf,
+    /// The directory the wheels are written to
+    pub out: &'a PathBuf,
+    /// Whether to build in release mode
+    pub release: bool,
+    /// Whether to strip the library
+    pub strip: bool,
+    /// Whether to skip the manylinux/musllinux compliance check
+    pub skip_auditwheel: bool,
+    /// Whether to use zig as linker
+    pub zig: bool,
+    /// Whether to build editable wheels
+    pub editable: bool,
+    /// Whether to generate the type stub of the native module
+    pub generate_stubs: bool,
+    /// Whether to put the binaries of the crate into the wheel of the native module
+    pub include_bins: bool,
+}
+
+impl<'a> BuildPlan<'a> {
+    /// Collects the build plan of a resolved build context
+    pub fn new(context: &'a BuildContext) -> Self {
+        BuildPlan {
+            crate_name: &context.crate_name,
+            module_name: &context.module_name,
+            manifest_path: &context.manifest_path,
+            pyproject_toml_path: context
+                .pyproject_toml
+                .as_ref()
+                .map(|_| &context.pyproject_toml_path),
+            project_layout: &context.project_layout,
+            bridge: BridgePlan::new(&context.bridge),
+            target: TargetPlan {
+                triple: context.target.target_triple().to_string(),
+                os: context.target.target_os().to_string(),
+                arch: context.target.target_arch().to_string(),
+                cross_compiling: context.target.cross_compiling(),
+                platform_tags: context
+                    .platform_tag
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect(),
+                universal2: context.universal2,
+            },
+            interpreters: &context.interpreter,
+            metadata: &context.metadata21,
+            cargo_options: &context.cargo_options,
+            target_dir: &context.target_dir,
+            out: &context.out,
+            release: context.release,
+            strip: context.strip,
+            skip_auditwheel: context.skip_auditwheel,
+            zig: context.zig,
+            editable: context.editable,
+            generate_stubs: context.generate_stubs,
+            include_bins: context.include_bins,
+        }
+    }
+
+    /// Serializes the build plan as pretty printed json
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize the build plan as json")
+    }
+
+    /// Serializes the build plan as toml
+    pub fn to_toml(&self) -> Result<String> {
+        toml_edit::easy::to_string_pretty(self)
+            .context("Failed to serialize the build plan as toml")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BridgePlan;
+    use crate::BridgeModel;
+
+    #[test]
+    fn test_bridge_plan() {
+        let plan = BridgePlan::new(&BridgeModel::BindingsAbi3(3, 7));
+        assert_eq!(plan.bindings, "pyo3");
+        assert!(plan.abi3 && !plan.bin);
+        assert_eq!(plan.minimum_python.as_deref(), Some("3.7"));
+
+        let plan = BridgePlan::new(&BridgeModel::Bin(Some(("pyo3".to_string(), 7))));
+        assert_eq!(plan.bindings, "pyo3");
+        assert!(plan.bin && !plan.abi3);
+
+        let plan = BridgePlan::new(&BridgeModel::Cffi);
+        assert_eq!(plan.bindings, "cffi");
+        assert!(plan.minimum_python.is_none());
+    }
+}
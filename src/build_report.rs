@@ -0,0 +1,167 @@
+use crate::auditwheel::Policy;
+use crate::build_context::hash_file;
+use crate::PythonInterpreter;
+use anyhow::{Context, Result};
+use fs_err as fs;
+use lddtree::Library;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The python interpreter a wheel was built for
+#[derive(Debug, Clone, Serialize)]
+pub struct InterpreterReport {
+    /// The python implementation, e.g. `CPython` or `PyPy`
+    pub implementation: String,
+    /// The python version, e.g. `3.10`
+    pub version: String,
+}
+
+/// An external shared library that was bundled into a wheel
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalLibReport {
+    /// The name the library is linked by
+    pub name: String,
+    /// The path the library was copied from
+    pub path: Option<PathBuf>,
+}
+
+/// Everything that went into a built wheel
+#[derive(Debug, Clone, Serialize)]
+pub struct WheelReport {
+    /// The path to the wheel
+    pub path: PathBuf,
+    /// The expanded compatibility tags of the wheel, e.g. `cp37-abi3-manylinux_2_17_x86_64`
+    pub tags: Vec<String>,
+    /// The sha256 of the wheel file
+    pub sha256: String,
+    /// The python interpreter the wheel was built for, none for abi3 and binary wheels
+    pub interpreter: Option<InterpreterReport>,
+    /// The kind of bindings, e.g. `pyo3` or `bin`
+    pub bridge: String,
+    /// The manylinux/musllinux policy the wheel was checked against, only set on linux
+    pub auditwheel_policy: Option<String>,
+    /// The external shared libraries that were bundled into the wheel
+    pub external_libs: Vec<ExternalLibReport>,
+}
+
+impl WheelReport {
+    /// Collects the report for a freshly built wheel
+    pub fn new(
+        path: &Path,
+        python_interpreter: Option<&PythonInterpreter>,
+        bridge: String,
+        auditwheel_policy: Option<&Policy>,
+        external_libs: &[Library],
+    ) -> Result<Self> {
+        Ok(WheelReport {
+            path: path.to_path_buf(),
+            tags: wheel_tags(path),
+            sha256: hash_file(path)
+                .with_context(|| format!("Failed to hash {}", path.display()))?,
+            interpreter: python_interpreter.map(|interpreter| InterpreterReport {
+                implementation: interpreter.interpreter_kind.to_string(),
+                version: format!("{}.{}", interpreter.major, interpreter.minor),
+            }),
+            bridge,
+            auditwheel_policy: auditwheel_policy.map(|policy| policy.name.clone()),
+            external_libs: external_libs
+                .iter()
+                .map(|lib| ExternalLibReport {
+                    name: lib.name.clone(),
+                    path: lib.realpath.clone(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// A built source distribution
+#[derive(Debug, Clone, Serialize)]
+pub struct SdistReport {
+    /// The path to the source distribution
+    pub path: PathBuf,
+    /// The sha256 of the source distribution
+    pub sha256: String,
+}
+
+/// The machine readable summary of a build that `maturin build --report` writes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BuildReport {
+    /// The built wheels
+    pub wheels: Vec<WheelReport>,
+    /// The source distribution, if one was built
+    pub sdist: Option<SdistReport>,
+}
+
+impl BuildReport {
+    /// Creates the report for the built wheels and the source distribution at `sdist`
+    pub fn new(wheels: Vec<WheelReport>, sdist: Option<&Path>) -> Result<Self> {
+        let sdist = match sdist {
+            Some(path) => Some(SdistReport {
+                path: path.to_path_buf(),
+                sha256: hash_file(path)
+                    .with_context(|| format!("Failed to hash {}", path.display()))?,
+            }),
+            None => None,
+        };
+        Ok(BuildReport { wheels, sdist })
+    }
+
+    /// Writes the report as json
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json + "\n")
+            .with_context(|| format!("Failed to write the build report to {}", path.display()))
+    }
+}
+
+/// Expands the compressed tag sets in a wheel filename, e.g.
+/// `foo-1.0-cp37-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64.whl` has the tags
+/// `cp37-abi3-manylinux_2_17_x86_64` and `cp37-abi3-manylinux2014_x86_64`
+fn wheel_tags(wheel: &Path) -> Vec<String> {
+    let stem = wheel
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let parts: Vec<_> = stem.split('-').collect();
+    if parts.len() < 5 {
+        return Vec::new();
+    }
+    let (python, abi, platform) = (
+        parts[parts.len() - 3],
+        parts[parts.len() - 2],
+        parts[parts.len() - 1],
+    );
+    let mut tags = Vec::new();
+    for python in python.split('.') {
+        for abi in abi.split('.') {
+            for platform in platform.split('.') {
+                tags.push(format!("{}-{}-{}", python, abi, platform));
+            }
+        }
+    }
+    tags
+}
+
+#[cfg(test)]
+mod test {
+    use super::wheel_tags;
+    use std::path::Path;
+
+    #[test]
+    fn test_wheel_tags() {
+        let tags = wheel_tags(Path::new(
+            "dist/foo-1.0-cp37-abi3-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+        ));
+        assert_eq!(
+            tags,
+            [
+                "cp37-abi3-manylinux_2_17_x86_64",
+                "cp37-abi3-manylinux2014_x86_64"
+            ]
+        );
+        let tags = wheel_tags(Path::new("foo-1.0-py3.py2-none-any.whl"));
+        assert_eq!(tags, ["py3-none-any", "py2-none-any"]);
+        assert!(wheel_tags(Path::new("foo.whl")).is_empty());
+    }
+}
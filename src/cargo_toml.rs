@@ -55,6 +55,16 @@ impl CargoToml {
 
     /// Warn about removed python metadata support in `Cargo.toml`
     pub fn warn_removed_python_metadata(&self) -> bool {
+        if let Some(message) = self.removed_python_metadata() {
+            println!("⚠️  Warning: {}", message);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the warning for `warn_removed_python_metadata`, if any
+    pub(crate) fn removed_python_metadata(&self) -> Option<String> {
         let mut removed = Vec::new();
         if let Some(CargoTomlMetadata {
             maturin: Some(extra_metadata),
@@ -80,15 +90,14 @@ impl CargoToml {
             }
         }
         if !removed.is_empty() {
-            println!(
-                "⚠️  Warning: the following metadata fields in `package.metadata.maturin` section \
+            Some(format!(
+                "the following metadata fields in `package.metadata.maturin` section \
                 of Cargo.toml are removed since maturin 0.14.0: {}, \
                 please set them in pyproject.toml as PEP 621 specifies.",
                 removed.join(", ")
-            );
-            true
+            ))
         } else {
-            false
+            None
         }
     }
 }
@@ -0,0 +1,344 @@
+use crate::build_options::{find_bridge, get_min_python_minor};
+use crate::module_writer::find_uniffi_udl;
+use crate::project_layout::ProjectResolver;
+use crate::{BridgeModel, BuildOptions, Target};
+use anyhow::{bail, Result};
+use fs_err as fs;
+use regex::Regex;
+use std::fmt;
+use std::path::Path;
+
+/// How bad a problem found by `maturin check` or `maturin check-dist` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    /// The build works, but probably not the way it was intended
+    Warning,
+    /// The build or importing the module will fail
+    Error,
+}
+
+/// A problem with the project found by `maturin check` or with a distribution found by
+/// `maturin check-dist`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Diagnostic {
+    pub(crate) severity: Severity,
+    pub(crate) message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn warning(message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn error(message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Warning => write!(f, "⚠️  Warning: {}", self.message),
+            Severity::Error => write!(f, "❌ Error: {}", self.message),
+        }
+    }
+}
+
+/// Checks the project configuration without compiling anything and prints all problems found
+///
+/// This resolves the project layout, the bindings, the python metadata and the interpreters like
+/// `maturin build` does, but continues after the first problem where possible.
+pub fn check_project(build_options: &BuildOptions) -> Result<()> {
+    let mut diagnostics = Vec::new();
+    if let Err(err) = diagnose(build_options, &mut diagnostics) {
+        diagnostics.push(Diagnostic::error(format!("{:#}", err)));
+    }
+
+    println!();
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    if errors > 0 {
+        bail!(
+            "Found {} error(s) and {} warning(s) in the project",
+            errors,
+            warnings
+        );
+    } else if warnings > 0 {
+        println!("🔍 Found {} warning(s) in the project", warnings);
+    } else {
+        println!("✅ No problems found");
+    }
+    Ok(())
+}
+
+/// Collects the problems of the project, returning an error for problems that stop the checks
+fn diagnose(build_options: &BuildOptions, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+    let ProjectResolver {
+        project_layout,
+        cargo_toml_path,
+        cargo_toml,
+        pyproject_toml_path,
+        pyproject_toml,
+        module_name,
+        metadata21,
+        cargo_metadata,
+        ..
+    } = ProjectResolver::resolve(
+        build_options.manifest_path.clone(),
+        build_options.cargo.clone(),
+    )?;
+    let pyproject = pyproject_toml.as_ref();
+
+    if let Some(message) = cargo_toml.removed_python_metadata() {
+        diagnostics.push(Diagnostic::warning(message));
+    }
+    match pyproject {
+        Some(pyproject) => {
+            if let Some(message) = pyproject.missing_maturin_version() {
+                diagnostics.push(Diagnostic::warning(message));
+            }
+            if let Some(message) = pyproject.missing_build_backend() {
+                diagnostics.push(Diagnostic::warning(message));
+            }
+            if let Some(python_source) = pyproject.python_source() {
+                if project_layout.python_module.is_none() {
+                    let project_root = pyproject_toml_path.parent().unwrap();
+                    let package = project_layout
+                        .extension_name
+                        .split('.')
+                        .next()
+                        .unwrap_or(&project_layout.extension_name);
+                    let message = if !project_root.join(python_source).is_dir() {
+                        format!(
+                            "python-source `{}` in pyproject.toml doesn't exist",
+                            python_source.display()
+                        )
+                    } else {
+                        format!(
+                            "python-source `{}` in pyproject.toml doesn't contain a `{}` package, \
+                            so the python source is ignored",
+                            python_source.display(),
+                            package
+                        )
+                    };
+                    diagnostics.push(Diagnostic::error(message));
+                }
+            }
+            if let Some(project_name) = pyproject.project_name() {
+                let crate_name = &cargo_toml.package.name;
+                if normalize_name(project_name) != normalize_name(crate_name) {
+                    diagnostics.push(Diagnostic::warning(format!(
+                        "project.name `{}` in pyproject.toml differs from the crate name `{}`, \
+                        the package is published as `{}` but imported as `{}`",
+                        project_name, crate_name, project_name, project_layout.extension_name
+                    )));
+                }
+            }
+        }
+        None => diagnostics.push(Diagnostic::warning(format!(
+            "{} doesn't exist, so pip can't build the project from source and \
+            no source distribution can be built",
+            pyproject_toml_path.display()
+        ))),
+    }
+
+    let bindings = build_options
+        .bindings
+        .as_deref()
+        .or_else(|| pyproject.and_then(|x| x.bindings()));
+    let bridge = match find_bridge(&cargo_metadata, bindings) {
+        Ok(bridge) => bridge,
+        Err(err) => {
+            // Everything below depends on the bindings
+            diagnostics.push(Diagnostic::error(format!("{:#}", err)));
+            return Ok(());
+        }
+    };
+
+    if !bridge.is_bin() && module_name.contains('-') {
+        diagnostics.push(Diagnostic::error(format!(
+            "The module name `{}` must not contain a minus, \
+            set an appropriate [lib] name in your Cargo.toml",
+            module_name
+        )));
+    }
+
+    if bridge.is_bindings("pyo3") || matches!(bridge, BridgeModel::BindingsAbi3(..)) {
+        let lib_src = cargo_metadata.root_package().and_then(|package| {
+            package
+                .targets
+                .iter()
+                .find(|target| target.kind.iter().any(|kind| kind == "cdylib"))
+                .map(|target| target.src_path.clone().into_std_path_buf())
+        });
+        if let Some(lib_src) = lib_src {
+            let module_names = find_pymodules(lib_src.parent().unwrap())?;
+            let extension_name = &project_layout.extension_name;
+            if module_names.is_empty() {
+                diagnostics.push(Diagnostic::warning(format!(
+                    "Couldn't find a `#[pymodule]` in {}",
+                    lib_src.parent().unwrap().display()
+                )));
+            } else if !module_names.contains(extension_name) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "The `#[pymodule]` is named {} but python expects `{}`, \
+                    importing the module will fail. Rename the function or use \
+                    `#[pyo3(name = \"{}\")]`",
+                    module_names
+                        .iter()
+                        .map(|name| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    extension_name,
+                    extension_name
+                )));
+            }
+        }
+    }
+
+    if bridge == BridgeModel::UniFfi {
+        if let Err(err) = find_uniffi_udl(cargo_toml_path.parent().unwrap()) {
+            diagnostics.push(Diagnostic::error(format!("{:#}", err)));
+        }
+    }
+
+    if let Some(message) = check_requires_python(
+        metadata21.requires_python.as_deref(),
+        get_min_python_minor(&metadata21),
+        &bridge,
+    ) {
+        diagnostics.push(Diagnostic::warning(message));
+    }
+
+    let target = Target::from_target_triple(build_options.target.clone())?;
+    if let Err(err) =
+        build_options.discover_interpreters(&bridge, &target, &metadata21, &cargo_metadata)
+    {
+        diagnostics.push(Diagnostic::error(format!("{:#}", err)));
+    }
+
+    Ok(())
+}
+
+/// Normalizes a package name as specified in PEP 503 for comparisons
+pub(crate) fn normalize_name(name: &str) -> String {
+    Regex::new(r"[-_.]+")
+        .unwrap()
+        .replace_all(name, "-")
+        .to_lowercase()
+}
+
+/// Finds the names of the `#[pymodule]`s in the rust sources, taking `#[pyo3(name = "...")]`
+/// into account
+fn find_pymodules(src_dir: &Path) -> Result<Vec<String>> {
+    let mut module_names = Vec::new();
+    for entry in ignore::Walk::new(src_dir) {
+        let path = entry?.into_path();
+        if path.extension().map(|ext| ext == "rs").unwrap_or_default() {
+            module_names.extend(pymodule_names(&fs::read_to_string(&path)?));
+        }
+    }
+    Ok(module_names)
+}
+
+/// Extracts the module names of all `#[pymodule]` functions from rust source code
+fn pymodule_names(source: &str) -> Vec<String> {
+    let regex = Regex::new(
+        r#"(?:#\[pyo3\(name\s*=\s*"(\w+)"\)\]\s*)?#\[pymodule\]\s*(?:#\[pyo3\(name\s*=\s*"(\w+)"\)\]\s*)?(?:pub(?:\([^)]*\))?\s+)?fn\s+(\w+)"#,
+    )
+    .unwrap();
+    regex
+        .captures_iter(source)
+        .filter_map(|captures| {
+            captures
+                .get(1)
+                .or_else(|| captures.get(2))
+                .or_else(|| captures.get(3))
+                .map(|name| name.as_str().to_string())
+        })
+        .collect()
+}
+
+/// Checks that requires-python doesn't allow python versions the wheels can't support
+fn check_requires_python(
+    requires_python: Option<&str>,
+    min_python_minor: Option<usize>,
+    bridge: &BridgeModel,
+) -> Option<String> {
+    let (requires_python, min_python_minor) = (requires_python?, min_python_minor?);
+    let (bridge_minor, reason) = match bridge {
+        BridgeModel::BindingsAbi3(3, minor) => (*minor as usize, "the abi3 wheels"),
+        BridgeModel::Bindings(_, minor) | BridgeModel::Bin(Some((_, minor))) => {
+            (*minor, "the bindings crate")
+        }
+        _ => return None,
+    };
+    if min_python_minor < bridge_minor {
+        Some(format!(
+            "requires-python `{}` allows Python 3.{}, but {} need at least Python 3.{}",
+            requires_python, min_python_minor, reason, bridge_minor
+        ))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_requires_python, normalize_name, pymodule_names};
+    use crate::BridgeModel;
+
+    #[test]
+    fn test_pymodule_names() {
+        let source = r#"
+            #[pymodule]
+            fn foo(_py: Python, m: &PyModule) -> PyResult<()> {
+                Ok(())
+            }
+
+            #[pymodule]
+            #[pyo3(name = "bar")]
+            pub fn bar_module(_py: Python, m: &PyModule) -> PyResult<()> {
+                Ok(())
+            }
+
+            #[pyo3(name = "baz")]
+            #[pymodule]
+            pub(crate) fn baz_module(_py: Python, m: &PyModule) -> PyResult<()> {
+                Ok(())
+            }
+
+            #[pyfunction]
+            fn qux() {}
+        "#;
+        assert_eq!(pymodule_names(source), ["foo", "bar", "baz"]);
+    }
+
+    #[test]
+    fn test_normalize_name() {
+        assert_eq!(normalize_name("Hello_World"), "hello-world");
+        assert_eq!(normalize_name("hello.-world"), "hello-world");
+    }
+
+    #[test]
+    fn test_check_requires_python() {
+        let abi3 = BridgeModel::BindingsAbi3(3, 7);
+        assert!(check_requires_python(Some(">=3.6"), Some(6), &abi3).is_some());
+        assert!(check_requires_python(Some(">=3.7"), Some(7), &abi3).is_none());
+        assert!(check_requires_python(Some(">=3.8"), Some(8), &abi3).is_none());
+        assert!(check_requires_python(None, None, &abi3).is_none());
+        assert!(check_requires_python(Some(">=3.6"), Some(6), &BridgeModel::Cffi).is_none());
+    }
+}
@@ -0,0 +1,845 @@
+use crate::check::{normalize_name, Diagnostic, Severity};
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use fs_err::File;
+use python_pkginfo::Metadata;
+use regex::Regex;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use std::collections::{BTreeSet, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The trove classifiers PyPI accepts, one per line
+///
+/// This is a snapshot of the [trove-classifiers](https://pypi.org/project/trove-classifiers/)
+/// package, which PyPI uses to validate uploads. PyPI adds classifiers over time, so a classifier
+/// missing from the snapshot is only a warning. To update it, run
+/// `python -c "from trove_classifiers import sorted_classifiers; print(*sorted_classifiers, sep='\n')" > src/classifiers.txt`
+/// with the latest trove-classifiers installed.
+const CLASSIFIERS: &str = include_str!("classifiers.txt");
+
+/// The metadata versions PyPI accepts
+const METADATA_VERSIONS: &[&str] = &["1.0", "1.1", "1.2", "2.0", "2.1", "2.2", "2.3"];
+
+/// Checks wheels and source distributions for problems that make the upload fail or break the
+/// project page, similar to `twine check`, and prints all problems found
+pub fn check_dists(paths: &[PathBuf]) -> Result<()> {
+    let mut failed = Vec::new();
+    for path in paths {
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        println!("🔍 Checking {}", filename);
+        let diagnostics = check_dist(path);
+        for diagnostic in &diagnostics {
+            println!("    {}", diagnostic);
+        }
+        if diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            failed.push(filename);
+        } else {
+            println!("✅ {} passed", filename);
+        }
+    }
+    if !failed.is_empty() {
+        bail!(
+            "{} distribution(s) failed the checks: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Collects the problems of a single wheel or source distribution
+fn check_dist(path: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let filename = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let result = if filename.ends_with(".whl") {
+        check_wheel(path, filename, &mut diagnostics)
+    } else if filename.ends_with(".tar.gz") || filename.ends_with(".zip") {
+        check_sdist(path, filename, &mut diagnostics)
+    } else {
+        Err(anyhow::anyhow!(
+            "{} is neither a wheel nor a source distribution (.whl, .tar.gz or .zip)",
+            path.display()
+        ))
+    };
+    if let Err(err) = result {
+        diagnostics.push(Diagnostic::error(format!("{:#}", err)));
+    }
+    diagnostics
+}
+
+/// Checks the filename, the metadata, the tags and the RECORD of a wheel
+fn check_wheel(path: &Path, filename: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .with_context(|| format!("Failed to read {} as zip file", path.display()))?;
+    let mut file_names: Vec<String> = archive.file_names().map(ToString::to_string).collect();
+    file_names.sort();
+
+    let parts: Vec<&str> = filename.trim_end_matches(".whl").split('-').collect();
+    let (name, version, tags) = match parts[..] {
+        [name, version, python, abi, platform] | [name, version, _, python, abi, platform] => {
+            (name, version, expand_tags(python, abi, platform))
+        }
+        _ => bail!(
+            "{} is not a valid wheel filename, expected {{name}}-{{version}}(-{{build}})?-{{python}}-{{abi}}-{{platform}}.whl",
+            filename
+        ),
+    };
+
+    let dist_info_dirs: BTreeSet<&str> = file_names
+        .iter()
+        .filter_map(|file_name| file_name.split('/').next())
+        .filter(|dir| dir.ends_with(".dist-info"))
+        .collect();
+    let dist_info = match dist_info_dirs.iter().collect::<Vec<_>>()[..] {
+        [dist_info] => dist_info.to_string(),
+        _ => bail!(
+            "Expected exactly one .dist-info directory in the wheel, found {}",
+            dist_info_dirs.len()
+        ),
+    };
+    if let Some((dist_info_name, dist_info_version)) =
+        dist_info.trim_end_matches(".dist-info").rsplit_once('-')
+    {
+        if normalize_name(dist_info_name) != normalize_name(name)
+            || !versions_match(dist_info_version, version)
+        {
+            diagnostics.push(Diagnostic::error(format!(
+                "The .dist-info directory {} doesn't match the filename",
+                dist_info
+            )));
+        }
+    }
+
+    let metadata_path = format!("{}/METADATA", dist_info);
+    match read_zip_file(&mut archive, &metadata_path)? {
+        Some(metadata) => {
+            if let Some(metadata) = check_metadata(&metadata, diagnostics) {
+                check_filename(&metadata, name, version, diagnostics);
+            }
+        }
+        None => diagnostics.push(Diagnostic::error(format!(
+            "{} is missing from the wheel",
+            metadata_path
+        ))),
+    }
+
+    let wheel_path = format!("{}/WHEEL", dist_info);
+    match read_zip_file(&mut archive, &wheel_path)? {
+        Some(wheel) => {
+            let wheel = String::from_utf8_lossy(&wheel);
+            let wheel_tags: BTreeSet<String> = wheel
+                .lines()
+                .filter_map(|line| line.strip_prefix("Tag:"))
+                .map(|tag| tag.trim().to_string())
+                .collect();
+            if wheel_tags != tags {
+                diagnostics.push(Diagnostic::error(format!(
+                    "The tags of the filename ({}) don't match the tags in {} ({})",
+                    tags.into_iter().collect::<Vec<_>>().join(", "),
+                    wheel_path,
+                    wheel_tags.into_iter().collect::<Vec<_>>().join(", ")
+                )));
+            }
+        }
+        None => diagnostics.push(Diagnostic::error(format!(
+            "{} is missing from the wheel",
+            wheel_path
+        ))),
+    }
+
+    check_record(&mut archive, &file_names, &dist_info, diagnostics)
+}
+
+/// Checks the filename, the layout and the metadata of a source distribution
+fn check_sdist(path: &Path, filename: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<()> {
+    let stem = filename
+        .trim_end_matches(".tar.gz")
+        .trim_end_matches(".zip");
+    let (name, version) = stem.rsplit_once('-').with_context(|| {
+        format!(
+            "{} is not a valid source distribution filename, expected {{name}}-{{version}}.tar.gz",
+            filename
+        )
+    })?;
+
+    let pkg_info_path = format!("{}/PKG-INFO", stem);
+    let mut file_names = Vec::new();
+    let mut pkg_info = None;
+    if filename.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(File::open(path)?)
+            .with_context(|| format!("Failed to read {} as zip file", path.display()))?;
+        file_names.extend(archive.file_names().map(ToString::to_string));
+        pkg_info = read_zip_file(&mut archive, &pkg_info_path)?;
+    } else {
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+        for entry in archive
+            .entries()
+            .with_context(|| format!("Failed to read {} as tar.gz file", path.display()))?
+        {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.to_string_lossy().to_string();
+            if entry_path == pkg_info_path {
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content)?;
+                pkg_info = Some(content);
+            }
+            file_names.push(entry_path);
+        }
+    }
+
+    let top_level: BTreeSet<&str> = file_names
+        .iter()
+        .filter_map(|file_name| file_name.split('/').next())
+        .collect();
+    if top_level.len() != 1 || !top_level.contains(stem) {
+        diagnostics.push(Diagnostic::error(format!(
+            "A source distribution must contain a single {} directory, found {}",
+            stem,
+            top_level.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+
+    match pkg_info {
+        Some(pkg_info) => {
+            if let Some(metadata) = check_metadata(&pkg_info, diagnostics) {
+                check_filename(&metadata, name, version, diagnostics);
+            }
+        }
+        None => diagnostics.push(Diagnostic::error(format!(
+            "{} is missing from the source distribution",
+            pkg_info_path
+        ))),
+    }
+    Ok(())
+}
+
+/// Reads a file from a zip archive, `None` if it doesn't exist
+fn read_zip_file(archive: &mut zip::ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", name)),
+    };
+    let mut content = Vec::new();
+    file.read_to_end(&mut content)?;
+    Ok(Some(content))
+}
+
+/// Expands the compressed tag set of a wheel filename, e.g. `py2.py3-none-any` to
+/// `py2-none-any` and `py3-none-any`
+fn expand_tags(python: &str, abi: &str, platform: &str) -> BTreeSet<String> {
+    let mut tags = BTreeSet::new();
+    for python in python.split('.') {
+        for abi in abi.split('.') {
+            for platform in platform.split('.') {
+                tags.insert(format!("{}-{}-{}", python, abi, platform));
+            }
+        }
+    }
+    tags
+}
+
+/// Compares a version from a filename with one from the metadata, accounting for the escaping
+/// of the filename
+fn versions_match(filename_version: &str, version: &str) -> bool {
+    let filename_version = filename_version.replace('_', "-");
+    match (
+        pep440::Version::parse(&filename_version),
+        pep440::Version::parse(version),
+    ) {
+        (Some(left), Some(right)) => left.normalize() == right.normalize(),
+        _ => filename_version == version,
+    }
+}
+
+/// Checks that the name and the version of the filename match the metadata
+fn check_filename(
+    metadata: &Metadata,
+    name: &str,
+    version: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if normalize_name(name) != normalize_name(&metadata.name) {
+        diagnostics.push(Diagnostic::error(format!(
+            "The filename has the name {}, but the metadata has {}",
+            name, metadata.name
+        )));
+    }
+    if !versions_match(version, &metadata.version) {
+        diagnostics.push(Diagnostic::error(format!(
+            "The filename has the version {}, but the metadata has {}",
+            version, metadata.version
+        )));
+    }
+}
+
+/// Checks the core metadata, returning the parsed metadata if it has the required fields
+fn check_metadata(content: &[u8], diagnostics: &mut Vec<Diagnostic>) -> Option<Metadata> {
+    let metadata = match Metadata::parse(content) {
+        Ok(metadata) => metadata,
+        Err(python_pkginfo::Error::FieldNotFound(field)) => {
+            diagnostics.push(Diagnostic::error(format!(
+                "The metadata is missing the required field {}",
+                field
+            )));
+            return None;
+        }
+        Err(err) => {
+            diagnostics.push(Diagnostic::error(format!(
+                "Failed to parse the metadata: {}",
+                err
+            )));
+            return None;
+        }
+    };
+
+    if !METADATA_VERSIONS.contains(&metadata.metadata_version.as_str()) {
+        diagnostics.push(Diagnostic::error(format!(
+            "Unsupported Metadata-Version {}, expected one of {}",
+            metadata.metadata_version,
+            METADATA_VERSIONS.join(", ")
+        )));
+    }
+    let name_re = Regex::new(r"(?i)^([a-z0-9]|[a-z0-9][a-z0-9._-]*[a-z0-9])$").unwrap();
+    if !name_re.is_match(&metadata.name) {
+        diagnostics.push(Diagnostic::error(format!(
+            "{:?} is not a valid project name, it must consist of ascii letters, digits, `.`, `_` and `-` and start and end with a letter or digit",
+            metadata.name
+        )));
+    }
+    match pep440::Version::parse(&metadata.version) {
+        Some(version) if !version.local_str().is_empty() => {
+            diagnostics.push(Diagnostic::warning(format!(
+                "The version {} has a local version label, which PyPI rejects",
+                metadata.version
+            )));
+        }
+        Some(_) => {}
+        None => diagnostics.push(Diagnostic::error(format!(
+            "{} is not a valid PEP 440 version",
+            metadata.version
+        ))),
+    }
+    if let Some(summary) = &metadata.summary {
+        if summary.contains('\n') {
+            diagnostics.push(Diagnostic::error("The summary must be a single line"));
+        }
+        if summary.chars().count() > 512 {
+            diagnostics.push(Diagnostic::error(
+                "The summary is longer than the 512 characters PyPI allows",
+            ));
+        }
+    }
+    check_description(&metadata, diagnostics);
+    check_classifiers(&metadata.classifiers, diagnostics);
+    Some(metadata)
+}
+
+/// Checks that the classifiers are in the list of trove classifiers
+///
+/// Unknown classifiers are only a warning since the list may be older than the one on PyPI
+fn check_classifiers(classifiers: &[String], diagnostics: &mut Vec<Diagnostic>) {
+    let known: HashSet<&str> = CLASSIFIERS.lines().collect();
+    for classifier in classifiers {
+        if classifier.starts_with("Private ::") {
+            diagnostics.push(Diagnostic::error(format!(
+                "PyPI rejects private classifiers like `{}`",
+                classifier
+            )));
+        } else if !known.contains(classifier.as_str()) {
+            diagnostics.push(Diagnostic::warning(format!(
+                "`{}` is not a known trove classifier, PyPI rejects the upload unless it was \
+                added after this version of maturin",
+                classifier
+            )));
+        }
+    }
+}
+
+/// Checks that `Description-Content-Type` is valid and fits the description
+fn check_description(metadata: &Metadata, diagnostics: &mut Vec<Diagnostic>) {
+    let description = match &metadata.description {
+        Some(description) if !description.trim().is_empty() => description,
+        _ => {
+            diagnostics.push(Diagnostic::warning(
+                "The metadata has no description, the project page will be empty",
+            ));
+            return;
+        }
+    };
+    let content_type = match &metadata.description_content_type {
+        Some(content_type) => content_type.as_str(),
+        None => {
+            diagnostics.push(Diagnostic::warning(
+                "Description-Content-Type is missing, PyPI renders the description as reStructuredText",
+            ));
+            "text/x-rst"
+        }
+    };
+
+    let mut parts = content_type.split(';');
+    let mime_type = parts.next().unwrap_or_default().trim().to_lowercase();
+    for parameter in parts {
+        let (key, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = value.trim().trim_matches('"');
+        match key.trim().to_lowercase().as_str() {
+            "charset" if !value.eq_ignore_ascii_case("utf-8") => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "The description must be utf-8 encoded, but Description-Content-Type is {}",
+                    content_type
+                )));
+            }
+            "variant"
+                if mime_type == "text/markdown" && !["GFM", "CommonMark"].contains(&value) =>
+            {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Unknown markdown variant {}, PyPI supports GFM and CommonMark",
+                    value
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    match mime_type.as_str() {
+        "text/plain" => {}
+        "text/x-rst" => {
+            for problem in rst_problems(description) {
+                diagnostics.push(Diagnostic::error(format!(
+                    "The description fails to render as reStructuredText: {}",
+                    problem
+                )));
+            }
+            if looks_like_markdown(description) {
+                diagnostics.push(Diagnostic::warning(
+                    "The description looks like markdown, but is rendered as reStructuredText. Set Description-Content-Type to text/markdown",
+                ));
+            }
+        }
+        "text/markdown" => {
+            let directive = Regex::new(r"(?m)^\.\. [\w-]+::").unwrap();
+            if directive.is_match(description) {
+                diagnostics.push(Diagnostic::warning(
+                    "The description looks like reStructuredText, but is rendered as markdown. Set Description-Content-Type to text/x-rst",
+                ));
+            }
+        }
+        _ => diagnostics.push(Diagnostic::error(format!(
+            "Unsupported Description-Content-Type {}, PyPI supports text/plain, text/x-rst and text/markdown",
+            content_type
+        ))),
+    }
+}
+
+/// Finds the section titles whose underline or overline is too short, which makes docutils and
+/// therefore PyPI refuse to render the description
+fn rst_problems(description: &str) -> Vec<String> {
+    let lines: Vec<&str> = description.lines().collect();
+    let mut problems = Vec::new();
+    for (index, pair) in lines.windows(2).enumerate() {
+        let (title, underline) = (pair[0], pair[1]);
+        if title.trim().is_empty()
+            || title.starts_with(char::is_whitespace)
+            || is_adornment(title)
+            || !is_adornment(underline)
+        {
+            continue;
+        }
+        if underline.trim_end().chars().count() < title.trim_end().chars().count() {
+            problems.push(format!(
+                "line {}: the title underline is too short for {:?}",
+                index + 2,
+                title.trim_end()
+            ));
+        }
+    }
+    problems
+}
+
+/// Whether the line is a section title adornment like `=====`
+fn is_adornment(line: &str) -> bool {
+    let line = line.trim_end();
+    match line.chars().next() {
+        Some(first) => {
+            first.is_ascii_punctuation()
+                && line.chars().count() >= 4
+                && line.chars().all(|char| char == first)
+        }
+        None => false,
+    }
+}
+
+/// Whether the description has markdown headings, fenced code blocks or links
+fn looks_like_markdown(description: &str) -> bool {
+    let markdown = Regex::new(r"(?m)^(#{1,6} \S|```)|\]\(https?://").unwrap();
+    markdown.is_match(description)
+}
+
+/// Checks that every file of the wheel is in RECORD with the right hash and size
+fn check_record(
+    archive: &mut zip::ZipArchive<File>,
+    file_names: &[String],
+    dist_info: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    let record_path = format!("{}/RECORD", dist_info);
+    let record = match read_zip_file(archive, &record_path)? {
+        Some(record) => String::from_utf8(record).context("RECORD is not utf-8")?,
+        None => {
+            diagnostics.push(Diagnostic::error(format!(
+                "{} is missing from the wheel",
+                record_path
+            )));
+            return Ok(());
+        }
+    };
+    // Signatures of RECORD can't be in RECORD
+    let unrecorded = [
+        record_path.clone(),
+        format!("{}.jws", record_path),
+        format!("{}.p7s", record_path),
+    ];
+
+    let mut recorded = HashSet::new();
+    for (index, line) in record.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (path, hash, size) = match parse_record_line(line) {
+            Some(entry) => entry,
+            None => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Line {} of RECORD is malformed: {}",
+                    index + 1,
+                    line
+                )));
+                continue;
+            }
+        };
+        recorded.insert(path.clone());
+        if unrecorded.contains(&path) {
+            continue;
+        }
+        let (algorithm, expected) = match hash.split_once('=') {
+            Some(hash) => hash,
+            None => {
+                diagnostics.push(Diagnostic::error(format!("{} has no hash in RECORD", path)));
+                continue;
+            }
+        };
+        let mut content = Vec::new();
+        match archive.by_name(&path) {
+            Ok(mut file) => file.read_to_end(&mut content)?,
+            Err(zip::result::ZipError::FileNotFound) => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "{} is in RECORD, but not in the wheel",
+                    path
+                )));
+                continue;
+            }
+            Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path)),
+        };
+        let digest = match algorithm {
+            "sha256" => Sha256::digest(&content).to_vec(),
+            "sha384" => Sha384::digest(&content).to_vec(),
+            "sha512" => Sha512::digest(&content).to_vec(),
+            _ => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "{} uses the hash algorithm {} in RECORD, only sha256 or stronger is allowed",
+                    path, algorithm
+                )));
+                continue;
+            }
+        };
+        if base64::encode_config(&digest, base64::URL_SAFE_NO_PAD) != expected.trim_end_matches('=')
+        {
+            diagnostics.push(Diagnostic::error(format!(
+                "The {} of {} doesn't match RECORD",
+                algorithm, path
+            )));
+        }
+        if !size.is_empty() && size.parse::<usize>().ok() != Some(content.len()) {
+            diagnostics.push(Diagnostic::error(format!(
+                "The size of {} is {} bytes, but RECORD says {}",
+                path,
+                content.len(),
+                size
+            )));
+        }
+    }
+
+    for file_name in file_names {
+        if !file_name.ends_with('/') && !recorded.contains(file_name) {
+            diagnostics.push(Diagnostic::error(format!(
+                "{} is in the wheel, but not in RECORD",
+                file_name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Splits a line of RECORD into path, hash and size. The path is the only field that can
+/// contain commas, in which case it's quoted
+fn parse_record_line(line: &str) -> Option<(String, &str, &str)> {
+    let mut fields = line.rsplitn(3, ',');
+    let size = fields.next()?;
+    let hash = fields.next()?;
+    let path = fields.next()?;
+    let path = match path
+        .strip_prefix('"')
+        .and_then(|path| path.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => path.to_string(),
+    };
+    Some((path, hash, size))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        check_classifiers, check_dist, expand_tags, parse_record_line, rst_problems,
+        versions_match, CLASSIFIERS,
+    };
+    use crate::check::Severity;
+    use fs_err::File;
+    use sha2::{Digest, Sha256};
+    use std::io::Write;
+    use std::path::Path;
+    use zip::write::FileOptions;
+
+    /// Writes a wheel with the files and a RECORD of their hashes, unless RECORD is one of them
+    fn write_wheel(path: &Path, files: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let mut record = String::new();
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+            let hash =
+                base64::encode_config(Sha256::digest(content.as_bytes()), base64::URL_SAFE_NO_PAD);
+            record.push_str(&format!("{},sha256={},{}\n", name, hash, content.len()));
+        }
+        if !files.iter().any(|(name, _)| name.ends_with("RECORD")) {
+            record.push_str("foo-1.0.dist-info/RECORD,,\n");
+            zip.start_file("foo-1.0.dist-info/RECORD", FileOptions::default())
+                .unwrap();
+            zip.write_all(record.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn messages(path: &Path, severity: Severity) -> Vec<String> {
+        check_dist(path)
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    const WHEEL: &str =
+        "Wheel-Version: 1.0\nRoot-Is-Purelib: false\nTag: py2-none-any\nTag: py3-none-any\n";
+
+    #[test]
+    fn test_check_wheel() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo-1.0-py2.py3-none-any.whl");
+        let metadata = "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\nClassifier: Programming Language :: Rust\nDescription-Content-Type: text/markdown\n\n# Foo\n";
+        write_wheel(
+            &path,
+            &[
+                ("foo/__init__.py", "print('foo')\n"),
+                ("foo-1.0.dist-info/METADATA", metadata),
+                ("foo-1.0.dist-info/WHEEL", WHEEL),
+            ],
+        );
+        assert!(check_dist(&path).is_empty(), "{:?}", check_dist(&path));
+
+        let path = temp_dir.path().join("foo-1.1-cp37-abi3-any.whl");
+        let metadata = "Metadata-Version: 2.1\nName: bar\nVersion: 1.0\nClassifier: Programming Language :: Rust :: Fast\nDescription-Content-Type: text/x-rst\n\nFoo\n==\n\nBarbaz\n====\n";
+        write_wheel(
+            &path,
+            &[
+                ("foo-1.0.dist-info/METADATA", metadata),
+                ("foo-1.0.dist-info/WHEEL", WHEEL),
+            ],
+        );
+        assert_eq!(
+            messages(&path, Severity::Error),
+            [
+                "The .dist-info directory foo-1.0.dist-info doesn't match the filename",
+                "The description fails to render as reStructuredText: line 5: the title underline is too short for \"Barbaz\"",
+                "The filename has the name foo, but the metadata has bar",
+                "The filename has the version 1.1, but the metadata has 1.0",
+                "The tags of the filename (cp37-abi3-any) don't match the tags in foo-1.0.dist-info/WHEEL (py2-none-any, py3-none-any)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_wheel_record() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo-1.0-py3-none-any.whl");
+        let metadata = "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\n";
+        let hash =
+            base64::encode_config(Sha256::digest(b"print('foo')\n"), base64::URL_SAFE_NO_PAD);
+        let record = format!(
+            "foo/__init__.py,sha256={},13\nfoo/bar.py,sha256={},13\nfoo-1.0.dist-info/METADATA,sha1=abc,1\nfoo-1.0.dist-info/RECORD,,\n",
+            hash, hash
+        );
+        write_wheel(
+            &path,
+            &[
+                ("foo/__init__.py", "print('bar')\n"),
+                ("foo/baz.py", ""),
+                ("foo-1.0.dist-info/METADATA", metadata),
+                (
+                    "foo-1.0.dist-info/WHEEL",
+                    "Wheel-Version: 1.0\nTag: py3-none-any\n",
+                ),
+                ("foo-1.0.dist-info/RECORD", &record),
+            ],
+        );
+        assert_eq!(
+            messages(&path, Severity::Error),
+            [
+                "The sha256 of foo/__init__.py doesn't match RECORD",
+                "foo/bar.py is in RECORD, but not in the wheel",
+                "foo-1.0.dist-info/METADATA uses the hash algorithm sha1 in RECORD, only sha256 or stronger is allowed",
+                "foo-1.0.dist-info/WHEEL is in the wheel, but not in RECORD",
+                "foo/baz.py is in the wheel, but not in RECORD",
+            ]
+        );
+        assert_eq!(
+            messages(&path, Severity::Warning),
+            ["The metadata has no description, the project page will be empty"]
+        );
+    }
+
+    #[test]
+    fn test_check_sdist() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("foo-1.0.tar.gz");
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        ));
+        let pkg_info = b"Metadata-Version: 2.1\nName: foo\nVersion: 1.0.0+local\nSummary: Foo\nDescription-Content-Type: text/markdown; charset=latin1\n\nFoo\n";
+        for (name, content) in [
+            ("foo-1.0/PKG-INFO", &pkg_info[..]),
+            ("bar/Cargo.toml", &b""[..]),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, content).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(
+            messages(&path, Severity::Error),
+            [
+                "A source distribution must contain a single foo-1.0 directory, found bar, foo-1.0",
+                "The description must be utf-8 encoded, but Description-Content-Type is text/markdown; charset=latin1",
+                "The filename has the version 1.0, but the metadata has 1.0.0+local",
+            ]
+        );
+        assert_eq!(
+            messages(&path, Severity::Warning),
+            ["The version 1.0.0+local has a local version label, which PyPI rejects"]
+        );
+
+        let path = temp_dir.path().join("foo-1.0.tar.bz2");
+        assert_eq!(
+            messages(&path, Severity::Error),
+            [format!(
+                "{} is neither a wheel nor a source distribution (.whl, .tar.gz or .zip)",
+                path.display()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_rst_problems() {
+        let description = "Foo\n===\n\nBarbaz\n~~~~\n\n.. code-block:: python\n\n   a == b\n\n----\n\nQux\n----\n";
+        assert_eq!(
+            rst_problems(description),
+            ["line 5: the title underline is too short for \"Barbaz\""]
+        );
+    }
+
+    #[test]
+    fn test_record_and_tags() {
+        assert_eq!(
+            parse_record_line("\"foo,bar.py\",sha256=abc,3"),
+            Some(("foo,bar.py".to_string(), "sha256=abc", "3"))
+        );
+        assert_eq!(
+            parse_record_line("foo-1.0.dist-info/RECORD,,"),
+            Some(("foo-1.0.dist-info/RECORD".to_string(), "", ""))
+        );
+        assert_eq!(parse_record_line("foo.py"), None);
+        assert_eq!(
+            expand_tags("cp37", "abi3", "manylinux_2_17_x86_64.manylinux2014_x86_64")
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [
+                "cp37-abi3-manylinux2014_x86_64",
+                "cp37-abi3-manylinux_2_17_x86_64"
+            ]
+        );
+        assert!(versions_match("1.0_rc1", "1.0-rc.1"));
+        assert!(versions_match("1.0.post1", "1.0-1"));
+        assert!(!versions_match("1.0", "1.1"));
+    }
+
+    #[test]
+    fn test_check_classifiers() {
+        let classifiers = [
+            "Programming Language :: Rust".to_string(),
+            "Programming Language :: Rust :: Fast".to_string(),
+            "Private :: Do Not Upload".to_string(),
+        ];
+        let mut diagnostics = Vec::new();
+        check_classifiers(&classifiers, &mut diagnostics);
+        let diagnostics: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.message.as_str()))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                (
+                    Severity::Warning,
+                    "`Programming Language :: Rust :: Fast` is not a known trove classifier, \
+                    PyPI rejects the upload unless it was added after this version of maturin"
+                ),
+                (
+                    Severity::Error,
+                    "PyPI rejects private classifiers like `Private :: Do Not Upload`"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_classifiers_are_sorted_and_unique() {
+        let classifiers: Vec<&str> = CLASSIFIERS.lines().collect();
+        let mut sorted = classifiers.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(classifiers, sorted);
+        assert!(classifiers.contains(&"License :: OSI Approved :: MIT License"));
+    }
+}
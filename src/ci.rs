@@ -0,0 +1,479 @@
+use crate::build_options::{find_bridge, get_min_python_minor};
+use crate::project_layout::ProjectResolver;
+use crate::{BridgeModel, CargoOptions, PlatformTag};
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use fs_err as fs;
+use minijinja::{context, Environment};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// The python minor version used on the CI runners unless requires-python asks for a newer one
+const DEFAULT_PYTHON_MINOR: usize = 10;
+
+/// The CI provider to generate the configuration for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CIProvider {
+    /// GitHub Actions
+    #[value(name = "github")]
+    GitHub,
+    /// GitLab CI/CD
+    #[value(name = "gitlab")]
+    GitLab,
+    /// Azure Pipelines
+    #[value(name = "azure")]
+    Azure,
+}
+
+impl Default for CIProvider {
+    fn default() -> Self {
+        CIProvider::GitHub
+    }
+}
+
+/// The platforms wheels can be built for in CI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CIPlatform {
+    /// Linux, as manylinux/musllinux wheels
+    #[value(name = "linux")]
+    Linux,
+    /// Windows
+    #[value(name = "windows")]
+    Windows,
+    /// macOS
+    #[value(name = "macos")]
+    Macos,
+}
+
+impl CIPlatform {
+    /// The target names that can be used for the platform and their rust target triples
+    fn targets(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            CIPlatform::Linux => &[
+                ("x86_64", "x86_64-unknown-linux-gnu"),
+                ("x86", "i686-unknown-linux-gnu"),
+                ("aarch64", "aarch64-unknown-linux-gnu"),
+                ("armv7", "armv7-unknown-linux-gnueabihf"),
+                ("s390x", "s390x-unknown-linux-gnu"),
+                ("ppc64le", "powerpc64le-unknown-linux-gnu"),
+            ],
+            CIPlatform::Windows => &[
+                ("x64", "x86_64-pc-windows-msvc"),
+                ("x86", "i686-pc-windows-msvc"),
+                ("aarch64", "aarch64-pc-windows-msvc"),
+            ],
+            CIPlatform::Macos => &[
+                ("x86_64", "x86_64-apple-darwin"),
+                ("aarch64", "aarch64-apple-darwin"),
+            ],
+        }
+    }
+
+    /// The targets that are built when none are given
+    fn default_targets(&self) -> &'static [&'static str] {
+        match self {
+            CIPlatform::Linux => &["x86_64", "aarch64"],
+            CIPlatform::Windows => &["x64", "x86"],
+            CIPlatform::Macos => &["x86_64", "aarch64"],
+        }
+    }
+
+    /// The target of the CI runners, which is the only one the wheels can be tested on
+    fn native_target(&self) -> &'static str {
+        match self {
+            CIPlatform::Linux | CIPlatform::Macos => "x86_64",
+            CIPlatform::Windows => "x64",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            CIPlatform::Linux => "linux",
+            CIPlatform::Windows => "windows",
+            CIPlatform::Macos => "macos",
+        }
+    }
+}
+
+/// Generate CI configuration for an existing project
+#[derive(Debug, Default, clap::Parser)]
+pub struct GenerateCI {
+    /// The CI provider
+    #[arg(value_enum)]
+    pub provider: CIProvider,
+    /// Path to Cargo.toml
+    #[arg(short = 'm', long, value_name = "PATH")]
+    pub manifest_path: Option<PathBuf>,
+    /// Write the configuration to this file instead of stdout
+    #[arg(short = 'o', long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// The platforms to build wheels on, defaults to linux, windows and macos
+    /// (only linux for gitlab)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub platform: Vec<CIPlatform>,
+    /// The linux targets to build wheels for, defaults to x86_64 and aarch64
+    ///
+    /// Possible values: x86_64, x86, aarch64, armv7, s390x, ppc64le
+    #[arg(long, value_delimiter = ',', value_name = "TARGET")]
+    pub linux_target: Vec<String>,
+    /// The windows targets to build wheels for, defaults to x64 and x86
+    ///
+    /// Possible values: x64, x86, aarch64
+    #[arg(long, value_delimiter = ',', value_name = "TARGET")]
+    pub windows_target: Vec<String>,
+    /// The macOS targets to build wheels for, defaults to x86_64 and aarch64
+    ///
+    /// Possible values: x86_64, aarch64
+    #[arg(long, value_delimiter = ',', value_name = "TARGET")]
+    pub macos_target: Vec<String>,
+    /// Install the built wheels and run pytest, which is the default when there is a `tests`
+    /// directory
+    #[arg(long)]
+    pub pytest: bool,
+    /// Don't build a source distribution
+    #[arg(long)]
+    pub no_sdist: bool,
+}
+
+/// What the generated CI configuration needs to know about the project
+#[derive(Debug, Clone)]
+pub(crate) struct CIProject {
+    /// The name of the python package
+    pub name: String,
+    /// The bindings of the crate
+    pub bridge: BridgeModel,
+    /// The `[tool.maturin] compatibility` of the project
+    pub compatibility: Option<PlatformTag>,
+    /// Whether the project has a pyproject.toml, which is required for the sdist
+    pub sdist: bool,
+    /// Whether the project has python tests
+    pub tests: bool,
+    /// The minimum python minor version from `requires-python`
+    pub min_python_minor: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct TargetContext {
+    arch: &'static str,
+    triple: &'static str,
+    cross: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct PlatformContext {
+    name: &'static str,
+    runner: &'static str,
+    targets: Vec<TargetContext>,
+    native_arch: &'static str,
+    native_triple: &'static str,
+    /// Whether the wheels for the runner's own target are built, so they can be tested
+    testable: bool,
+}
+
+impl GenerateCI {
+    /// Reads the bindings and settings of the project and writes the CI configuration
+    pub fn execute(&self) -> Result<()> {
+        let config = self.generate()?;
+        match &self.output {
+            Some(output) => {
+                fs::write(output, config)?;
+                println!("📝 Wrote CI configuration to {}", output.display());
+            }
+            None => print!("{}", config),
+        }
+        Ok(())
+    }
+
+    /// Reads the bindings and settings of the project and renders the CI configuration
+    pub fn generate(&self) -> Result<String> {
+        let ProjectResolver {
+            pyproject_toml_path,
+            pyproject_toml,
+            metadata21,
+            cargo_metadata,
+            ..
+        } = ProjectResolver::resolve(self.manifest_path.clone(), CargoOptions::default())?;
+        let pyproject = pyproject_toml.as_ref();
+        let bridge = find_bridge(&cargo_metadata, pyproject.and_then(|x| x.bindings()))?;
+        let min_python_minor = get_min_python_minor(&metadata21);
+        let project = CIProject {
+            name: metadata21.name,
+            bridge,
+            compatibility: pyproject.and_then(|x| x.compatibility()),
+            sdist: pyproject.is_some(),
+            tests: pyproject_toml_path.parent().unwrap().join("tests").is_dir(),
+            min_python_minor,
+        };
+        self.render(&project)
+    }
+
+    /// Renders the CI configuration for the project
+    pub(crate) fn render(&self, project: &CIProject) -> Result<String> {
+        let platforms = self.platforms()?;
+
+        let mut build_args = "--release --out dist".to_string();
+        if matches!(
+            project.bridge,
+            BridgeModel::Bindings(..) | BridgeModel::Bin(Some(..))
+        ) {
+            // Every python version needs its own wheel
+            build_args.push_str(" --find-interpreter");
+        }
+        if let Some(manifest_path) = &self.manifest_path {
+            build_args.push_str(&format!(" --manifest-path {}", manifest_path.display()));
+        }
+        let manylinux = match &project.compatibility {
+            Some(PlatformTag::Linux) => "off".to_string(),
+            Some(tag @ PlatformTag::Manylinux { .. })
+            | Some(tag @ PlatformTag::Musllinux { .. }) => tag.to_string(),
+            _ => "auto".to_string(),
+        };
+        let pytest = self.pytest || project.tests;
+        let pytest_needs = platforms
+            .iter()
+            .find(|platform| platform.name == "linux")
+            .and_then(|platform| {
+                platform
+                    .targets
+                    .iter()
+                    .find(|target| !target.cross)
+                    .map(|target| format!("linux-{}", target.arch))
+            })
+            .filter(|_| pytest);
+        let sdist = project.sdist && !self.no_sdist;
+        // The python that runs maturin and the tests on the runners, which must satisfy
+        // requires-python
+        let python_version = format!(
+            "3.{}",
+            project
+                .min_python_minor
+                .unwrap_or(DEFAULT_PYTHON_MINOR)
+                .max(DEFAULT_PYTHON_MINOR)
+        );
+        let mut jobs: Vec<_> = platforms.iter().map(|platform| platform.name).collect();
+        if sdist {
+            jobs.push("sdist");
+        }
+
+        let (template, source) = match self.provider {
+            CIProvider::GitHub => ("github", include_str!("templates/ci/github.yml.j2")),
+            CIProvider::GitLab => ("gitlab", include_str!("templates/ci/gitlab.yml.j2")),
+            CIProvider::Azure => ("azure", include_str!("templates/ci/azure.yml.j2")),
+        };
+        let mut env = Environment::new();
+        env.add_template(template, source)?;
+        let version_major: usize = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap();
+        let version_minor: usize = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap();
+        let config = env.get_template(template)?.render(context!(
+            version => env!("CARGO_PKG_VERSION"),
+            version_major => version_major,
+            version_minor => version_minor,
+            command => self.command(),
+            name => project.name,
+            build_args => build_args,
+            manifest_path => self.manifest_path,
+            manylinux => manylinux,
+            python_version => python_version,
+            platforms => platforms,
+            pytest => pytest,
+            pytest_needs => pytest_needs,
+            sdist => sdist,
+            jobs => jobs
+        ))?;
+        Ok(config + "\n")
+    }
+
+    /// Resolves the platforms and their targets, using the defaults where nothing was given
+    fn platforms(&self) -> Result<Vec<PlatformContext>> {
+        let platforms = if !self.platform.is_empty() {
+            self.platform.clone()
+        } else if self.provider == CIProvider::GitLab {
+            vec![CIPlatform::Linux]
+        } else {
+            vec![CIPlatform::Linux, CIPlatform::Windows, CIPlatform::Macos]
+        };
+        let mut contexts = Vec::new();
+        for platform in platforms {
+            let runner = match (self.provider, platform) {
+                (CIProvider::GitLab, CIPlatform::Linux) => "",
+                (CIProvider::GitLab, _) => bail!(
+                    "GitLab CI only supports building on linux, {} wheels need to be built elsewhere",
+                    platform.name()
+                ),
+                (_, CIPlatform::Linux) => "ubuntu-latest",
+                (_, CIPlatform::Windows) => "windows-latest",
+                (CIProvider::GitHub, CIPlatform::Macos) => "macos-latest",
+                (CIProvider::Azure, CIPlatform::Macos) => "macOS-latest",
+            };
+            let targets = match platform {
+                CIPlatform::Linux => &self.linux_target,
+                CIPlatform::Windows => &self.windows_target,
+                CIPlatform::Macos => &self.macos_target,
+            };
+            let targets: Vec<&str> = if targets.is_empty() {
+                platform.default_targets().to_vec()
+            } else {
+                targets.iter().map(String::as_str).collect()
+            };
+            let native_arch = platform.native_target();
+            let native_triple = platform
+                .targets()
+                .iter()
+                .find(|(arch, _)| *arch == native_arch)
+                .map(|(_, triple)| *triple)
+                .unwrap();
+            let targets: Vec<TargetContext> = targets
+                .into_iter()
+                .map(
+                    |arch| match platform.targets().iter().find(|(name, _)| *name == arch) {
+                        Some((arch, triple)) => Ok(TargetContext {
+                            arch,
+                            triple,
+                            cross: *arch != native_arch,
+                        }),
+                        None => bail!(
+                            "Unsupported {} target {}, possible values are {}",
+                            platform.name(),
+                            arch,
+                            platform
+                                .targets()
+                                .iter()
+                                .map(|(name, _)| *name)
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    },
+                )
+                .collect::<Result<_>>()?;
+            let testable = targets.iter().any(|target| !target.cross);
+            contexts.push(PlatformContext {
+                name: platform.name(),
+                runner,
+                targets,
+                native_arch,
+                native_triple,
+                testable,
+            });
+        }
+        Ok(contexts)
+    }
+
+    /// The command line that regenerates the configuration
+    fn command(&self) -> String {
+        let provider = self.provider.to_possible_value().unwrap();
+        let mut command = format!("maturin generate-ci {}", provider.get_name());
+        if let Some(manifest_path) = &self.manifest_path {
+            command.push_str(&format!(" --manifest-path {}", manifest_path.display()));
+        }
+        if let Some(output) = &self.output {
+            command.push_str(&format!(" --output {}", output.display()));
+        }
+        if !self.platform.is_empty() {
+            let platforms: Vec<_> = self.platform.iter().map(CIPlatform::name).collect();
+            command.push_str(&format!(" --platform {}", platforms.join(",")));
+        }
+        for (option, targets) in [
+            ("--linux-target", &self.linux_target),
+            ("--windows-target", &self.windows_target),
+            ("--macos-target", &self.macos_target),
+        ]
+        .iter()
+        {
+            if !targets.is_empty() {
+                command.push_str(&format!(" {} {}", option, targets.join(",")));
+            }
+        }
+        if self.pytest {
+            command.push_str(" --pytest");
+        }
+        if self.no_sdist {
+            command.push_str(" --no-sdist");
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CIPlatform, CIProject, CIProvider, GenerateCI};
+    use crate::BridgeModel;
+    use clap::Parser;
+
+    fn project(bridge: BridgeModel) -> CIProject {
+        CIProject {
+            name: "example".to_string(),
+            bridge,
+            compatibility: None,
+            sdist: true,
+            tests: false,
+            min_python_minor: None,
+        }
+    }
+
+    #[test]
+    fn test_github_defaults() {
+        let generate_ci = GenerateCI::parse_from(["generate-ci", "github"]);
+        let config = generate_ci
+            .render(&project(BridgeModel::Bindings("pyo3".to_string(), 7)))
+            .unwrap();
+        assert!(config.contains("#    maturin generate-ci github\n"));
+        assert!(config.contains("args: --release --out dist --find-interpreter\n"));
+        assert!(config.contains("target: [x86_64, aarch64]"));
+        assert!(config.contains("target: [x64, x86]"));
+        assert!(config.contains("needs: [linux, windows, macos, sdist]"));
+        assert!(config.contains("python-version: '3.10'"));
+        assert!(!config.contains("pytest"));
+    }
+
+    #[test]
+    fn test_requires_python() {
+        let mut project = project(BridgeModel::Bindings("pyo3".to_string(), 7));
+        project.min_python_minor = Some(12);
+        for provider in ["github", "gitlab", "azure"] {
+            let generate_ci =
+                GenerateCI::parse_from(["generate-ci", provider, "--platform", "linux"]);
+            let config = generate_ci.render(&project).unwrap();
+            assert!(!config.contains("3.10"), "{}", config);
+            assert!(config.contains("3.12"), "{}", config);
+        }
+    }
+
+    #[test]
+    fn test_gitlab_abi3() {
+        let generate_ci = GenerateCI::parse_from([
+            "generate-ci",
+            "gitlab",
+            "--linux-target",
+            "x86_64,armv7",
+            "--pytest",
+            "--no-sdist",
+        ]);
+        assert_eq!(generate_ci.provider, CIProvider::GitLab);
+        let config = generate_ci
+            .render(&project(BridgeModel::BindingsAbi3(3, 7)))
+            .unwrap();
+        assert!(config.contains("linux-x86_64:"));
+        assert!(config.contains(
+            "maturin build --release --out dist --target armv7-unknown-linux-gnueabihf --zig"
+        ));
+        assert!(config.contains("needs: [linux-x86_64]"));
+        assert!(!config.contains("find-interpreter"));
+        assert!(!config.contains("sdist:"));
+    }
+
+    #[test]
+    fn test_invalid_targets() {
+        let generate_ci = GenerateCI {
+            provider: CIProvider::GitLab,
+            platform: vec![CIPlatform::Windows],
+            ..Default::default()
+        };
+        assert!(generate_ci.render(&project(BridgeModel::Cffi)).is_err());
+        let generate_ci = GenerateCI {
+            provider: CIProvider::Azure,
+            macos_target: vec!["ppc64le".to_string()],
+            ..Default::default()
+        };
+        assert!(generate_ci.render(&project(BridgeModel::Cffi)).is_err());
+    }
+}
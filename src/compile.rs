@@ -10,6 +10,7 @@ use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::str;
+use std::thread;
 
 /// The first version of pyo3 that supports building Windows abi3 wheel
 /// without `PYO3_NO_PYTHON` environment variable
@@ -35,6 +36,17 @@ pub fn compile(
     context: &BuildContext,
     python_interpreter: Option<&PythonInterpreter>,
     bindings_crate: &BridgeModel,
+) -> Result<Vec<HashMap<String, BuildArtifact>>> {
+    compile_with_output(context, python_interpreter, bindings_crate, None)
+}
+
+/// Like [compile], but collects the output of cargo in `output` instead of printing it if given,
+/// so that concurrent builds don't interleave their output
+pub(crate) fn compile_with_output(
+    context: &BuildContext,
+    python_interpreter: Option<&PythonInterpreter>,
+    bindings_crate: &BridgeModel,
+    output: Option<&mut String>,
 ) -> Result<Vec<HashMap<String, BuildArtifact>>> {
     let root_pkg = context.cargo_metadata.root_package().unwrap();
     let mut targets: Vec<_> = root_pkg
@@ -59,9 +71,21 @@ pub fn compile(
         }
     }
     if context.target.is_macos() && context.universal2 {
-        compile_universal2(context, python_interpreter, bindings_crate, &targets)
+        compile_universal2(
+            context,
+            python_interpreter,
+            bindings_crate,
+            &targets,
+            output,
+        )
     } else {
-        compile_targets(context, python_interpreter, bindings_crate, &targets)
+        compile_targets(
+            context,
+            python_interpreter,
+            bindings_crate,
+            &targets,
+            output,
+        )
     }
 }
 
@@ -71,6 +95,7 @@ fn compile_universal2(
     python_interpreter: Option<&PythonInterpreter>,
     bindings_crate: &BridgeModel,
     targets: &[&cargo_metadata::Target],
+    mut output: Option<&mut String>,
 ) -> Result<Vec<HashMap<String, BuildArtifact>>> {
     let build_type = if bindings_crate.is_bin() {
         "bin"
@@ -85,14 +110,20 @@ fn compile_universal2(
         python_interpreter,
         bindings_crate,
         targets,
+        output.as_deref_mut(),
     )
     .context("Failed to build a aarch64 library through cargo")?;
     let mut x86_64_context = context.clone();
     x86_64_context.target = Target::from_target_triple(Some("x86_64-apple-darwin".to_string()))?;
 
-    let x86_64_artifacts =
-        compile_targets(&x86_64_context, python_interpreter, bindings_crate, targets)
-            .context("Failed to build a x86_64 library through cargo")?;
+    let x86_64_artifacts = compile_targets(
+        &x86_64_context,
+        python_interpreter,
+        bindings_crate,
+        targets,
+        output,
+    )
+    .context("Failed to build a x86_64 library through cargo")?;
 
     let mut universal_artifacts = Vec::with_capacity(targets.len());
     for (aarch64_artifact, x86_64_artifact) in aarch64_artifacts.iter().zip(x86_64_artifacts) {
@@ -151,6 +182,7 @@ fn compile_targets(
     python_interpreter: Option<&PythonInterpreter>,
     bindings_crate: &BridgeModel,
     targets: &[&cargo_metadata::Target],
+    mut output: Option<&mut String>,
 ) -> Result<Vec<HashMap<String, BuildArtifact>>> {
     let mut artifacts = Vec::with_capacity(targets.len());
     for target in targets {
@@ -159,6 +191,7 @@ fn compile_targets(
             python_interpreter,
             bindings_crate,
             target,
+            output.as_deref_mut(),
         )?);
     }
     Ok(artifacts)
@@ -169,6 +202,7 @@ fn compile_target(
     python_interpreter: Option<&PythonInterpreter>,
     bindings_crate: &BridgeModel,
     binding_target: &cargo_metadata::Target,
+    mut output: Option<&mut String>,
 ) -> Result<HashMap<String, BuildArtifact>> {
     let target = &context.target;
 
@@ -200,7 +234,10 @@ fn compile_target(
         BridgeModel::Bin(..) => {
             cargo_rustc.bin.push(binding_target.name.clone());
         }
-        BridgeModel::Cffi | BridgeModel::Bindings(..) | BridgeModel::BindingsAbi3(..) => {
+        BridgeModel::Cffi
+        | BridgeModel::UniFfi
+        | BridgeModel::Bindings(..)
+        | BridgeModel::BindingsAbi3(..) => {
             cargo_rustc.lib = true;
             // https://github.com/rust-lang/rust/issues/59302#issue-422994250
             // We must only do this for libraries as it breaks binaries
@@ -301,6 +338,10 @@ fn compile_target(
         build.build_command()?
     };
 
+    if let Some(current_dir) = &context.cargo_options.current_dir {
+        build_command.current_dir(current_dir);
+    }
+
     if context.zig {
         // Pass zig command to downstream, eg. python3-dll-a
         if let Ok((zig_cmd, zig_args)) = cargo_zigbuild::Zig::find_zig() {
@@ -320,7 +361,11 @@ fn compile_target(
         .stdout(Stdio::piped())
         // We can't get colored human and json messages from rustc as they are mutually exclusive,
         // but forwarding stderr is still useful in case there some non-json error
-        .stderr(Stdio::inherit());
+        .stderr(if output.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        });
 
     if let Some(flags) = rust_flags {
         build_command.env("RUSTFLAGS", flags);
@@ -388,6 +433,15 @@ fn compile_target(
         .spawn()
         .context("Failed to run `cargo rustc`")?;
 
+    // Read stderr on another thread, otherwise cargo blocks once the pipe is full
+    let stderr_reader = cargo_build.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut buffer = String::new();
+            let _ = stderr.read_to_string(&mut buffer);
+            buffer
+        })
+    });
+
     let mut artifacts = HashMap::new();
     let mut linked_paths = Vec::new();
 
@@ -413,9 +467,12 @@ fn compile_target(
                             && !artifact.features.contains(&"rustc-dep-of-std".to_string());
                         if should_warn {
                             // This is a spurious error I don't really understand
-                            println!(
-                                "⚠️  Warning: The package {} wasn't listed in `cargo metadata`",
-                                package_id
+                            print_or_capture(
+                                &mut output,
+                                &format!(
+                                    "⚠️  Warning: The package {} wasn't listed in `cargo metadata`",
+                                    package_id
+                                ),
                             );
                         }
                         continue;
@@ -450,7 +507,7 @@ fn compile_target(
                 }
             }
             cargo_metadata::Message::CompilerMessage(msg) => {
-                println!("{}", msg.message);
+                print_or_capture(&mut output, &msg.message.to_string());
             }
             _ => (),
         }
@@ -465,6 +522,10 @@ fn compile_target(
         .wait()
         .expect("Failed to wait on cargo child process");
 
+    if let (Some(output), Some(stderr_reader)) = (output, stderr_reader) {
+        output.push_str(&stderr_reader.join().unwrap_or_default());
+    }
+
     if !status.success() {
         bail!(
             r#"Cargo build finished with "{}": `{:?}`"#,
@@ -476,6 +537,17 @@ fn compile_target(
     Ok(artifacts)
 }
 
+/// Prints a line of the cargo output or appends it to the collected output
+fn print_or_capture(output: &mut Option<&mut String>, line: &str) {
+    match output {
+        Some(output) => {
+            output.push_str(line);
+            output.push('\n');
+        }
+        None => println!("{}", line),
+    }
+}
+
 /// Checks that the native library contains a function called `PyInit_<module name>` and warns
 /// if it's missing.
 ///
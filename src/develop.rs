@@ -57,8 +57,10 @@ pub fn develop(
         bindings,
         out: Some(wheel_dir.path().to_path_buf()),
         skip_auditwheel: false,
+        auditwheel_policy: None,
         zig: false,
         universal2: false,
+        parallel: false,
         cargo: CargoOptions {
             target: target_triple,
             ..cargo_options
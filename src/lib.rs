@@ -25,9 +25,16 @@
 
 #![deny(missing_docs)]
 
-pub use crate::build_context::{BridgeModel, BuildContext, BuiltWheelMetadata};
+pub use crate::build_context::{BridgeModel, BuildContext, BuiltWheelMetadata, ExtensionModule};
 pub use crate::build_options::{BuildOptions, CargoOptions};
+pub use crate::build_plan::{BridgePlan, BuildPlan, TargetPlan};
+pub use crate::build_report::{
+    BuildReport, ExternalLibReport, InterpreterReport, SdistReport, WheelReport,
+};
 pub use crate::cargo_toml::CargoToml;
+pub use crate::check::check_project;
+pub use crate::check_dist::check_dists;
+pub use crate::ci::{CIPlatform, CIProvider, GenerateCI};
 pub use crate::compile::{compile, BuildArtifact};
 pub use crate::develop::develop;
 pub use crate::metadata::{Metadata21, WheelMetadata};
@@ -37,15 +44,21 @@ pub use crate::module_writer::{
 pub use crate::new_project::{init_project, new_project, GenerateProjectOptions};
 pub use crate::pyproject_toml::PyProjectToml;
 pub use crate::python_interpreter::PythonInterpreter;
+pub use crate::run_tests::TestOptions;
 pub use crate::target::Target;
 #[cfg(feature = "upload")]
 pub use crate::upload::{upload, upload_ui, PublishOpt, Registry, UploadError};
-pub use auditwheel::PlatformTag;
+pub use auditwheel::{audit_wheels, repair_wheel, PlatformTag, Policy};
 
 mod auditwheel;
 mod build_context;
 mod build_options;
+mod build_plan;
+mod build_report;
 mod cargo_toml;
+mod check;
+mod check_dist;
+mod ci;
 mod compile;
 mod cross_compile;
 mod develop;
@@ -55,7 +68,9 @@ mod new_project;
 mod project_layout;
 mod pyproject_toml;
 mod python_interpreter;
+mod run_tests;
 mod source_distribution;
 mod target;
+mod type_stub;
 #[cfg(feature = "upload")]
 mod upload;
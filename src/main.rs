@@ -8,14 +8,17 @@ use cargo_zigbuild::Zig;
 use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Generator;
 use maturin::{
-    develop, init_project, new_project, write_dist_info, BridgeModel, BuildOptions, CargoOptions,
-    GenerateProjectOptions, PathWriter, PlatformTag, PythonInterpreter, Target,
+    audit_wheels, check_dists, check_project, develop, init_project, new_project, repair_wheel,
+    write_dist_info, BridgeModel, BuildOptions, BuildPlan, BuildReport, CargoOptions, GenerateCI,
+    GenerateProjectOptions, PathWriter, PlatformTag, Policy, PyProjectToml, PythonInterpreter,
+    Target, TestOptions,
 };
 #[cfg(feature = "upload")]
 use maturin::{upload_ui, PublishOpt};
 use std::env;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::str::FromStr;
 
 #[derive(Debug, Parser)]
@@ -40,6 +43,10 @@ enum Opt {
         /// Build a source distribution
         #[arg(long)]
         sdist: bool,
+        /// Write a json report with the built wheels, their tags, hashes, auditwheel policies
+        /// and bundled libraries to this file
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
         #[command(flatten)]
         build: BuildOptions,
     },
@@ -61,6 +68,39 @@ enum Opt {
         #[command(flatten)]
         build: BuildOptions,
     },
+    /// Check the project configuration without compiling it
+    ///
+    /// Resolves the project layout, the bindings, the python metadata and the python
+    /// interpreters like `maturin build` and reports all problems it finds.
+    #[command(name = "check")]
+    Check {
+        #[command(flatten)]
+        build: BuildOptions,
+    },
+    /// Print the resolved build configuration without compiling
+    ///
+    /// Shows the project layout, module name, bindings, target, platform tags, python
+    /// interpreters, python metadata and cargo options that `maturin build` would use.
+    #[command(name = "inspect")]
+    Inspect {
+        /// Build artifacts in release mode, with optimizations
+        #[arg(short = 'r', long)]
+        release: bool,
+        /// Strip the library for minimum file size
+        #[arg(long)]
+        strip: bool,
+        /// The output format
+        #[arg(long, value_enum, default_value_t = InspectFormat::Json)]
+        format: InspectFormat,
+        /// Write the build plan to this file instead of stdout
+        #[arg(long, value_name = "FILE")]
+        file: Option<PathBuf>,
+        /// Used by `output_without_status` to get the build plan without the status messages
+        #[arg(long, value_name = "FILE", hide = true)]
+        stdout_file: Option<PathBuf>,
+        #[command(flatten)]
+        build: BuildOptions,
+    },
     #[command(name = "list-python")]
     /// Search and list the available python installations
     ListPython {
@@ -72,7 +112,7 @@ enum Opt {
     ///
     /// Note that this command doesn't create entrypoints
     Develop {
-        /// Which kind of bindings to use. Possible values are pyo3, rust-cpython, cffi and bin
+        /// Which kind of bindings to use. Possible values are pyo3, rust-cpython, cffi, uniffi and bin
         #[arg(short = 'b', long = "bindings", alias = "binding-crate")]
         bindings: Option<String>,
         /// Pass --release to cargo
@@ -94,6 +134,12 @@ enum Opt {
         #[command(flatten)]
         cargo_options: CargoOptions,
     },
+    /// Build the crate into a virtualenv under `target/` and run the python tests in it
+    ///
+    /// Installs the test extras and runs `python -m pytest` or the `test-command` from
+    /// `[tool.maturin]`, once for each interpreter. Exits with the code of the tests.
+    #[command(name = "test")]
+    Test(TestOptions),
     /// Build only a source distribution (sdist) without compiling.
     ///
     /// Building a source distribution requires a pyproject.toml with a `[build-system]` table.
@@ -108,6 +154,67 @@ enum Opt {
         /// directory in the project's target directory
         #[arg(short, long)]
         out: Option<PathBuf>,
+        /// Vendor the cargo dependencies into the source distribution and configure cargo to
+        /// use them, so it builds without network access. Same as `sdist-vendor` in
+        /// `[tool.maturin]`
+        #[arg(long)]
+        vendor: bool,
+        /// Unpack the source distribution and build a wheel from it without network access to
+        /// check that it's complete, reporting the files of the project that are missing
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Check wheels and source distributions before uploading them
+    ///
+    /// Validates the required metadata fields, the version, the classifiers, the description
+    /// content type, the filename and wheel tags and the RECORD hashes, similar to `twine check`.
+    #[command(name = "check-dist")]
+    CheckDist {
+        /// The wheels and source distributions to check
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Check that prebuilt wheels satisfy the manylinux/musllinux policies they are tagged with
+    ///
+    /// Reports the highest policy each shared library in the wheel satisfies, why higher
+    /// policies were rejected and which external libraries still need to be bundled.
+    #[command(name = "audit")]
+    Audit {
+        /// The wheels to audit
+        #[arg(value_name = "WHEEL", required = true)]
+        wheels: Vec<PathBuf>,
+        /// A json file with custom auditwheel policies, which are checked alongside the built-in
+        /// ones. Defaults to `auditwheel-policy` in the pyproject.toml of the current directory
+        #[arg(long = "auditwheel-policy", value_name = "FILE")]
+        auditwheel_policy: Option<PathBuf>,
+    },
+    /// Bundle external shared libraries into prebuilt wheels and re-tag them
+    ///
+    /// This is the equivalent of `auditwheel repair`: it copies the libraries that aren't
+    /// allowed by the policy into a `<name>.libs` directory, points the rpaths of the shared
+    /// libraries there and renames the wheel and its WHEEL file for the new platform tag.
+    #[command(name = "repair")]
+    Repair {
+        /// The wheels to repair
+        #[arg(value_name = "WHEEL", required = true)]
+        wheels: Vec<PathBuf>,
+        /// The manylinux/musllinux platform tag to repair the wheels for, e.g. `manylinux2014`
+        /// or `musllinux_1_2`
+        #[arg(
+            id = "plat",
+            long = "plat",
+            required = true,
+            num_args = 1..,
+            action = clap::ArgAction::Append
+        )]
+        platform_tag: Vec<PlatformTag>,
+        /// The directory to store the repaired wheels in. Defaults to "wheelhouse"
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// A json file with custom auditwheel policies, which can be passed to `--plat`.
+        /// Defaults to `auditwheel-policy` in the pyproject.toml of the current directory
+        #[arg(long = "auditwheel-policy", value_name = "FILE")]
+        auditwheel_policy: Option<PathBuf>,
     },
     /// Create a new cargo project in an existing directory
     #[command(name = "init")]
@@ -125,6 +232,19 @@ enum Opt {
         #[command(flatten)]
         options: GenerateProjectOptions,
     },
+    /// Generate a CI configuration for building, testing and publishing the project
+    ///
+    /// The pipeline is tailored to the bindings, abi3 settings and `[tool.maturin]`
+    /// options of the project.
+    #[command(name = "generate-ci")]
+    #[group(skip)]
+    GenerateCI {
+        #[command(flatten)]
+        generate_ci: GenerateCI,
+        /// Used by `output_without_status` to get the configuration without the status messages
+        #[arg(long, value_name = "FILE", hide = true)]
+        stdout_file: Option<PathBuf>,
+    },
     /// Upload python packages to pypi
     ///
     /// It is mostly similar to `twine upload`, but can only upload python wheels
@@ -203,6 +323,13 @@ enum Pep517Command {
     },
 }
 
+/// The output format of `maturin inspect`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum InspectFormat {
+    Json,
+    Toml,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum)]
 #[allow(clippy::enum_variant_names)]
 enum Shell {
@@ -258,7 +385,7 @@ fn pep517(subcommand: Pep517Command) -> Result<()> {
                         .get_platform_tag(&[PlatformTag::Linux], context.universal2)?;
                     vec![format!("cp{}{}-abi3-{}", major, minor, platform)]
                 }
-                BridgeModel::Bin(None) | BridgeModel::Cffi => {
+                BridgeModel::Bin(None) | BridgeModel::Cffi | BridgeModel::UniFfi => {
                     context
                         .target
                         .get_universal_tags(&[PlatformTag::Linux], context.universal2)?
@@ -303,6 +430,48 @@ fn pep517(subcommand: Pep517Command) -> Result<()> {
     Ok(())
 }
 
+/// Runs the current subcommand again with the hidden `--stdout-file` option pointing to a
+/// temporary file and returns what was written to it. The status messages of the child process
+/// are forwarded to stderr, so that they don't end up in the machine readable output on stdout
+fn output_without_status() -> Result<String> {
+    let output_file = tempfile::NamedTempFile::new()?;
+    let mut args = env::args_os().skip(1);
+    let subcommand = args.next().context("Missing subcommand")?;
+    let mut child = Command::new(env::current_exe()?)
+        .arg(subcommand)
+        .arg("--stdout-file")
+        .arg(output_file.path())
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Failed to run maturin")?;
+    io::copy(child.stdout.as_mut().unwrap(), &mut io::stderr())?;
+    let status = child.wait()?;
+    if !status.success() {
+        // The error was already printed by the child process
+        drop(output_file);
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(fs_err::read_to_string(output_file.path())?)
+}
+
+/// Reads the custom auditwheel policies for `maturin audit` and `maturin repair` from
+/// `auditwheel_policy` or from `[tool.maturin] auditwheel-policy` in ./pyproject.toml
+fn custom_policies(auditwheel_policy: Option<PathBuf>) -> Result<Vec<Policy>> {
+    let pyproject_toml_path = Path::new("pyproject.toml");
+    let auditwheel_policy = match auditwheel_policy {
+        Some(auditwheel_policy) => Some(auditwheel_policy),
+        None if pyproject_toml_path.is_file() => PyProjectToml::new(pyproject_toml_path)?
+            .auditwheel_policy()
+            .map(Path::to_path_buf),
+        None => None,
+    };
+    match auditwheel_policy {
+        Some(auditwheel_policy) => Policy::from_file(&auditwheel_policy),
+        None => Ok(Vec::new()),
+    }
+}
+
 fn run() -> Result<()> {
     #[cfg(feature = "log")]
     tracing_subscriber::fmt::init();
@@ -328,15 +497,23 @@ fn run() -> Result<()> {
             release,
             strip,
             sdist,
+            report,
         } => {
             let build_context = build.into_build_context(release, strip, false)?;
-            if sdist {
-                build_context
+            let sdist = if sdist {
+                let (sdist_path, _) = build_context
                     .build_source_distribution()?
                     .context("Failed to build source distribution, pyproject.toml not found")?;
-            }
-            let wheels = build_context.build_wheels()?;
+                Some(sdist_path)
+            } else {
+                None
+            };
+            let (wheels, wheel_reports) = build_context.build_wheels_with_report()?;
             assert!(!wheels.is_empty());
+            if let Some(report) = report {
+                BuildReport::new(wheel_reports, sdist.as_deref())?.write(&report)?;
+                println!("📝 Wrote build report to {}", report.display());
+            }
         }
         #[cfg(feature = "upload")]
         Opt::Publish {
@@ -363,6 +540,34 @@ fn run() -> Result<()> {
 
             upload_ui(&items, &publish)?
         }
+        Opt::Check { build } => check_project(&build)?,
+        Opt::Inspect {
+            release,
+            strip,
+            format,
+            file,
+            stdout_file,
+            build,
+        } => {
+            let output = if file.is_none() && stdout_file.is_none() {
+                output_without_status()?
+            } else {
+                let build_context = build.into_build_context(release, strip, false)?;
+                let plan = BuildPlan::new(&build_context);
+                match format {
+                    InspectFormat::Json => plan.to_json()?,
+                    InspectFormat::Toml => plan.to_toml()?,
+                }
+            };
+            match (file, stdout_file) {
+                (Some(file), _) => {
+                    fs_err::write(&file, format!("{}\n", output.trim_end()))?;
+                    println!("📝 Wrote build plan to {}", file.display());
+                }
+                (None, Some(stdout_file)) => fs_err::write(stdout_file, output)?,
+                (None, None) => println!("{}", output.trim_end()),
+            }
+        }
         Opt::ListPython { target } => {
             let found = if target.is_some() {
                 let target = Target::from_target_triple(target)?;
@@ -402,7 +607,18 @@ fn run() -> Result<()> {
 
             develop(bindings, cargo_options, &venv_dir, release, strip, extras)?;
         }
-        Opt::SDist { manifest_path, out } => {
+        Opt::Test(test) => {
+            let exit_code = test.execute()?;
+            if exit_code != 0 {
+                std::process::exit(exit_code);
+            }
+        }
+        Opt::SDist {
+            manifest_path,
+            out,
+            vendor,
+            verify,
+        } => {
             let build_options = BuildOptions {
                 out,
                 cargo: CargoOptions {
@@ -411,14 +627,44 @@ fn run() -> Result<()> {
                 },
                 ..Default::default()
             };
-            let build_context = build_options.into_build_context(false, false, false)?;
-            build_context
+            let mut build_context = build_options.into_build_context(false, false, false)?;
+            build_context.sdist_vendor |= vendor;
+            let (sdist_path, _) = build_context
                 .build_source_distribution()?
                 .context("Failed to build source distribution, pyproject.toml not found")?;
+            if verify {
+                build_context.verify_source_distribution(&sdist_path)?;
+            }
+        }
+        Opt::CheckDist { files } => check_dists(&files)?,
+        Opt::Audit {
+            wheels,
+            auditwheel_policy,
+        } => audit_wheels(&wheels, &custom_policies(auditwheel_policy)?)?,
+        Opt::Repair {
+            wheels,
+            platform_tag,
+            out,
+            auditwheel_policy,
+        } => {
+            let out = out.unwrap_or_else(|| PathBuf::from("wheelhouse"));
+            let custom_policies = custom_policies(auditwheel_policy)?;
+            for wheel in wheels {
+                let wheel_path = repair_wheel(&wheel, &platform_tag, &out, &custom_policies)?;
+                println!("📦 Repaired wheel written to {}", wheel_path.display());
+            }
         }
         Opt::Pep517(subcommand) => pep517(subcommand)?,
         Opt::InitProject { path, options } => init_project(path, options)?,
         Opt::NewProject { path, options } => new_project(path, options)?,
+        Opt::GenerateCI {
+            generate_ci,
+            stdout_file,
+        } => match stdout_file {
+            Some(stdout_file) => fs_err::write(stdout_file, generate_ci.generate()?)?,
+            None if generate_ci.output.is_none() => print!("{}", output_without_status()?),
+            None => generate_ci.execute()?,
+        },
         #[cfg(feature = "upload")]
         Opt::Upload { publish, files } => {
             if files.is_empty() {
@@ -432,7 +432,9 @@ impl Metadata21 {
         // Project-URL is special
         // "A string containing a browsable URL for the project and a label for it, separated by a comma."
         // `Project-URL: Bug Tracker, http://bitbucket.org/tarek/distribute/issues/`
-        for (key, value) in self.project_url.iter() {
+        let mut project_urls: Vec<_> = self.project_url.iter().collect();
+        project_urls.sort();
+        for (key, value) in project_urls {
             fields.push(("Project-URL", format!("{}, {}", key, value)))
         }
 
@@ -481,6 +483,15 @@ impl Metadata21 {
         re.replace_all(&self.name, "_").to_string()
     }
 
+    /// Returns the name of the `.data` directory of the wheel, e.g. `foo-1.0.data`
+    pub fn get_data_dir(&self) -> PathBuf {
+        PathBuf::from(format!(
+            "{}-{}.data",
+            self.get_distribution_escaped(),
+            self.version
+        ))
+    }
+
     /// Returns the version encoded according to PEP 427, Section "Escaping
     /// and Unicode"
     pub fn get_version_escaped(&self) -> String {
@@ -1,6 +1,8 @@
 //! The wheel format is (mostly) specified in PEP 427
 use crate::project_layout::ProjectLayout;
-use crate::{BridgeModel, Metadata21, PythonInterpreter, Target};
+use crate::pyproject_toml::Format;
+use crate::type_stub::is_generated_stub;
+use crate::{BridgeModel, Metadata21, PyProjectToml, PythonInterpreter, Target};
 use anyhow::{anyhow, bail, Context, Result};
 use flate2::write::GzEncoder;
 use flate2::Compression;
@@ -10,6 +12,7 @@ use ignore::WalkBuilder;
 use normpath::PathExt as _;
 use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::ffi::OsStr;
 use std::fmt::Write as _;
 #[cfg(target_family = "unix")]
@@ -21,6 +24,7 @@ use std::os::unix::fs::OpenOptionsExt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
 use std::str;
+use std::time::SystemTime;
 use tempfile::{tempdir, TempDir};
 use tracing::debug;
 use zip::{self, ZipWriter};
@@ -202,6 +206,10 @@ pub struct WheelWriter {
     record: Vec<(String, String, usize)>,
     record_file: PathBuf,
     wheel_path: PathBuf,
+    /// Code to insert into an `__init__.py` once it is added, together with its path
+    init_code: Option<(String, String)>,
+    /// The modification time of all files when building reproducibly
+    mtime: Option<zip::DateTime>,
 }
 
 impl ModuleWriter for WheelWriter {
@@ -218,6 +226,18 @@ impl ModuleWriter for WheelWriter {
         // The zip standard mandates using unix style paths
         let target = target.as_ref().to_str().unwrap().replace('\\', "/");
 
+        let patched;
+        let bytes = match self.init_code.take() {
+            Some((init_file, code)) if init_file == target => {
+                patched = insert_init_code(bytes, &code);
+                &patched
+            }
+            init_code => {
+                self.init_code = init_code;
+                bytes
+            }
+        };
+
         // Unlike users which can use the develop subcommand, the tests have to go through
         // packing a zip which pip than has to unpack. This makes this 2-3 times faster
         let compression_method = if cfg!(feature = "faster-tests") {
@@ -225,9 +245,7 @@ impl ModuleWriter for WheelWriter {
         } else {
             zip::CompressionMethod::Deflated
         };
-        let options = zip::write::FileOptions::default()
-            .unix_permissions(permissions)
-            .compression_method(compression_method);
+        let options = self.file_options(compression_method, permissions);
         self.zip.start_file(target.clone(), options)?;
         self.zip.write_all(bytes)?;
 
@@ -262,6 +280,8 @@ impl WheelWriter {
             record: Vec::new(),
             record_file: metadata21.get_dist_info_dir().join("RECORD"),
             wheel_path,
+            init_code: None,
+            mtime: source_date_epoch().map(zip_datetime),
         };
 
         write_dist_info(&mut builder, metadata21, tags)?;
@@ -269,6 +289,20 @@ impl WheelWriter {
         Ok(builder)
     }
 
+    /// Create a new wheel file without any metadata, which is used to rewrite an existing wheel
+    /// whose `.dist-info` directory is copied over
+    pub(crate) fn new_empty(wheel_path: PathBuf, dist_info_dir: &Path) -> Result<WheelWriter> {
+        let file = File::create(&wheel_path)?;
+        Ok(WheelWriter {
+            zip: ZipWriter::new(file),
+            record: Vec::new(),
+            record_file: dist_info_dir.join("RECORD"),
+            wheel_path,
+            init_code: None,
+            mtime: source_date_epoch().map(zip_datetime),
+        })
+    }
+
     /// Add a pth file to wheel root for editable installs
     pub fn add_pth(
         &mut self,
@@ -289,14 +323,42 @@ impl WheelWriter {
         Ok(())
     }
 
+    /// The options for a file entry, which use the `SOURCE_DATE_EPOCH` modification time if set
+    fn file_options(
+        &self,
+        compression_method: zip::CompressionMethod,
+        permissions: u32,
+    ) -> zip::write::FileOptions {
+        let options = zip::write::FileOptions::default()
+            .unix_permissions(permissions)
+            .compression_method(compression_method);
+        match self.mtime {
+            Some(mtime) => options.last_modified_time(mtime),
+            None => options,
+        }
+    }
+
+    /// Inserts python code into the given `__init__.py` when it is added to the wheel,
+    /// after the docstring and `__future__` imports but before any other import
+    pub(crate) fn insert_into_init(&mut self, init_file: &Path, code: String) {
+        let init_file = init_file.to_str().unwrap().replace('\\', "/");
+        self.init_code = Some((init_file, code));
+    }
+
     /// Creates the record file and finishes the zip
     pub fn finish(mut self) -> Result<PathBuf, io::Error> {
+        if let Some((init_file, _)) = &self.init_code {
+            println!(
+                "⚠️  Warning: The wheel doesn't contain {}, the bundled libraries may not be found",
+                init_file
+            );
+        }
         let compression_method = if cfg!(feature = "faster-tests") {
             zip::CompressionMethod::Stored
         } else {
             zip::CompressionMethod::Deflated
         };
-        let options = zip::write::FileOptions::default().compression_method(compression_method);
+        let options = self.file_options(compression_method, 0o644);
         let record_filename = self.record_file.to_str().unwrap().replace('\\', "/");
         debug!("Adding {}", record_filename);
         self.zip.start_file(&record_filename, options)?;
@@ -313,11 +375,125 @@ impl WheelWriter {
     }
 }
 
+/// Reads `SOURCE_DATE_EPOCH`, which makes the modification times of all files in wheels and
+/// source distributions fixed, see https://reproducible-builds.org/docs/source-date-epoch/
+fn source_date_epoch() -> Option<u64> {
+    let epoch = env::var("SOURCE_DATE_EPOCH").ok()?;
+    match epoch.trim().parse() {
+        Ok(epoch) => Some(epoch),
+        Err(_) => {
+            println!(
+                "⚠️  Warning: Ignoring SOURCE_DATE_EPOCH={}, it must be an integer",
+                epoch
+            );
+            None
+        }
+    }
+}
+
+/// Converts a unix timestamp to a zip timestamp. Zip timestamps can't go before 1980, so earlier
+/// timestamps are clamped to 1980-01-01 like the wheel package does
+fn zip_datetime(epoch: u64) -> zip::DateTime {
+    time::OffsetDateTime::from_unix_timestamp(epoch as i64)
+        .ok()
+        .and_then(|datetime| zip::DateTime::from_time(datetime).ok())
+        .unwrap_or_default()
+}
+
+/// Inserts `code` into the content of an `__init__.py` after the leading comments, the module
+/// docstring and the `__future__` imports, which have to come before any other code
+fn insert_init_code(init: &[u8], code: &str) -> Vec<u8> {
+    let position = str::from_utf8(init)
+        .map(init_code_position)
+        .unwrap_or(init.len());
+    let mut patched = init[..position].to_vec();
+    if !patched.is_empty() && !patched.ends_with(b"\n") {
+        patched.push(b'\n');
+    }
+    patched.extend_from_slice(code.as_bytes());
+    patched.extend_from_slice(b"\n");
+    patched.extend_from_slice(&init[position..]);
+    patched
+}
+
+/// Returns the offset behind the leading comments, the module docstring and the `__future__`
+/// imports of python source code
+fn init_code_position(source: &str) -> usize {
+    let mut position = 0;
+    let mut allow_docstring = true;
+    while position < source.len() {
+        let rest = &source[position..];
+        let line_len = rest.find('\n').map_or(rest.len(), |end| end + 1);
+        let line = rest[..line_len].trim();
+        if line.is_empty() || line.starts_with('#') {
+            position += line_len;
+        } else if line.starts_with("from __future__ ") {
+            position += statement_len(rest);
+            allow_docstring = false;
+        } else if let Some(len) = string_literal_len(rest).filter(|_| allow_docstring) {
+            // Skip the rest of the line with the end of the docstring, too
+            position += len;
+            position += source[position..]
+                .find('\n')
+                .map_or(source.len() - position, |end| end + 1);
+            allow_docstring = false;
+        } else {
+            break;
+        }
+    }
+    position
+}
+
+/// Returns the length of the string literal at the start of `source`, if there is one
+fn string_literal_len(source: &str) -> Option<usize> {
+    let prefix_len = source.find(['"', '\''])?;
+    if prefix_len > 1 || !source[..prefix_len].chars().all(|c| "rRuU".contains(c)) {
+        return None;
+    }
+    let literal = &source[prefix_len..];
+    let quote = if literal.starts_with("\"\"\"") || literal.starts_with("\'\'\'") {
+        &literal[..3]
+    } else {
+        &literal[..1]
+    };
+    let mut chars = literal[quote.len()..].char_indices();
+    while let Some((index, c)) = chars.next() {
+        let offset = quote.len() + index;
+        if c == '\\' {
+            // Even in raw strings, a backslash keeps the next quote from ending the literal
+            chars.next();
+        } else if literal[offset..].starts_with(quote) {
+            return Some(prefix_len + offset + quote.len());
+        } else if c == '\n' && quote.len() == 1 {
+            return None;
+        }
+    }
+    None
+}
+
+/// Returns the length of the statement starting at the first line of `source`, including lines
+/// continued with parentheses or backslashes
+fn statement_len(source: &str) -> usize {
+    let mut depth = 0;
+    let mut len = 0;
+    for line in source.split_inclusive('\n') {
+        len += line.len();
+        let code = line.split('#').next().unwrap_or_default();
+        depth += code.matches('(').count() as isize - code.matches(')').count() as isize;
+        if depth <= 0 && !code.trim_end().ends_with('\\') {
+            break;
+        }
+    }
+    len
+}
+
 /// Creates a .tar.gz archive containing the source distribution
 pub struct SDistWriter {
     tar: tar::Builder<GzEncoder<File>>,
     path: PathBuf,
     files: HashSet<PathBuf>,
+    /// The modification time of all files when building reproducibly
+    mtime: Option<u64>,
 }
 
 impl ModuleWriter for SDistWriter {
@@ -337,10 +513,7 @@ impl ModuleWriter for SDistWriter {
             return Ok(());
         }
 
-        let mut header = tar::Header::new_gnu();
-        header.set_size(bytes.len() as u64);
-        header.set_mode(permissions);
-        header.set_cksum();
+        let mut header = self.header(bytes.len() as u64, permissions, 0);
         self.tar
             .append_data(&mut header, target, bytes)
             .context(format!(
@@ -367,13 +540,25 @@ impl ModuleWriter for SDistWriter {
         }
         debug!("Adding {} from {}", target.display(), source.display());
 
-        self.tar
-            .append_path_with_name(source, target)
-            .context(format!(
+        let add_failed_context = || {
+            format!(
                 "Failed to add file from {} to sdist as {}",
                 source.display(),
                 target.display(),
-            ))?;
+            )
+        };
+        let metadata = fs::metadata(source).with_context(add_failed_context)?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let mut header = self.header(metadata.len(), normalized_mode(&metadata), mtime);
+        let file = File::open(source).with_context(add_failed_context)?;
+        self.tar
+            .append_data(&mut header, target, file)
+            .with_context(add_failed_context)?;
         self.files.insert(target.to_path_buf());
         Ok(())
     }
@@ -396,9 +581,24 @@ impl SDistWriter {
             tar,
             path,
             files: HashSet::new(),
+            mtime: source_date_epoch(),
         })
     }
 
+    /// Creates a file header that doesn't leak the owner of the files, with `SOURCE_DATE_EPOCH`
+    /// taking precedence over the given modification time
+    fn header(&self, size: u64, mode: u32, mtime: u64) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(mode);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(self.mtime.unwrap_or(mtime));
+        header.set_cksum();
+        header
+    }
+
     /// Finished the .tar.gz archive
     pub fn finish(mut self) -> Result<PathBuf, io::Error> {
         self.tar.finish()?;
@@ -406,6 +606,24 @@ impl SDistWriter {
     }
 }
 
+/// Normalizes the permissions of a file to either 0o755 for executables or 0o644 otherwise
+#[cfg(target_family = "unix")]
+fn normalized_mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    if metadata.permissions().mode() & 0o111 != 0 {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+/// Normalizes the permissions of a file to either 0o755 for executables or 0o644 otherwise
+#[cfg(not(target_family = "unix"))]
+fn normalized_mode(_metadata: &std::fs::Metadata) -> u32 {
+    0o644
+}
+
 fn wheel_file(tags: &[String]) -> Result<String> {
     let mut wheel_file = format!(
         "Wheel-Version: 1.0
@@ -428,8 +646,10 @@ fn entry_points_txt(
     entry_type: &str,
     entrypoints: &HashMap<String, String, impl std::hash::BuildHasher>,
 ) -> String {
+    let mut entrypoints: Vec<_> = entrypoints.iter().collect();
+    entrypoints.sort();
     entrypoints
-        .iter()
+        .into_iter()
         .fold(format!("[{}]\n", entry_type), |text, (k, v)| {
             text + k + "=" + v + "\n"
         })
@@ -624,19 +844,214 @@ fn handle_cffi_call_result(
     }
 }
 
-/// Copies the shared library into the module, which is the only extra file needed with bindings
+/// The python wrapper that uniffi-bindgen generated for a crate
+#[derive(Debug, Clone)]
+struct UniFfiBindings {
+    /// The name of the python module of the wrapper
+    name: String,
+    /// The source of the wrapper
+    code: String,
+    /// The file name under which the wrapper loads the native library
+    cdylib: String,
+}
+
+/// Finds the single `.udl` interface definition in the `src` directory of the crate
+pub(crate) fn find_uniffi_udl(crate_dir: &Path) -> Result<PathBuf> {
+    let src_dir = crate_dir.join("src");
+    let pattern = src_dir.join("*.udl");
+    let pattern = pattern
+        .to_str()
+        .context("The path to the crate is not valid utf-8")?;
+    let mut udls = glob::glob(pattern)?.collect::<Result<Vec<_>, _>>()?;
+    match udls.len() {
+        0 => bail!("No UniFFI .udl file found in {}", src_dir.display()),
+        1 => Ok(udls.remove(0)),
+        _ => bail!(
+            "Found multiple UniFFI .udl files in {}, only one is supported",
+            src_dir.display()
+        ),
+    }
+}
+
+/// Returns the name of the native library the uniffi wrapper `module` loads, which is
+/// `uniffi_<module>` unless `[bindings.python] cdylib_name` is set in uniffi.toml
+fn uniffi_cdylib_name(config_file: &Path, module: &str) -> Result<String> {
+    if config_file.is_file() {
+        let config: toml_edit::Document = fs::read_to_string(config_file)?
+            .parse()
+            .with_context(|| format!("Failed to parse {}", config_file.display()))?;
+        if let Some(cdylib_name) = config
+            .get("bindings")
+            .and_then(|x| x.get("python"))
+            .and_then(|x| x.get("cdylib_name"))
+            .and_then(|x| x.as_str())
+        {
+            return Ok(cdylib_name.to_string());
+        }
+    }
+    Ok(format!("uniffi_{}", module))
+}
+
+/// Runs `uniffi-bindgen generate` for python on the `.udl` file of the crate and returns the
+/// generated wrapper module
+///
+/// The wrapper loads the native library through ctypes from its own directory, so the library
+/// needs to be written next to it under the name given by [uniffi_cdylib_name].
+fn generate_uniffi_bindings(crate_dir: &Path, target: &Target) -> Result<UniFfiBindings> {
+    let udl = find_uniffi_udl(crate_dir)?;
+    let tempdir = tempdir()?;
+    let mut command = Command::new("uniffi-bindgen");
+    command
+        .args([
+            "generate",
+            "--no-format",
+            "--language",
+            "python",
+            "--out-dir",
+        ])
+        .arg(tempdir.path());
+    let config_file = crate_dir.join("uniffi.toml");
+    if config_file.is_file() {
+        command.arg("--config").arg(&config_file);
+    }
+    command.arg(&udl);
+    debug!("Running {:?}", command);
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => bail!(
+            "uniffi-bindgen was not found, please install it with \
+            `cargo install uniffi_bindgen --version <the version of uniffi in Cargo.lock>`"
+        ),
+        Err(err) => return Err(err).context("Failed to run uniffi-bindgen"),
+    };
+    if !output.status.success() {
+        bail!(
+            "Failed to generate the UniFFI bindings for {}: {}\n--- Stdout:\n{}\n--- Stderr:\n{}",
+            udl.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+    // Don't swallow warnings
+    io::stderr().write_all(&output.stderr)?;
+
+    // The wrapper is named after the namespace in the udl file, which usually but not
+    // necessarily matches the name of the file
+    let mut py_files = Vec::new();
+    for entry in fs::read_dir(tempdir.path())? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new("py")) {
+            py_files.push(path);
+        }
+    }
+    let py_file = match py_files.as_slice() {
+        [py_file] => py_file,
+        _ => bail!(
+            "Expected uniffi-bindgen to generate a single python module, but it generated {}",
+            py_files.len()
+        ),
+    };
+    let name = py_file
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .context("uniffi-bindgen generated a python module with a non-utf8 name")?
+        .to_string();
+    let code = fs::read_to_string(py_file)?;
+    let cdylib_name = uniffi_cdylib_name(&config_file, &name)?;
+    let cdylib = if target.is_macos() {
+        format!("lib{}.dylib", cdylib_name)
+    } else if target.is_windows() {
+        format!("{}.dll", cdylib_name)
+    } else {
+        format!("lib{}.so", cdylib_name)
+    };
+    tempdir.close()?;
+    Ok(UniFfiBindings { name, code, cdylib })
+}
+
+/// Creates the uniffi module with the wrapper generated by uniffi-bindgen and the shared library
 #[allow(clippy::too_many_arguments)]
-pub fn write_bindings_module(
+pub fn write_uniffi_module(
     writer: &mut impl ModuleWriter,
     project_layout: &ProjectLayout,
+    pyproject_toml: Option<&PyProjectToml>,
+    crate_dir: &Path,
     module_name: &str,
     artifact: &Path,
-    python_interpreter: Option<&PythonInterpreter>,
     target: &Target,
     editable: bool,
 ) -> Result<()> {
-    let ext_name = &project_layout.extension_name;
-    let so_filename = match python_interpreter {
+    let UniFfiBindings {
+        name: binding_name,
+        code,
+        cdylib,
+    } = generate_uniffi_bindings(crate_dir, target)?;
+    let py_init = format!("from .{} import *  # NOQA\n", binding_name);
+
+    let module;
+
+    if let Some(python_module) = &project_layout.python_module {
+        if !editable {
+            write_python_part(writer, project_layout, pyproject_toml)
+                .context("Failed to add the python module to the package")?;
+        }
+
+        if editable {
+            let base_path = project_layout
+                .rust_module
+                .join(&project_layout.extension_name);
+            fs::create_dir_all(&base_path)?;
+            let target = base_path.join(&cdylib);
+            fs::copy(artifact, &target).context(format!(
+                "Failed to copy {} to {}",
+                artifact.display(),
+                target.display()
+            ))?;
+            File::create(base_path.join("__init__.py"))?.write_all(py_init.as_bytes())?;
+            File::create(base_path.join(format!("{}.py", binding_name)))?
+                .write_all(code.as_bytes())?;
+        }
+
+        let relative = project_layout
+            .rust_module
+            .strip_prefix(python_module.parent().unwrap())
+            .unwrap();
+        module = relative.join(&project_layout.extension_name);
+        if !editable {
+            writer.add_directory(&module)?;
+        }
+    } else {
+        module = PathBuf::from(module_name);
+        writer.add_directory(&module)?;
+        write_python_part(writer, project_layout, pyproject_toml)
+            .context("Failed to add the included files to the package")?;
+        let type_stub = project_layout
+            .rust_module
+            .join(format!("{}.pyi", module_name));
+        if type_stub.exists() {
+            println!("📖 Found type stub file at {}.pyi", module_name);
+            writer.add_file(module.join("__init__.pyi"), type_stub)?;
+            writer.add_bytes(module.join("py.typed"), b"")?;
+        }
+    };
+
+    if !editable || project_layout.python_module.is_none() {
+        writer.add_bytes(module.join("__init__.py"), py_init.as_bytes())?;
+        writer.add_bytes(module.join(format!("{}.py", binding_name)), code.as_bytes())?;
+        writer.add_file_with_permissions(module.join(cdylib), artifact, 0o755)?;
+    }
+
+    Ok(())
+}
+
+/// The file name of a native module for the interpreter, or the abi3 file name without one
+fn extension_filename(
+    ext_name: &str,
+    python_interpreter: Option<&PythonInterpreter>,
+    target: &Target,
+) -> String {
+    match python_interpreter {
         Some(python_interpreter) => python_interpreter.get_library_name(ext_name),
         // abi3
         None => {
@@ -647,10 +1062,92 @@ pub fn write_bindings_module(
                 format!("{base}.pyd", base = ext_name)
             }
         }
-    };
+    }
+}
+
+/// Adds the shared library of an extension module from another workspace member at its dotted
+/// path in the python package, e.g. `my_project._simd` goes to `my_project/_simd.<ext suffix>`
+pub fn write_extension_module(
+    writer: &mut impl ModuleWriter,
+    project_layout: &ProjectLayout,
+    module: &str,
+    artifact: &Path,
+    python_interpreter: Option<&PythonInterpreter>,
+    target: &Target,
+    editable: bool,
+) -> Result<()> {
+    let python_module = project_layout
+        .python_module
+        .as_ref()
+        .context("Extension modules from the workspace require a mixed python/rust project")?;
+    let parts: Vec<&str> = module.split('.').collect();
+    let (ext_name, package) = parts.split_last().unwrap();
+    let relative: PathBuf = package.iter().collect();
+    let so_filename = extension_filename(ext_name, python_interpreter, target);
+
+    if editable {
+        let target = python_module
+            .parent()
+            .unwrap()
+            .join(&relative)
+            .join(&so_filename);
+        // Remove existing so file to avoid triggering SIGSEV in running process
+        debug!("Removing {}", target.display());
+        let _ = fs::remove_file(&target);
+
+        debug!("Copying {} to {}", artifact.display(), target.display());
+        fs::copy(artifact, &target).context(format!(
+            "Failed to copy {} to {}",
+            artifact.display(),
+            target.display()
+        ))?;
+    } else {
+        writer.add_file_with_permissions(relative.join(so_filename), artifact, 0o755)?;
+    }
+    Ok(())
+}
+
+/// Copies the shared library into the module, which is the only extra file needed with bindings
+///
+/// `type_stub` is a generated type stub for the native module, which is placed next to it. In a
+/// mixed project, only editable installs write it into the python source, and a handwritten
+/// stub there takes precedence over it.
+#[allow(clippy::too_many_arguments)]
+pub fn write_bindings_module(
+    writer: &mut impl ModuleWriter,
+    project_layout: &ProjectLayout,
+    pyproject_toml: Option<&PyProjectToml>,
+    module_name: &str,
+    artifact: &Path,
+    python_interpreter: Option<&PythonInterpreter>,
+    target: &Target,
+    editable: bool,
+    type_stub: Option<&str>,
+) -> Result<()> {
+    let ext_name = &project_layout.extension_name;
+    let so_filename = extension_filename(ext_name, python_interpreter, target);
 
     if let Some(python_module) = &project_layout.python_module {
+        let stub_path = project_layout.rust_module.join(format!("{}.pyi", ext_name));
+        let type_stub = type_stub.filter(|_| {
+            if stub_path.exists() && !is_generated_stub(&stub_path) {
+                println!(
+                    "📖 Using the handwritten type stub at {} instead of generating one",
+                    stub_path.display()
+                );
+                false
+            } else {
+                true
+            }
+        });
+        let py_typed = python_module.join("py.typed");
         if editable {
+            if let Some(type_stub) = type_stub {
+                fs::write(&stub_path, type_stub)?;
+                if !py_typed.exists() {
+                    fs::write(&py_typed, "")?;
+                }
+            }
             let target = project_layout.rust_module.join(&so_filename);
             // Remove existing so file to avoid triggering SIGSEV in running process
             // See https://github.com/PyO3/maturin/issues/758
@@ -664,18 +1161,32 @@ pub fn write_bindings_module(
                 target.display()
             ))?;
         } else {
-            write_python_part(writer, python_module)
+            let python_dir = python_module.parent().unwrap();
+            // A stub generated by an earlier editable install is replaced by the new one
+            let skip = match type_stub {
+                Some(_) => HashSet::from([stub_path.strip_prefix(python_dir)?.to_path_buf()]),
+                None => HashSet::new(),
+            };
+            write_python_part_except(writer, project_layout, pyproject_toml, &skip)
                 .context("Failed to add the python module to the package")?;
 
-            let relative = project_layout
-                .rust_module
-                .strip_prefix(python_module.parent().unwrap())
-                .unwrap();
+            let relative = project_layout.rust_module.strip_prefix(python_dir).unwrap();
             writer.add_file_with_permissions(relative.join(&so_filename), &artifact, 0o755)?;
+            if let Some(type_stub) = type_stub {
+                writer.add_bytes(
+                    relative.join(format!("{}.pyi", ext_name)),
+                    type_stub.as_bytes(),
+                )?;
+                if !py_typed.exists() {
+                    writer.add_bytes(py_typed.strip_prefix(python_dir)?, b"")?;
+                }
+            }
         }
     } else {
         let module = PathBuf::from(module_name);
         writer.add_directory(&module)?;
+        write_python_part(writer, project_layout, pyproject_toml)
+            .context("Failed to add the included files to the package")?;
         // Reexport the shared library as if it were the top level module
         writer.add_bytes(
             &module.join("__init__.py"),
@@ -689,12 +1200,15 @@ if hasattr({module_name}, "__all__"):
             )
             .as_bytes(),
         )?;
-        let type_stub = project_layout
+        let type_stub_file = project_layout
             .rust_module
             .join(format!("{}.pyi", module_name));
-        if type_stub.exists() {
+        if let Some(type_stub) = type_stub {
+            writer.add_bytes(module.join("__init__.pyi"), type_stub.as_bytes())?;
+            writer.add_bytes(module.join("py.typed"), b"")?;
+        } else if type_stub_file.exists() {
             println!("📖 Found type stub file at {}.pyi", module_name);
-            writer.add_file(&module.join("__init__.pyi"), type_stub)?;
+            writer.add_file(module.join("__init__.pyi"), type_stub_file)?;
             writer.add_bytes(&module.join("py.typed"), b"")?;
         }
         writer.add_file_with_permissions(&module.join(so_filename), &artifact, 0o755)?;
@@ -708,6 +1222,7 @@ if hasattr({module_name}, "__all__"):
 pub fn write_cffi_module(
     writer: &mut impl ModuleWriter,
     project_layout: &ProjectLayout,
+    pyproject_toml: Option<&PyProjectToml>,
     crate_dir: &Path,
     target_dir: &Path,
     module_name: &str,
@@ -721,7 +1236,7 @@ pub fn write_cffi_module(
 
     if let Some(python_module) = &project_layout.python_module {
         if !editable {
-            write_python_part(writer, python_module)
+            write_python_part(writer, project_layout, pyproject_toml)
                 .context("Failed to add the python module to the package")?;
         }
 
@@ -749,6 +1264,8 @@ pub fn write_cffi_module(
     } else {
         module = PathBuf::from(module_name);
         writer.add_directory(&module)?;
+        write_python_part(writer, project_layout, pyproject_toml)
+            .context("Failed to add the included files to the package")?;
         let type_stub = project_layout
             .rust_module
             .join(format!("{}.pyi", module_name));
@@ -775,12 +1292,7 @@ pub fn write_bin(
     metadata: &Metadata21,
     bin_name: &str,
 ) -> Result<()> {
-    let data_dir = PathBuf::from(format!(
-        "{}-{}.data",
-        &metadata.get_distribution_escaped(),
-        &metadata.version
-    ))
-    .join("scripts");
+    let data_dir = metadata.get_data_dir().join("scripts");
 
     writer.add_directory(&data_dir)?;
 
@@ -844,35 +1356,120 @@ if __name__ == '__main__':
     Ok(())
 }
 
-/// Adds the python part of a mixed project to the writer,
+/// Adds the python part of a mixed project and the files matching the `include` patterns for
+/// wheels to the writer
+///
+/// The `include` and `exclude` patterns from pyproject.toml are relative to the project root, the
+/// same as for the sdist. Files in the python source directory keep their path relative to it in
+/// the wheel, other included files are added at their path relative to the project root.
 pub fn write_python_part(
     writer: &mut impl ModuleWriter,
-    python_module: impl AsRef<Path>,
+    project_layout: &ProjectLayout,
+    pyproject_toml: Option<&PyProjectToml>,
 ) -> Result<()> {
-    for absolute in WalkBuilder::new(&python_module).hidden(false).build() {
-        let absolute = absolute?.into_path();
-        let relative = absolute
-            .strip_prefix(python_module.as_ref().parent().unwrap())
-            .unwrap();
-        if absolute.is_dir() {
-            writer.add_directory(relative)?;
-        } else {
-            // Ignore native libraries from develop, if any
-            if let Some(extension) = relative.extension() {
-                if extension.to_string_lossy() == "so" {
-                    debug!("Ignoring native library {}", relative.display());
-                    continue;
+    write_python_part_except(writer, project_layout, pyproject_toml, &HashSet::new())
+}
+
+/// Like [write_python_part], but leaves out the files at the paths in the wheel in `skip`
+fn write_python_part_except(
+    writer: &mut impl ModuleWriter,
+    project_layout: &ProjectLayout,
+    pyproject_toml: Option<&PyProjectToml>,
+    skip: &HashSet<PathBuf>,
+) -> Result<()> {
+    let project_root = &project_layout.project_root;
+    let python_dir = project_layout
+        .python_module
+        .as_ref()
+        .map(|python_module| python_module.parent().unwrap());
+    // Maps a file to its path in the wheel
+    let wheel_path = |absolute: &Path| -> Result<PathBuf> {
+        let relative = match python_dir {
+            Some(python_dir) if absolute.starts_with(python_dir) => {
+                absolute.strip_prefix(python_dir)?
+            }
+            _ => absolute.strip_prefix(project_root)?,
+        };
+        Ok(relative.to_path_buf())
+    };
+    // Paths outside of the project root, e.g. of a python source in a parent directory, can
+    // only be matched by their path in the wheel
+    let is_excluded_from_wheel = |absolute: &Path, relative: &Path, excludes: &[glob::Pattern]| {
+        let relative_to_root = absolute.strip_prefix(project_root).unwrap_or(relative);
+        is_excluded(relative_to_root, excludes) || skip.contains(relative)
+    };
+    let excludes = match pyproject_toml {
+        Some(pyproject_toml) => pyproject_toml.exclude(Format::Wheel)?,
+        None => Vec::new(),
+    };
+    let mut added = HashSet::new();
+    if let Some(python_module) = &project_layout.python_module {
+        for absolute in WalkBuilder::new(python_module)
+            .hidden(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+        {
+            let absolute = absolute?.into_path();
+            let relative = wheel_path(&absolute)?;
+            if is_excluded_from_wheel(&absolute, &relative, &excludes) {
+                debug!("Excluding {}", relative.display());
+                continue;
+            }
+            if absolute.is_dir() {
+                writer.add_directory(&relative)?;
+            } else {
+                // Ignore native libraries from develop, if any
+                if let Some(extension) = relative.extension() {
+                    if extension.to_string_lossy() == "so" {
+                        debug!("Ignoring native library {}", relative.display());
+                        continue;
+                    }
                 }
+                writer
+                    .add_file(&relative, &absolute)
+                    .context(format!("File to add file from {}", absolute.display()))?;
+                added.insert(relative);
+            }
+        }
+    }
+
+    let includes = pyproject_toml
+        .map(|pyproject_toml| pyproject_toml.include(Format::Wheel))
+        .unwrap_or_default();
+    for pattern in includes {
+        println!("📦 Including files matching \"{}\"", pattern);
+        for source in glob::glob(&project_root.join(pattern).to_string_lossy())
+            .with_context(|| format!("Invalid include pattern \"{}\"", pattern))?
+            .filter_map(Result::ok)
+        {
+            let relative = wheel_path(&source)?;
+            if is_excluded_from_wheel(&source, &relative, &excludes) {
+                debug!("Excluding {}", relative.display());
+                continue;
+            }
+            if source.is_dir() {
+                writer.add_directory(&relative)?;
+            } else if added.insert(relative.clone()) {
+                writer.add_file(&relative, &source)?;
             }
-            writer
-                .add_file(relative, &absolute)
-                .context(format!("File to add file from {}", absolute.display()))?;
         }
     }
 
     Ok(())
 }
 
+/// Whether the relative path or one of its parent directories matches one of the exclude patterns
+pub(crate) fn is_excluded(relative: &Path, excludes: &[glob::Pattern]) -> bool {
+    relative
+        .ancestors()
+        .take_while(|ancestor| *ancestor != Path::new(""))
+        .any(|ancestor| {
+            excludes
+                .iter()
+                .any(|pattern| pattern.matches_path(ancestor))
+        })
+}
+
 /// Creates the .dist-info directory and fills it with all metadata files except RECORD
 pub fn write_dist_info(
     writer: &mut impl ModuleWriter,
@@ -897,7 +1494,9 @@ pub fn write_dist_info(
     if !metadata21.gui_scripts.is_empty() {
         entry_points.push_str(&entry_points_txt("gui_scripts", &metadata21.gui_scripts));
     }
-    for (entry_type, scripts) in &metadata21.entry_points {
+    let mut entry_types: Vec<_> = metadata21.entry_points.iter().collect();
+    entry_types.sort_by_key(|(entry_type, _)| *entry_type);
+    for (entry_type, scripts) in entry_types {
         entry_points.push_str(&entry_points_txt(entry_type, scripts));
     }
     if !entry_points.is_empty() {
@@ -929,8 +1528,11 @@ pub fn write_dist_info(
 pub fn add_data(writer: &mut impl ModuleWriter, data: Option<&Path>) -> Result<()> {
     let possible_data_dir_names = ["data", "scripts", "headers", "purelib", "platlib"];
     if let Some(data) = data {
-        for subdir in fs::read_dir(data).context("Failed to read data dir")? {
-            let subdir = subdir?;
+        let mut subdirs = fs::read_dir(data)
+            .context("Failed to read data dir")?
+            .collect::<Result<Vec<_>, _>>()?;
+        subdirs.sort_by_key(|subdir| subdir.file_name());
+        for subdir in subdirs {
             let dir_name = subdir
                 .file_name()
                 .to_str()
@@ -947,6 +1549,7 @@ pub fn add_data(writer: &mut impl ModuleWriter, data: Option<&Path>) -> Result<(
             (|| {
                 for file in WalkBuilder::new(subdir.path())
                     .standard_filters(false)
+                    .sort_by_file_name(|a, b| a.cmp(b))
                     .build()
                 {
                     let file = file?;
@@ -972,3 +1575,392 @@ pub fn add_data(writer: &mut impl ModuleWriter, data: Option<&Path>) -> Result<(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        find_uniffi_udl, insert_init_code, is_excluded, uniffi_cdylib_name, write_bindings_module,
+        write_extension_module, write_python_part, zip_datetime, ModuleWriter, SDistWriter,
+        WheelWriter,
+    };
+    use crate::project_layout::ProjectLayout;
+    use crate::type_stub::GENERATED_STUB_HEADER;
+    use crate::{Metadata21, PyProjectToml, Target};
+    use flate2::read::GzDecoder;
+    use fs_err as fs;
+    use std::collections::HashSet;
+    use std::io::Read;
+    use std::path::Path;
+
+    #[test]
+    fn test_zip_datetime() {
+        let datetime = zip_datetime(1_577_880_000);
+        assert_eq!(
+            (datetime.year(), datetime.month(), datetime.day()),
+            (2020, 1, 1)
+        );
+        assert_eq!(
+            (datetime.hour(), datetime.minute(), datetime.second()),
+            (12, 0, 0)
+        );
+        // Zip can't represent timestamps before 1980
+        let datetime = zip_datetime(0);
+        assert_eq!(
+            (datetime.year(), datetime.month(), datetime.day()),
+            (1980, 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_uniffi_cdylib_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join("uniffi.toml");
+        assert_eq!(
+            uniffi_cdylib_name(&config_file, "math").unwrap(),
+            "uniffi_math"
+        );
+        fs::write(
+            &config_file,
+            "[bindings.python]\ncdylib_name = \"arithmetic\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            uniffi_cdylib_name(&config_file, "math").unwrap(),
+            "arithmetic"
+        );
+    }
+
+    #[test]
+    fn test_find_uniffi_udl() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let src_dir = temp_dir.path().join("src");
+        fs::create_dir(&src_dir).unwrap();
+        assert!(find_uniffi_udl(temp_dir.path()).is_err());
+        fs::write(src_dir.join("math.udl"), "namespace math {};").unwrap();
+        assert_eq!(
+            find_uniffi_udl(temp_dir.path()).unwrap(),
+            src_dir.join("math.udl")
+        );
+        fs::write(src_dir.join("other.udl"), "namespace other {};").unwrap();
+        assert!(find_uniffi_udl(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let excludes = [
+            glob::Pattern::new("tests/data").unwrap(),
+            glob::Pattern::new("**/*.bin").unwrap(),
+        ];
+        assert!(is_excluded(Path::new("tests/data"), &excludes));
+        assert!(is_excluded(Path::new("tests/data/large/x.txt"), &excludes));
+        assert!(is_excluded(Path::new("my_project/model.bin"), &excludes));
+        assert!(!is_excluded(Path::new("tests/test_data.py"), &excludes));
+        assert!(!is_excluded(Path::new("my_project/__init__.py"), &excludes));
+    }
+
+    #[test]
+    fn test_reproducible_wheel() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut hashes = HashSet::new();
+        for name in ["a.whl", "b.whl"].iter() {
+            let wheel = temp_dir.path().join(name);
+            let mut writer =
+                WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+            writer.mtime = Some(zip_datetime(1_577_880_000));
+            writer.add_bytes("foo/__init__.py", b"").unwrap();
+            writer
+                .add_bytes_with_permissions("foo/bar", b"#!/bin/sh", 0o755)
+                .unwrap();
+            writer.finish().unwrap();
+            hashes.insert(fs::read(&wheel).unwrap());
+        }
+        assert_eq!(hashes.len(), 1);
+    }
+
+    #[test]
+    fn test_sdist_normalized_headers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path().join("lib.rs");
+        fs::write(&source, "fn main() {}").unwrap();
+        let metadata21 = Metadata21 {
+            name: "foo".to_string(),
+            version: "0.1.0".to_string(),
+            ..Default::default()
+        };
+        let mut writer = SDistWriter::new(temp_dir.path(), &metadata21).unwrap();
+        writer.mtime = Some(1_577_880_000);
+        writer.add_file("foo-0.1.0/src/lib.rs", &source).unwrap();
+        writer.add_bytes("foo-0.1.0/PKG-INFO", b"").unwrap();
+        let sdist = writer.finish().unwrap();
+
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(sdist).unwrap()));
+        for entry in archive.entries().unwrap() {
+            let header = entry.unwrap().header().clone();
+            assert_eq!(header.mtime().unwrap(), 1_577_880_000);
+            assert_eq!(header.mode().unwrap(), 0o644);
+            assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+            assert_eq!(header.username().unwrap(), Some(""));
+        }
+    }
+
+    #[test]
+    fn test_insert_init_code() {
+        let insert =
+            |init: &str| String::from_utf8(insert_init_code(init.as_bytes(), "HOOK")).unwrap();
+        assert_eq!(insert(""), "HOOK\n");
+        assert_eq!(insert("import os\n"), "HOOK\nimport os\n");
+        assert_eq!(
+            insert("# -*- coding: utf-8 -*-\nfrom .foo import *"),
+            "# -*- coding: utf-8 -*-\nHOOK\nfrom .foo import *"
+        );
+        // Imports in the docstring are not code
+        assert_eq!(
+            insert("\"\"\"Usage:\n\nimport foo\n\"\"\"\nfrom .foo import *\n"),
+            "\"\"\"Usage:\n\nimport foo\n\"\"\"\nHOOK\nfrom .foo import *\n"
+        );
+        assert_eq!(
+            insert("r'''Escaped \\''' import foo'''\n"),
+            "r'''Escaped \\''' import foo'''\nHOOK\n"
+        );
+        assert_eq!(
+            insert("'Docs'\nfrom __future__ import (\n    annotations,\n)\n\nx = 1\n"),
+            "'Docs'\nfrom __future__ import (\n    annotations,\n)\n\nHOOK\nx = 1\n"
+        );
+        // Only the first string literal is the docstring
+        assert_eq!(insert("'Docs'\n'More'\n"), "'Docs'\nHOOK\n'More'\n");
+    }
+
+    /// Builds a wheel of a mixed project with `foo/__init__.py` and the native module `foo._foo`
+    /// and returns the files in the wheel with their contents
+    fn mixed_wheel(dir: &Path, type_stub: &str, editable: bool) -> Vec<(String, String)> {
+        let python_module = dir.join("python").join("foo");
+        let artifact = dir.join("libfoo.so");
+        fs::write(&artifact, b"").unwrap();
+        let project_layout = ProjectLayout {
+            python_module: Some(python_module.clone()),
+            rust_module: python_module,
+            extension_name: "_foo".to_string(),
+            data: None,
+            project_root: dir.to_path_buf(),
+        };
+        let wheel = dir.join("foo-0.1.0-py3-none-any.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        write_bindings_module(
+            &mut writer,
+            &project_layout,
+            None,
+            "foo",
+            &artifact,
+            None,
+            &Target::from_target_triple(None).unwrap(),
+            editable,
+            Some(type_stub),
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        read_wheel(&wheel)
+    }
+
+    /// Returns the files in the wheel with their contents
+    fn read_wheel(wheel: &Path) -> Vec<(String, String)> {
+        let mut archive = zip::ZipArchive::new(fs::File::open(wheel).unwrap()).unwrap();
+        let mut files = Vec::new();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let mut content = String::new();
+            file.read_to_string(&mut content).unwrap();
+            files.push((file.name().to_string(), content));
+        }
+        files
+    }
+
+    #[test]
+    fn test_mixed_type_stub() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python_module = temp_dir.path().join("python").join("foo");
+        fs::create_dir_all(&python_module).unwrap();
+        fs::write(python_module.join("__init__.py"), "").unwrap();
+        let stub = format!("{}\ndef bar(): ...\n", GENERATED_STUB_HEADER);
+
+        // Wheels get the stub without changing the python source
+        let files = mixed_wheel(temp_dir.path(), &stub, false);
+        assert!(files.contains(&("foo/_foo.pyi".to_string(), stub.clone())));
+        assert!(files.contains(&("foo/py.typed".to_string(), String::new())));
+        assert!(!python_module.join("_foo.pyi").exists());
+        assert!(!python_module.join("py.typed").exists());
+
+        // Editable installs write it into the python source
+        mixed_wheel(temp_dir.path(), &stub, true);
+        assert_eq!(
+            fs::read_to_string(python_module.join("_foo.pyi")).unwrap(),
+            stub
+        );
+        assert!(python_module.join("py.typed").exists());
+
+        // A stub generated earlier is replaced, not added twice
+        let new_stub = format!("{}\ndef baz(): ...\n", GENERATED_STUB_HEADER);
+        let files = mixed_wheel(temp_dir.path(), &new_stub, false);
+        let stubs: Vec<_> = files
+            .iter()
+            .filter(|(name, _)| name == "foo/_foo.pyi")
+            .collect();
+        assert_eq!(stubs, [&("foo/_foo.pyi".to_string(), new_stub.clone())]);
+        assert_eq!(
+            files
+                .iter()
+                .filter(|(name, _)| name == "foo/py.typed")
+                .count(),
+            1
+        );
+
+        // A handwritten stub is neither overwritten nor replaced in the wheel
+        let handwritten = "def bar(x: int) -> int: ...\n";
+        fs::write(python_module.join("_foo.pyi"), handwritten).unwrap();
+        mixed_wheel(temp_dir.path(), &new_stub, true);
+        let files = mixed_wheel(temp_dir.path(), &new_stub, false);
+        assert_eq!(
+            fs::read_to_string(python_module.join("_foo.pyi")).unwrap(),
+            handwritten
+        );
+        assert!(files.contains(&("foo/_foo.pyi".to_string(), handwritten.to_string())));
+    }
+
+    #[test]
+    fn test_include_exclude_relative_to_project_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        let python_module = dir.join("python").join("foo");
+        fs::create_dir_all(&python_module).unwrap();
+        fs::write(python_module.join("__init__.py"), "").unwrap();
+        fs::write(python_module.join("big.bin"), "").unwrap();
+        fs::write(dir.join("LICENSE-DATA"), "data license").unwrap();
+        fs::write(
+            dir.join("pyproject.toml"),
+            r#"[build-system]
+requires = ["maturin"]
+build-backend = "maturin"
+
+[tool.maturin]
+python-source = "python"
+include = [{ path = "LICENSE-DATA", format = "wheel" }]
+exclude = [{ path = "python/foo/*.bin", format = "wheel" }]
+"#,
+        )
+        .unwrap();
+        let pyproject_toml = PyProjectToml::new(dir.join("pyproject.toml")).unwrap();
+
+        // The same patterns match in mixed projects ...
+        let project_layout = ProjectLayout {
+            python_module: Some(python_module.clone()),
+            rust_module: python_module,
+            extension_name: "foo".to_string(),
+            data: None,
+            project_root: dir.to_path_buf(),
+        };
+        let wheel = dir.join("mixed.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        write_python_part(&mut writer, &project_layout, Some(&pyproject_toml)).unwrap();
+        writer.finish().unwrap();
+        let names: HashSet<String> = read_wheel(&wheel)
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(names.contains("foo/__init__.py"));
+        assert!(names.contains("LICENSE-DATA"));
+        assert!(!names.contains("foo/big.bin"));
+
+        // ... and in pure Rust projects
+        let artifact = dir.join("libfoo.so");
+        fs::write(&artifact, b"").unwrap();
+        let project_layout = ProjectLayout {
+            python_module: None,
+            rust_module: dir.to_path_buf(),
+            extension_name: "foo".to_string(),
+            data: None,
+            project_root: dir.to_path_buf(),
+        };
+        let wheel = dir.join("pure.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        write_bindings_module(
+            &mut writer,
+            &project_layout,
+            Some(&pyproject_toml),
+            "foo",
+            &artifact,
+            None,
+            &Target::from_target_triple(None).unwrap(),
+            false,
+            None,
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        let files = read_wheel(&wheel);
+        assert!(files.contains(&("LICENSE-DATA".to_string(), "data license".to_string())));
+    }
+
+    #[test]
+    fn test_write_extension_module() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let python_module = temp_dir.path().join("python").join("foo");
+        fs::create_dir_all(python_module.join("codec")).unwrap();
+        let artifact = temp_dir.path().join("lib_codec.so");
+        fs::write(&artifact, b"codec").unwrap();
+        let project_layout = ProjectLayout {
+            python_module: Some(python_module.clone()),
+            rust_module: python_module.clone(),
+            extension_name: "foo".to_string(),
+            data: None,
+            project_root: temp_dir.path().to_path_buf(),
+        };
+        let target = Target::from_target_triple(None).unwrap();
+
+        // Wheels get the module at its dotted path relative to the python source
+        let wheel = temp_dir.path().join("foo-0.1.0-py3-none-any.whl");
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        write_extension_module(
+            &mut writer,
+            &project_layout,
+            "foo.codec._codec",
+            &artifact,
+            None,
+            &target,
+            false,
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        let files = read_wheel(&wheel);
+        let so_filename = if target.is_unix() {
+            "_codec.abi3.so"
+        } else {
+            "_codec.pyd"
+        };
+        assert!(files.contains(&(format!("foo/codec/{}", so_filename), "codec".to_string())));
+        assert!(!python_module.join("codec").join(so_filename).exists());
+
+        // Editable installs copy it into the python source instead
+        let mut writer =
+            WheelWriter::new_empty(wheel.clone(), Path::new("foo-0.1.0.dist-info")).unwrap();
+        write_extension_module(
+            &mut writer,
+            &project_layout,
+            "foo.codec._codec",
+            &artifact,
+            None,
+            &target,
+            true,
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        assert!(!read_wheel(&wheel)
+            .iter()
+            .any(|(name, _)| name.ends_with(so_filename)));
+        assert_eq!(
+            fs::read(python_module.join("codec").join(so_filename)).unwrap(),
+            b"codec"
+        );
+    }
+}
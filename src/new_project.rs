@@ -1,3 +1,6 @@
+use crate::ci::{CIProject, GenerateCI};
+use crate::python_interpreter::MINIMUM_PYTHON_MINOR;
+use crate::BridgeModel;
 use anyhow::{bail, Context, Result};
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Select};
@@ -39,7 +42,6 @@ impl<'a> ProjectGenerator<'a> {
         env.add_template("lib.rs", include_str!("templates/lib.rs.j2"))?;
         env.add_template("main.rs", include_str!("templates/main.rs.j2"))?;
         env.add_template("__init__.py", include_str!("templates/__init__.py.j2"))?;
-        env.add_template("CI.yml", include_str!("templates/CI.yml.j2"))?;
         Ok(Self {
             env,
             project_name,
@@ -58,7 +60,10 @@ impl<'a> ProjectGenerator<'a> {
         // CI configuration
         let gh_action_path = project_path.join(".github").join("workflows");
         fs::create_dir_all(&gh_action_path)?;
-        self.write_project_file(&gh_action_path, "CI.yml")?;
+        let ci_path = gh_action_path.join("CI.yml");
+        if self.overwrite || !ci_path.exists() {
+            fs::write(ci_path, self.render_ci()?)?;
+        }
 
         let rust_project = match self.layout {
             ProjectLayout::Mixed { src } => {
@@ -107,6 +112,24 @@ impl<'a> ProjectGenerator<'a> {
         Ok(out)
     }
 
+    /// Renders the GitHub Actions workflow the same way as `maturin generate-ci github`
+    fn render_ci(&self) -> Result<String> {
+        let bridge = match self.bindings.as_str() {
+            "cffi" => BridgeModel::Cffi,
+            "bin" => BridgeModel::Bin(None),
+            bindings => BridgeModel::Bindings(bindings.to_string(), MINIMUM_PYTHON_MINOR),
+        };
+        let project = CIProject {
+            name: self.project_name.clone(),
+            bridge,
+            compatibility: None,
+            sdist: true,
+            tests: false,
+            min_python_minor: None,
+        };
+        GenerateCI::default().render(&project)
+    }
+
     fn write_project_file(&self, directory: &Path, file: &str) -> Result<()> {
         let path = directory.join(file);
         if self.overwrite || !path.exists() {
@@ -3,6 +3,7 @@ use crate::{CargoToml, Metadata21, PyProjectToml};
 use anyhow::{bail, format_err, Context, Result};
 use cargo_metadata::{Metadata, MetadataCommand};
 use normpath::PathExt as _;
+use serde::Serialize;
 use std::env;
 use std::io;
 use std::path::{Path, PathBuf};
@@ -10,7 +11,7 @@ use std::path::{Path, PathBuf};
 const PYPROJECT_TOML: &str = "pyproject.toml";
 
 /// Whether this project is pure rust or rust mixed with python and whether it has wheel data
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
 pub struct ProjectLayout {
     /// Contains the canonicalized (i.e. absolute) path to the python part of the project
     /// If none, we have a rust crate compiled into a shared library with only some glue python for cffi
@@ -22,6 +23,9 @@ pub struct ProjectLayout {
     pub extension_name: String,
     /// The location of the wheel data, if any
     pub data: Option<PathBuf>,
+    /// The directory containing pyproject.toml, or Cargo.toml if there is no pyproject.toml.
+    /// The `include` and `exclude` patterns are relative to it
+    pub project_root: PathBuf,
 }
 
 /// Project resolver
@@ -79,14 +83,11 @@ impl ProjectResolver {
         cargo_options.manifest_path = Some(manifest_file.clone());
 
         let cargo_toml = CargoToml::from_path(&manifest_file)?;
-        cargo_toml.warn_removed_python_metadata();
 
         let manifest_dir = manifest_file.parent().unwrap();
         let pyproject_toml: Option<PyProjectToml> = if pyproject_file.is_file() {
             let pyproject =
                 PyProjectToml::new(&pyproject_file).context("pyproject.toml is invalid")?;
-            pyproject.warn_missing_maturin_version();
-            pyproject.warn_missing_build_backend();
             Some(pyproject)
         } else {
             None
@@ -135,7 +136,7 @@ impl ProjectResolver {
             manifest_dir
         };
         let py_root = match pyproject.and_then(|x| x.python_source()) {
-            Some(py_src) => py_src.to_path_buf(),
+            Some(py_src) => project_root.join(py_src),
             None => match extra_metadata.python_source.as_ref() {
                 Some(py_src) => manifest_dir.join(py_src),
                 None => match pyproject.and_then(|x| x.project_name()) {
@@ -274,10 +275,14 @@ impl ProjectResolver {
         cargo_options: &CargoOptions,
     ) -> Result<Metadata> {
         let cargo_metadata_extra_args = extract_cargo_metadata_args(cargo_options)?;
-        let result = MetadataCommand::new()
+        let mut command = MetadataCommand::new();
+        command
             .manifest_path(manifest_path)
-            .other_options(cargo_metadata_extra_args)
-            .exec();
+            .other_options(cargo_metadata_extra_args);
+        if let Some(current_dir) = &cargo_options.current_dir {
+            command.current_dir(current_dir);
+        }
+        let result = command.exec();
 
         let cargo_metadata = match result {
             Ok(cargo_metadata) => cargo_metadata,
@@ -347,6 +352,7 @@ impl ProjectLayout {
                 rust_module,
                 extension_name,
                 data,
+                project_root: project_root.to_path_buf(),
             })
         } else {
             Ok(ProjectLayout {
@@ -354,6 +360,7 @@ impl ProjectLayout {
                 rust_module: project_root.to_path_buf(),
                 extension_name,
                 data,
+                project_root: project_root.to_path_buf(),
             })
         }
     }
@@ -1,5 +1,5 @@
 use crate::PlatformTag;
-use anyhow::{format_err, Result};
+use anyhow::{format_err, Context, Result};
 use fs_err as fs;
 use pyproject_toml::PyProjectToml as ProjectToml;
 use serde::{Deserialize, Serialize};
@@ -12,19 +12,110 @@ pub struct Tool {
     maturin: Option<ToolMaturin>,
 }
 
+/// A native module built from another member of the cargo workspace, see
+/// `[[tool.maturin.extension-modules]]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExtensionModuleConfig {
+    /// The name of the workspace member
+    pub package: String,
+    /// The dotted path of the module in the python package, e.g. `my_project._simd`
+    pub module: String,
+}
+
+/// The archive format an `include` or `exclude` pattern applies to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// The source distribution
+    Sdist,
+    /// The wheels
+    Wheel,
+}
+
+/// One or several formats
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Formats {
+    /// A single format
+    Single(Format),
+    /// Several formats
+    Multiple(Vec<Format>),
+}
+
+impl Formats {
+    /// Whether the format is one of these
+    pub fn targets(&self, format: Format) -> bool {
+        match self {
+            Formats::Single(single) => *single == format,
+            Formats::Multiple(multiple) => multiple.contains(&format),
+        }
+    }
+}
+
+/// A glob pattern of `[tool.maturin] include` or `exclude`, which applies to both formats unless
+/// restricted with `format`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum GlobPattern {
+    /// A pattern for both formats
+    Path(String),
+    /// A pattern for the given formats
+    WithFormat {
+        /// The glob pattern
+        path: String,
+        /// The formats the pattern applies to
+        format: Formats,
+    },
+}
+
+impl GlobPattern {
+    /// Returns the pattern if it applies to the format
+    pub fn targets(&self, format: Format) -> Option<&str> {
+        match self {
+            GlobPattern::Path(path) => Some(path),
+            GlobPattern::WithFormat {
+                path,
+                format: formats,
+            } if formats.targets(format) => Some(path),
+            GlobPattern::WithFormat { .. } => None,
+        }
+    }
+}
+
 /// The `[tool.maturin]` section of a pyproject.toml
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct ToolMaturin {
     // maturin specific options
     sdist_include: Option<Vec<String>>,
+    /// Files to add to the sdist and/or the wheels
+    include: Option<Vec<GlobPattern>>,
+    /// Files to leave out of the sdist and/or the wheels
+    exclude: Option<Vec<GlobPattern>>,
+    /// Vendor the cargo dependencies into the source distribution
+    #[serde(default)]
+    sdist_vendor: bool,
     bindings: Option<String>,
     #[serde(alias = "manylinux")]
     compatibility: Option<PlatformTag>,
     #[serde(default)]
     skip_auditwheel: bool,
+    /// Path to a json file with custom auditwheel policies, relative to pyproject.toml
+    auditwheel_policy: Option<PathBuf>,
     #[serde(default)]
     strip: bool,
+    /// Generate a `.pyi` type stub by importing the compiled module
+    #[serde(default)]
+    generate_stubs: bool,
+    /// Native modules from other workspace members that go into the same wheel
+    #[serde(default)]
+    extension_modules: Vec<ExtensionModuleConfig>,
+    /// Put the `[[bin]]` targets into the wheel next to the native module
+    #[serde(default)]
+    include_bins: bool,
+    /// The command `maturin test` runs in the virtualenv, defaults to `python -m pytest`
+    test_command: Option<Vec<String>>,
     /// The directory with python module, contains `<module_name>/__init__.py`
     python_source: Option<PathBuf>,
     /// Path to the wheel directory, defaults to `<module_name>.data`
@@ -102,6 +193,39 @@ impl PyProjectToml {
         self.maturin()?.sdist_include.as_ref()
     }
 
+    /// Returns the patterns of `[tool.maturin.include]` in pyproject.toml for the format, for the
+    /// sdist together with `[tool.maturin.sdist-include]`
+    pub fn include(&self, format: Format) -> Vec<&str> {
+        let maturin = match self.maturin() {
+            Some(maturin) => maturin,
+            None => return Vec::new(),
+        };
+        let mut patterns: Vec<&str> = maturin
+            .include
+            .iter()
+            .flatten()
+            .filter_map(|pattern| pattern.targets(format))
+            .collect();
+        if format == Format::Sdist {
+            patterns.extend(maturin.sdist_include.iter().flatten().map(String::as_str));
+        }
+        patterns
+    }
+
+    /// Returns the patterns of `[tool.maturin.exclude]` in pyproject.toml for the format
+    pub fn exclude(&self, format: Format) -> Result<Vec<glob::Pattern>> {
+        self.maturin()
+            .and_then(|maturin| maturin.exclude.as_ref())
+            .into_iter()
+            .flatten()
+            .filter_map(|pattern| pattern.targets(format))
+            .map(|pattern| {
+                glob::Pattern::new(pattern)
+                    .with_context(|| format!("Invalid exclude pattern \"{}\"", pattern))
+            })
+            .collect()
+    }
+
     /// Returns the value of `[tool.maturin.bindings]` in pyproject.toml
     pub fn bindings(&self) -> Option<&str> {
         self.maturin()?.bindings.as_deref()
@@ -109,7 +233,7 @@ impl PyProjectToml {
 
     /// Returns the value of `[tool.maturin.compatibility]` in pyproject.toml
     pub fn compatibility(&self) -> Option<PlatformTag> {
-        self.maturin()?.compatibility
+        self.maturin()?.compatibility.clone()
     }
 
     /// Returns the value of `[tool.maturin.skip-auditwheel]` in pyproject.toml
@@ -119,6 +243,19 @@ impl PyProjectToml {
             .unwrap_or_default()
     }
 
+    /// Returns the value of `[tool.maturin.auditwheel-policy]` in pyproject.toml
+    pub fn auditwheel_policy(&self) -> Option<&Path> {
+        self.maturin()
+            .and_then(|maturin| maturin.auditwheel_policy.as_deref())
+    }
+
+    /// Returns the value of `[tool.maturin.sdist-vendor]` in pyproject.toml
+    pub fn sdist_vendor(&self) -> bool {
+        self.maturin()
+            .map(|maturin| maturin.sdist_vendor)
+            .unwrap_or_default()
+    }
+
     /// Returns the value of `[tool.maturin.strip]` in pyproject.toml
     pub fn strip(&self) -> bool {
         self.maturin()
@@ -126,6 +263,32 @@ impl PyProjectToml {
             .unwrap_or_default()
     }
 
+    /// Returns the value of `[tool.maturin.generate-stubs]` in pyproject.toml
+    pub fn generate_stubs(&self) -> bool {
+        self.maturin()
+            .map(|maturin| maturin.generate_stubs)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `[[tool.maturin.extension-modules]]` in pyproject.toml
+    pub fn extension_modules(&self) -> &[ExtensionModuleConfig] {
+        self.maturin()
+            .map(|maturin| maturin.extension_modules.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `[tool.maturin.include-bins]` in pyproject.toml
+    pub fn include_bins(&self) -> bool {
+        self.maturin()
+            .map(|maturin| maturin.include_bins)
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of `[tool.maturin.test-command]` in pyproject.toml
+    pub fn test_command(&self) -> Option<&[String]> {
+        self.maturin()?.test_command.as_deref()
+    }
+
     /// Returns the value of `[tool.maturin.python-source]` in pyproject.toml
     pub fn python_source(&self) -> Option<&Path> {
         self.maturin()
@@ -148,29 +311,36 @@ impl PyProjectToml {
     ///
     /// Returns true if the pyproject.toml has the constraint
     pub fn warn_missing_maturin_version(&self) -> bool {
+        if let Some(message) = self.missing_maturin_version() {
+            eprintln!("⚠️  Warning: {}", message);
+            return false;
+        }
+        true
+    }
+
+    /// Returns the warning for `warn_missing_maturin_version`, if any
+    pub(crate) fn missing_maturin_version(&self) -> Option<String> {
         let maturin = env!("CARGO_PKG_NAME");
-        if let Some(requires_maturin) = self
+        let requires_maturin = self
             .build_system
             .requires
             .iter()
-            .find(|x| x.starts_with(maturin))
-        {
-            // Note: Update this once 1.0 is out
-            assert_eq!(env!("CARGO_PKG_VERSION_MAJOR"), "0");
-            let current_minor: usize = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap();
-            if requires_maturin == maturin {
-                eprintln!(
-                    "⚠️  Warning: Please use {maturin} in pyproject.toml with a version constraint, \
-                    e.g. `requires = [\"{maturin}>=0.{current},<0.{next}\"]`. \
-                    This will become an error.",
-                    maturin = maturin,
-                    current = current_minor,
-                    next = current_minor + 1,
-                );
-                return false;
-            }
+            .find(|x| x.starts_with(maturin))?;
+        // Note: Update this once 1.0 is out
+        assert_eq!(env!("CARGO_PKG_VERSION_MAJOR"), "0");
+        let current_minor: usize = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap();
+        if requires_maturin == maturin {
+            Some(format!(
+                "Please use {maturin} in pyproject.toml with a version constraint, \
+                e.g. `requires = [\"{maturin}>=0.{current},<0.{next}\"]`. \
+                This will become an error.",
+                maturin = maturin,
+                current = current_minor,
+                next = current_minor + 1,
+            ))
+        } else {
+            None
         }
-        true
     }
 
     /// Having a pyproject.toml without `build-backend` set to `maturin`
@@ -178,21 +348,31 @@ impl PyProjectToml {
     ///
     /// Returns true if the pyproject.toml has `build-backend` set to `maturin`
     pub fn warn_missing_build_backend(&self) -> bool {
+        if let Some(message) = self.missing_build_backend() {
+            eprintln!("⚠️  Warning: {}", message);
+            return false;
+        }
+        true
+    }
+
+    /// Returns the warning for `warn_missing_build_backend`, if any
+    pub(crate) fn missing_build_backend(&self) -> Option<String> {
         let maturin = env!("CARGO_PKG_NAME");
         if self.build_system.build_backend.as_deref() != Some(maturin) {
-            eprintln!(
-                "⚠️  Warning: `build-backend` in pyproject.toml is not set to `{maturin}`, \
-                    packaging tools such as pip will not use maturin to build this project.",
+            Some(format!(
+                "`build-backend` in pyproject.toml is not set to `{maturin}`, \
+                packaging tools such as pip will not use maturin to build this project.",
                 maturin = maturin
-            );
-            return false;
+            ))
+        } else {
+            None
         }
-        true
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{ExtensionModuleConfig, Format};
     use crate::PyProjectToml;
     use fs_err as fs;
     use pretty_assertions::assert_eq;
@@ -218,6 +398,11 @@ mod tests {
             no-default-features = true
             locked = true
             rustc-args = ["-Z", "unstable-options"]
+            include-bins = true
+
+            [[tool.maturin.extension-modules]]
+            package = "my-project-simd"
+            module = "my_project._simd"
             "#,
         )
         .unwrap();
@@ -238,6 +423,53 @@ mod tests {
             maturin.rustc_args,
             Some(vec!["-Z".to_string(), "unstable-options".to_string()])
         );
+        assert!(pyproject.include_bins());
+        assert_eq!(
+            pyproject.extension_modules(),
+            [ExtensionModuleConfig {
+                package: "my-project-simd".to_string(),
+                module: "my_project._simd".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_include_exclude_per_format() {
+        let tmp_dir = TempDir::new().unwrap();
+        let pyproject_file = tmp_dir.path().join("pyproject.toml");
+
+        fs::write(
+            &pyproject_file,
+            r#"[build-system]
+            requires = ["maturin"]
+            build-backend = "maturin"
+
+            [tool.maturin]
+            sdist-include = ["legacy/*"]
+            include = ["both/*", { path = "sdist/*", format = "sdist" }]
+            exclude = [
+                { path = "tests/data/**", format = ["sdist", "wheel"] },
+                { path = "**/*.bin", format = "wheel" },
+            ]
+            "#,
+        )
+        .unwrap();
+        let pyproject = PyProjectToml::new(pyproject_file).unwrap();
+        assert_eq!(
+            pyproject.include(Format::Sdist),
+            ["both/*", "sdist/*", "legacy/*"]
+        );
+        assert_eq!(pyproject.include(Format::Wheel), ["both/*"]);
+        let excludes = |format| -> Vec<String> {
+            pyproject
+                .exclude(format)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+        assert_eq!(excludes(Format::Sdist), ["tests/data/**"]);
+        assert_eq!(excludes(Format::Wheel), ["tests/data/**", "**/*.bin"]);
     }
 
     #[test]
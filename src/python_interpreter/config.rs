@@ -4,7 +4,7 @@ use crate::Target;
 use anyhow::{format_err, Context, Result};
 use fs_err as fs;
 use once_cell::sync::Lazy;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::io::{BufRead, BufReader};
@@ -47,7 +47,7 @@ static WELLKNOWN_SYSCONFIG: Lazy<HashMap<Os, HashMap<Arch, Vec<InterpreterConfig
     });
 
 /// Some of the sysconfigdata of Python interpreter we care about
-#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct InterpreterConfig {
     /// Python's major version
     pub major: usize,
@@ -3,7 +3,7 @@ use crate::auditwheel::PlatformTag;
 use crate::{BridgeModel, Target};
 use anyhow::{bail, format_err, Context, Result};
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fmt;
 use std::io::{self, Write};
@@ -275,7 +275,7 @@ fn windows_python_info(executable: &Path) -> Result<Option<InterpreterConfig>> {
     }
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum InterpreterKind {
     CPython,
@@ -334,7 +334,7 @@ struct InterpreterMetadataMessage {
 }
 
 /// The location and version of an interpreter
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub struct PythonInterpreter {
     /// Python's sysconfig
     /// Python's major version
@@ -353,6 +353,7 @@ pub struct PythonInterpreter {
     /// and it's `executable` is empty
     pub runnable: bool,
     /// Comes from `sys.platform.name`
+    #[serde(rename = "implementation_name")]
     pub implmentation_name: String,
     /// Comes from sysconfig var `SOABI`
     pub soabi: Option<String>,
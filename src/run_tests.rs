@@ -0,0 +1,242 @@
+use crate::build_options::CargoOptions;
+use crate::develop::develop;
+use crate::project_layout::ProjectResolver;
+use crate::{BridgeModel, PythonInterpreter, Target};
+use anyhow::{bail, Context, Result};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The command that runs the tests unless `[tool.maturin] test-command` is set
+const DEFAULT_TEST_COMMAND: &[&str] = &["python", "-m", "pytest"];
+
+/// Build the crate into a cached virtualenv and run the python tests
+///
+/// The trailing arguments of the cargo options, e.g. `maturin test -- -k foo`, are passed on to
+/// the test command instead of rustc
+#[derive(Debug, Default, clap::Parser)]
+#[command(mut_arg("args", |arg| arg.help("Arguments passed on to the test command")))]
+pub struct TestOptions {
+    /// The python interpreters to create the virtualenvs with, runs the tests once for each.
+    /// Defaults to `python3`
+    #[arg(short = 'i', long, num_args = 0.., action = clap::ArgAction::Append)]
+    pub interpreter: Vec<PathBuf>,
+    /// Which kind of bindings to use. Possible values are pyo3, rust-cpython, cffi, uniffi and bin
+    #[arg(short = 'b', long = "bindings")]
+    pub bindings: Option<String>,
+    /// Pass --release to cargo
+    #[arg(short = 'r', long)]
+    pub release: bool,
+    /// Strip the library for minimum file size
+    #[arg(long)]
+    pub strip: bool,
+    /// The extras with the test dependencies, defaults to `test` if the project has it
+    ///
+    /// Use as `--extras=extra1,extra2`
+    #[arg(
+        short = 'E',
+        long,
+        value_delimiter = ',',
+        action = clap::ArgAction::Append
+    )]
+    pub extras: Vec<String>,
+    /// Cargo build options
+    #[command(flatten)]
+    pub cargo_options: CargoOptions,
+}
+
+impl TestOptions {
+    /// Installs the crate into a virtualenv under `target/maturin/venvs` for each interpreter and
+    /// runs the test command in it
+    ///
+    /// Returns the exit code of the test command, the last non-zero one if the tests failed with
+    /// some of the interpreters
+    pub fn execute(&self) -> Result<i32> {
+        let ProjectResolver {
+            pyproject_toml_path,
+            pyproject_toml,
+            metadata21,
+            cargo_metadata,
+            ..
+        } = ProjectResolver::resolve(
+            self.cargo_options.manifest_path.clone(),
+            self.cargo_options.clone(),
+        )?;
+        let test_args = &self.cargo_options.args;
+        let cargo_options = CargoOptions {
+            args: Vec::new(),
+            ..self.cargo_options.clone()
+        };
+        let target = Target::from_target_triple(self.cargo_options.target.clone())?;
+        let target_dir = self
+            .cargo_options
+            .target_dir
+            .clone()
+            .unwrap_or_else(|| cargo_metadata.target_directory.clone().into_std_path_buf());
+        let project_root = pyproject_toml_path.parent().unwrap();
+
+        let extras = if self.extras.is_empty() {
+            metadata21
+                .provides_extra
+                .iter()
+                .filter(|extra| extra.as_str() == "test")
+                .cloned()
+                .collect()
+        } else {
+            self.extras.clone()
+        };
+        let test_command: Vec<String> = match pyproject_toml.as_ref().and_then(|x| x.test_command())
+        {
+            Some(test_command) if !test_command.is_empty() => test_command.to_vec(),
+            Some(_) => bail!("test-command in pyproject.toml must not be empty"),
+            None => DEFAULT_TEST_COMMAND
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        };
+
+        let interpreters = if self.interpreter.is_empty() {
+            vec![target.get_python()]
+        } else {
+            self.interpreter.clone()
+        };
+        let mut exit_code = 0;
+        for interpreter in &interpreters {
+            let python =
+                PythonInterpreter::check_executable(interpreter, &target, &BridgeModel::Cffi)?
+                    .with_context(|| {
+                        format!("{} is not a python interpreter", interpreter.display())
+                    })?;
+            let venv_dir = target_dir.join("maturin").join("venvs").join(format!(
+                "{}-{}.{}{}",
+                python.interpreter_kind.to_string().to_lowercase(),
+                python.major,
+                python.minor,
+                python.abiflags
+            ));
+            create_venv(&python.executable, &venv_dir)?;
+            println!("🧪 Testing with {} in {}", python, venv_dir.display());
+
+            develop(
+                self.bindings.clone(),
+                cargo_options.clone(),
+                &venv_dir,
+                self.release,
+                self.strip,
+                extras.clone(),
+            )?;
+            if test_command[..] == *DEFAULT_TEST_COMMAND {
+                install_pytest(&target.get_venv_python(&venv_dir))?;
+            }
+
+            let code = run_in_venv(&target, &venv_dir, project_root, &test_command, test_args)?;
+            if code != 0 {
+                eprintln!("💥 Tests failed with {}", python);
+                exit_code = code;
+            }
+        }
+        Ok(exit_code)
+    }
+}
+
+/// Creates a virtualenv with the interpreter unless it already exists
+fn create_venv(python: &Path, venv_dir: &Path) -> Result<()> {
+    if venv_dir.join("pyvenv.cfg").is_file() {
+        return Ok(());
+    }
+    println!("🐍 Creating a virtualenv at {}", venv_dir.display());
+    let status = Command::new(python)
+        .args(["-m", "venv"])
+        .arg(venv_dir)
+        .status()
+        .with_context(|| format!("Failed to run {}", python.display()))?;
+    if !status.success() {
+        bail!(
+            "Creating a virtualenv with {} failed with {}",
+            python.display(),
+            status
+        );
+    }
+    Ok(())
+}
+
+/// Installs pytest into the virtualenv if the test dependencies didn't bring it
+fn install_pytest(venv_python: &Path) -> Result<()> {
+    let has_pytest = Command::new(venv_python)
+        .args(["-c", "import pytest"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or_default();
+    if has_pytest {
+        return Ok(());
+    }
+    let status = Command::new(venv_python)
+        .args([
+            "-m",
+            "pip",
+            "install",
+            "--disable-pip-version-check",
+            "pytest",
+        ])
+        .status()
+        .context("Failed to run pip install")?;
+    if !status.success() {
+        bail!(r#"pip install pytest finished with "{}""#, status)
+    }
+    Ok(())
+}
+
+/// Runs the command with the virtualenv activated and returns its exit code
+fn run_in_venv(
+    target: &Target,
+    venv_dir: &Path,
+    cwd: &Path,
+    command: &[String],
+    args: &[String],
+) -> Result<i32> {
+    let bin_dir = target.get_venv_bin_dir(venv_dir);
+    let mut path = vec![bin_dir];
+    if let Some(existing) = env::var_os("PATH") {
+        path.extend(env::split_paths(&existing));
+    }
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .args(args)
+        .current_dir(cwd)
+        .env("VIRTUAL_ENV", venv_dir)
+        .env("PATH", env::join_paths(path)?)
+        .env_remove("CONDA_PREFIX")
+        .status()
+        .with_context(|| format!("Failed to run `{}`", command.join(" ")))?;
+    // Killed by a signal
+    Ok(status.code().unwrap_or(1))
+}
+
+#[cfg(test)]
+mod test {
+    use super::TestOptions;
+    use clap::Parser;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_trailing_args_go_to_the_tests() {
+        let options = TestOptions::try_parse_from([
+            "test",
+            "-i",
+            "python3.9",
+            "-i",
+            "python3.11",
+            "--release",
+            "--",
+            "-k",
+            "foo",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.interpreter,
+            [PathBuf::from("python3.9"), PathBuf::from("python3.11")]
+        );
+        assert!(options.release);
+        assert_eq!(options.cargo_options.args, ["-k", "foo"]);
+    }
+}
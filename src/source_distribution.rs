@@ -1,7 +1,11 @@
-use crate::module_writer::{add_data, ModuleWriter};
-use crate::{BuildContext, PyProjectToml, SDistWriter};
+use crate::module_writer::{add_data, is_excluded, ModuleWriter};
+use crate::pyproject_toml::Format;
+use crate::{
+    BuildContext, BuildOptions, CargoOptions, ExtensionModule, PyProjectToml, SDistWriter,
+};
 use anyhow::{bail, Context, Result};
 use cargo_metadata::{Metadata, MetadataCommand};
+use flate2::read::GzDecoder;
 use fs_err as fs;
 use normpath::PathExt as _;
 use std::collections::HashMap;
@@ -11,6 +15,7 @@ use std::str;
 use tracing::debug;
 
 const LOCAL_DEPENDENCIES_FOLDER: &str = "local_dependencies";
+const VENDOR_FOLDER: &str = "vendor";
 /// Inheritable workspace fields, see
 /// https://github.com/rust-lang/cargo/blob/13ae438cf079da58272edc71f4d4968043dbd27b/src/cargo/util/toml/mod.rs#L1140-L1158
 const WORKSPACE_INHERITABLE_FIELDS: &[&str] = &[
@@ -44,6 +49,8 @@ fn rewrite_cargo_toml(
     manifest_path: impl AsRef<Path>,
     workspace_manifest: &toml_edit::Document,
     known_path_deps: &HashMap<String, PathBuf>,
+    extension_crates: &[String],
+    package_workspace: Option<&str>,
     local_deps_folder: String,
     root_crate: bool,
 ) -> Result<String> {
@@ -196,25 +203,60 @@ fn rewrite_cargo_toml(
                     }
                     rewritten = true;
                 } else {
+                    let manifest_dir = manifest_path.parent().unwrap();
                     let mut new_members = toml_edit::Array::new();
                     for member in members.iter() {
                         if let toml_edit::Value::String(ref s) = member {
+                            // Members are found by their location, since the directory name
+                            // doesn't need to match the package name
+                            let member_dir = manifest_dir.join(s.value()).normalize().ok();
+                            let by_path = known_path_deps.iter().find(|(_, dep_manifest)| {
+                                member_dir.is_some()
+                                    && dep_manifest.parent().and_then(|x| x.normalize().ok())
+                                        == member_dir
+                            });
                             let path = Path::new(s.value());
-                            if let Some(name) = path.file_name().and_then(|x| x.to_str()) {
-                                if known_path_deps.contains_key(name) {
-                                    new_members
-                                        .push(format!("{}/{}", LOCAL_DEPENDENCIES_FOLDER, name));
-                                }
+                            let name = match by_path {
+                                Some((name, _)) => Some(name.as_str()),
+                                None => path
+                                    .file_name()
+                                    .and_then(|x| x.to_str())
+                                    .filter(|name| known_path_deps.contains_key(*name)),
+                            };
+                            if let Some(name) = name {
+                                new_members.push(format!("{}/{}", LOCAL_DEPENDENCIES_FOLDER, name));
                             }
                         }
                     }
-                    if !new_members.is_empty() {
+                    if !new_members.is_empty() || !extension_crates.is_empty() {
                         workspace["members"] = toml_edit::value(new_members);
                         rewritten = true;
                     }
                 }
             }
         }
+        // The extension modules are resolved as workspace members, so they need to stay in the
+        // workspace of the root crate
+        if !extension_crates.is_empty() {
+            let workspace = data
+                .as_table_mut()
+                .entry("workspace")
+                .or_insert_with(toml_edit::table)
+                .as_table_mut()
+                .context("`workspace` in Cargo.toml is not a table")?;
+            let members = workspace
+                .entry("members")
+                .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+                .as_array_mut()
+                .context("`workspace.members` in Cargo.toml is not an array")?;
+            for name in extension_crates {
+                let member = format!("{}/{}", local_deps_folder, name);
+                if !members.iter().any(|x| x.as_str() == Some(&member)) {
+                    members.push(member);
+                }
+            }
+            rewritten = true;
+        }
     } else {
         // Update package.workspace
         // https://rust-lang.github.io/rfcs/1525-cargo-workspace.html#implicit-relations
@@ -228,6 +270,12 @@ fn rewrite_cargo_toml(
                     rewritten = true;
                 }
             }
+            // Extension modules are members of the root crate's workspace, but they aren't
+            // below it in the source distribution, so cargo needs to be pointed to it
+            if let Some(package_workspace) = package_workspace {
+                package["workspace"] = toml_edit::value(package_workspace);
+                rewritten = true;
+            }
         }
     }
     if rewritten {
@@ -241,6 +289,7 @@ fn rewrite_cargo_toml(
 /// and rewriting path entries in Cargo.toml
 ///
 /// Runs `cargo package --list --allow-dirty` to obtain a list of files to package.
+#[allow(clippy::too_many_arguments)]
 fn add_crate_to_source_distribution(
     writer: &mut SDistWriter,
     pyproject_toml_path: impl AsRef<Path>,
@@ -248,7 +297,10 @@ fn add_crate_to_source_distribution(
     workspace_manifest: &toml_edit::Document,
     prefix: impl AsRef<Path>,
     known_path_deps: &HashMap<String, PathBuf>,
+    extension_crates: &[String],
+    package_workspace: Option<&str>,
     root_crate: bool,
+    excludes: &[glob::Pattern],
 ) -> Result<()> {
     let manifest_path = manifest_path.as_ref();
     let pyproject_toml_path = pyproject_toml_path.as_ref();
@@ -311,6 +363,23 @@ fn add_crate_to_source_distribution(
                 source.exists()
             }
         })
+        // The exclude patterns are relative to the project root, so files of path dependencies
+        // outside of it can't be excluded
+        .filter(|(_, source)| {
+            match abs_manifest_dir
+                .join(source.strip_prefix(manifest_dir).unwrap())
+                .strip_prefix(pyproject_dir)
+            {
+                Ok(relative_to_project_root) => {
+                    let excluded = is_excluded(relative_to_project_root, excludes);
+                    if excluded {
+                        debug!("Excluding {}", source.display());
+                    }
+                    !excluded
+                }
+                Err(_) => true,
+            }
+        })
         .collect();
 
     if root_crate
@@ -347,6 +416,8 @@ fn add_crate_to_source_distribution(
         &manifest_path,
         workspace_manifest,
         known_path_deps,
+        extension_crates,
+        package_workspace,
         local_deps_folder,
         root_crate,
     )?;
@@ -370,13 +441,28 @@ fn add_crate_to_source_distribution(
 }
 
 /// Finds all path dependencies of the crate
-fn find_path_deps(cargo_metadata: &Metadata) -> Result<HashMap<String, PathBuf>> {
+///
+/// The workspace members of the extension modules are packed like path dependencies
+fn find_path_deps(
+    cargo_metadata: &Metadata,
+    extension_modules: &[ExtensionModule],
+) -> Result<HashMap<String, PathBuf>> {
     let root = cargo_metadata
         .root_package()
         .context("Expected the dependency graph to have a root package")?;
     // scan the dependency graph for path dependencies
     let mut path_deps = HashMap::new();
     let mut stack: Vec<&cargo_metadata::Package> = vec![root];
+    for module in extension_modules {
+        path_deps.insert(module.crate_name.clone(), module.manifest_path.clone());
+        if let Some(package) = cargo_metadata
+            .packages
+            .iter()
+            .find(|package| package.manifest_path == module.manifest_path)
+        {
+            stack.push(package)
+        }
+    }
     while let Some(top) = stack.pop() {
         for dependency in &top.dependencies {
             if let Some(path) = &dependency.path {
@@ -401,6 +487,94 @@ fn find_path_deps(cargo_metadata: &Metadata) -> Result<HashMap<String, PathBuf>>
     Ok(path_deps)
 }
 
+/// Runs `cargo vendor` for the root crate and adds the vendored crates to the source distribution,
+/// together with a `.cargo/config.toml` that replaces crates.io and the git sources with them
+fn add_vendored_dependencies(
+    writer: &mut SDistWriter,
+    build_context: &BuildContext,
+    manifest_path: &Path,
+    root_dir: &Path,
+) -> Result<()> {
+    println!("📦 Vendoring the cargo dependencies");
+    let temp_dir = tempfile::tempdir()?;
+    let vendor_dir = temp_dir.path().join(VENDOR_FOLDER);
+    let cargo_options = &build_context.cargo_options;
+    let mut command = Command::new("cargo");
+    command
+        .args(["vendor", "--manifest-path"])
+        .arg(manifest_path)
+        .arg(&vendor_dir);
+    if cargo_options.locked {
+        command.arg("--locked");
+    }
+    if cargo_options.frozen {
+        command.arg("--frozen");
+    }
+    if cargo_options.offline {
+        command.arg("--offline");
+    }
+    let output = command.output().with_context(|| {
+        format!(
+            "Failed to run `cargo vendor --manifest-path {}`",
+            manifest_path.display()
+        )
+    })?;
+    if !output.status.success() {
+        bail!(
+            "Failed to vendor the dependencies: {}\n--- Manifest path: {}\n--- Stderr:\n{}",
+            output.status,
+            manifest_path.display(),
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+    let config = vendored_sources_config(
+        str::from_utf8(&output.stdout).context("Cargo printed invalid utf-8 ಠ_ಠ")?,
+        &vendor_dir,
+    )?;
+
+    // The checksum files are hidden, so we can't use the default filters
+    for entry in ignore::WalkBuilder::new(&vendor_dir)
+        .standard_filters(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build()
+    {
+        let source = entry?.into_path();
+        let target = root_dir.join(source.strip_prefix(temp_dir.path()).unwrap());
+        if source.is_dir() {
+            writer.add_directory(target)?;
+        } else {
+            writer.add_file(target, &source)?;
+        }
+    }
+    writer.add_bytes(
+        root_dir.join(".cargo").join("config.toml"),
+        config.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Takes the source replacement `cargo vendor` prints and points it to the `vendor` folder in the
+/// root of the source distribution instead of the temporary directory we vendored to.
+///
+/// Cargo resolves `directory` relative to the parent of the `.cargo` folder
+fn vendored_sources_config(cargo_vendor_stdout: &str, vendor_dir: &Path) -> Result<String> {
+    let mut config: toml_edit::Document = cargo_vendor_stdout
+        .parse()
+        .context("Failed to parse the source replacement printed by `cargo vendor`")?;
+    let vendor_dir = vendor_dir
+        .to_str()
+        .context("The vendor directory is not utf-8")?;
+    if let Some(sources) = config.get_mut("source").and_then(|x| x.as_table_like_mut()) {
+        for (_, source) in sources.iter_mut() {
+            let directory = source.get_mut("directory");
+            if let Some(directory) = directory.filter(|x| x.as_str() == Some(vendor_dir)) {
+                *directory = toml_edit::value(VENDOR_FOLDER);
+            }
+        }
+    }
+    Ok(config.to_string())
+}
+
 /// Creates a source distribution, packing the root crate and all local dependencies
 ///
 /// The source distribution format is specified in
@@ -424,7 +598,33 @@ pub fn source_distribution(
     let workspace_manifest: toml_edit::Document =
         fs::read_to_string(&workspace_manifest_path)?.parse()?;
 
-    let known_path_deps = find_path_deps(&build_context.cargo_metadata)?;
+    let known_path_deps = find_path_deps(
+        &build_context.cargo_metadata,
+        &build_context.extension_modules,
+    )?;
+    let extension_crates: Vec<String> = build_context
+        .extension_modules
+        .iter()
+        .map(|module| module.crate_name.clone())
+        .collect();
+    // The extension modules point back to the workspace of the root crate from
+    // `local_dependencies/<name>`
+    let abs_manifest_path = manifest_path.normalize()?.into_path_buf();
+    let abs_manifest_dir = abs_manifest_path.parent().unwrap();
+    let root_crate_workspace =
+        match abs_manifest_dir.strip_prefix(pyproject_toml_path.parent().unwrap()) {
+            Ok(relative) if relative != Path::new("") => {
+                format!(
+                    "../../{}",
+                    relative
+                        .to_str()
+                        .context("The path to Cargo.toml is not utf-8")?
+                )
+            }
+            _ => "../..".to_string(),
+        };
+
+    let excludes = pyproject.exclude(Format::Sdist)?;
 
     let mut writer = SDistWriter::new(&build_context.out, metadata21)?;
     let root_dir = PathBuf::from(format!(
@@ -435,7 +635,10 @@ pub fn source_distribution(
 
     // Add local path dependencies
     let mut path_dep_workspace_manifests = HashMap::new();
-    for (name, path_dep) in known_path_deps.iter() {
+    // Sort the path dependencies so that the source distribution is reproducible
+    let mut sorted_path_deps: Vec<_> = known_path_deps.iter().collect();
+    sorted_path_deps.sort();
+    for (name, path_dep) in sorted_path_deps {
         // Path dependencies may not be in the same workspace as the root crate,
         // thus we need to find out its workspace root from `cargo metadata`
         let path_dep_metadata = MetadataCommand::new()
@@ -470,7 +673,14 @@ pub fn source_distribution(
             path_dep_workspace_manifest,
             &root_dir.join(LOCAL_DEPENDENCIES_FOLDER).join(name),
             &known_path_deps,
+            &[],
+            if extension_crates.contains(name) {
+                Some(root_crate_workspace.as_str())
+            } else {
+                None
+            },
             false,
+            &excludes,
         )
         .context(format!(
             "Failed to add local dependency {} at {} to the source distribution",
@@ -487,14 +697,22 @@ pub fn source_distribution(
         &workspace_manifest,
         &root_dir,
         &known_path_deps,
+        &extension_crates,
+        None,
         true,
+        &excludes,
     )?;
 
-    let abs_manifest_path = manifest_path.normalize()?.into_path_buf();
-    let abs_manifest_dir = abs_manifest_path.parent().unwrap();
+    // `cargo vendor` writes Cargo.lock if there is none yet, so this goes first
+    if build_context.sdist_vendor {
+        add_vendored_dependencies(&mut writer, build_context, &abs_manifest_path, &root_dir)?;
+    }
+
     let cargo_lock_path = abs_manifest_dir.join("Cargo.lock");
-    let cargo_lock_required =
-        build_context.cargo_options.locked || build_context.cargo_options.frozen;
+    // The vendored sources can only be used with the exact versions they were vendored for
+    let cargo_lock_required = build_context.cargo_options.locked
+        || build_context.cargo_options.frozen
+        || build_context.sdist_vendor;
     if cargo_lock_required || cargo_lock_path.exists() {
         let project_root = pyproject_toml_path.parent().unwrap();
         let relative_cargo_lock = if cargo_lock_path.starts_with(project_root) {
@@ -513,7 +731,10 @@ pub fn source_distribution(
     let pyproject_dir = pyproject_toml_path.parent().unwrap();
     // Add python source files
     if let Some(python_source) = build_context.project_layout.python_module.as_ref() {
-        for entry in ignore::Walk::new(python_source) {
+        for entry in ignore::WalkBuilder::new(python_source)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+        {
             let source = entry?.into_path();
             // Technically, `ignore` crate should handle this,
             // but somehow it doesn't on Alpine Linux running in GitHub Actions,
@@ -527,7 +748,12 @@ pub fn source_distribution(
                 debug!("Ignoring {}", source.display());
                 continue;
             }
-            let target = root_dir.join(source.strip_prefix(&pyproject_dir).unwrap());
+            let relative = source.strip_prefix(pyproject_dir).unwrap();
+            if is_excluded(relative, &excludes) {
+                debug!("Excluding {}", source.display());
+                continue;
+            }
+            let target = root_dir.join(relative);
             if source.is_dir() {
                 writer.add_directory(target)?;
             } else {
@@ -550,19 +776,22 @@ pub fn source_distribution(
         }
     }
 
-    if let Some(include_targets) = pyproject.sdist_include() {
-        for pattern in include_targets {
-            println!("📦 Including files matching \"{}\"", pattern);
-            for source in glob::glob(&pyproject_dir.join(pattern).to_string_lossy())
-                .expect("No files found for pattern")
-                .filter_map(Result::ok)
-            {
-                let target = root_dir.join(&source.strip_prefix(pyproject_dir).unwrap());
-                if source.is_dir() {
-                    writer.add_directory(target)?;
-                } else {
-                    writer.add_file(target, source)?;
-                }
+    for pattern in pyproject.include(Format::Sdist) {
+        println!("📦 Including files matching \"{}\"", pattern);
+        for source in glob::glob(&pyproject_dir.join(pattern).to_string_lossy())
+            .with_context(|| format!("Invalid include pattern \"{}\"", pattern))?
+            .filter_map(Result::ok)
+        {
+            let relative = source.strip_prefix(pyproject_dir).unwrap();
+            if is_excluded(relative, &excludes) {
+                debug!("Excluding {}", source.display());
+                continue;
+            }
+            let target = root_dir.join(relative);
+            if source.is_dir() {
+                writer.add_directory(target)?;
+            } else {
+                writer.add_file(target, &source)?;
             }
         }
     }
@@ -582,3 +811,301 @@ pub fn source_distribution(
 
     Ok(source_distribution_path)
 }
+
+/// Unpacks the source distribution and builds a wheel from it offline, like pip does after
+/// downloading it from PyPI
+///
+/// The files of the packed crates and the python source that are not in the source distribution
+/// are reported first, since they are the usual reason for a failing build
+pub fn verify_source_distribution(build_context: &BuildContext, sdist_path: &Path) -> Result<()> {
+    println!("🔍 Verifying the source distribution by building a wheel from it");
+    let temp_dir = tempfile::tempdir()?;
+    let unpacked = temp_dir.path().join("sdist");
+    let root_dir = unpack_source_distribution(sdist_path, &unpacked)?;
+
+    for (source, target) in find_missing_files(build_context, &root_dir)? {
+        eprintln!(
+            "⚠️  Warning: {} is not in the source distribution, expected it at {}",
+            source.display(),
+            target.strip_prefix(&unpacked).unwrap().display()
+        );
+    }
+
+    // The crate is at the same place relative to pyproject.toml as in the project
+    let pyproject_dir = build_context
+        .pyproject_toml_path
+        .normalize()?
+        .into_path_buf()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let abs_manifest_path = build_context.manifest_path.normalize()?.into_path_buf();
+    let manifest_path = match abs_manifest_path.strip_prefix(&pyproject_dir) {
+        Ok(relative) => root_dir.join(relative),
+        Err(_) => root_dir.join("Cargo.toml"),
+    };
+    let build_options = BuildOptions {
+        interpreter: build_context
+            .interpreter
+            .iter()
+            .take(1)
+            .map(|interpreter| interpreter.executable.clone())
+            .collect(),
+        out: Some(temp_dir.path().join("wheels")),
+        skip_auditwheel: true,
+        cargo: CargoOptions {
+            target_dir: Some(
+                build_context
+                    .target_dir
+                    .join("maturin")
+                    .join("sdist-verify"),
+            ),
+            manifest_path: Some(manifest_path),
+            offline: true,
+            // Run cargo in the root of the source distribution like pip, which is also the only
+            // place cargo picks up the `.cargo/config.toml` of vendored dependencies from
+            current_dir: Some(root_dir.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let wheels = build_options
+        .into_build_context(false, false, false)
+        .and_then(|context| context.build_wheels())
+        .context("Failed to build a wheel from the source distribution")?;
+
+    println!(
+        "✅ Built {} from the source distribution",
+        wheels[0].0.file_name().unwrap().to_string_lossy()
+    );
+    Ok(())
+}
+
+/// Unpacks the source distribution into `dest` and returns its root directory
+fn unpack_source_distribution(sdist_path: &Path, dest: &Path) -> Result<PathBuf> {
+    tar::Archive::new(GzDecoder::new(fs::File::open(sdist_path)?))
+        .unpack(dest)
+        .with_context(|| format!("Failed to unpack {}", sdist_path.display()))?;
+    let root_dir = fs::read_dir(dest)?
+        .next()
+        .context("The source distribution is empty")??
+        .path();
+    Ok(root_dir)
+}
+
+/// Lists the files in the directories of the packed crates and the python source that are not in
+/// the unpacked source distribution, together with where they would have been
+///
+/// Build artifacts, virtualenvs, hidden files and nested crates that are not path dependencies
+/// are skipped
+fn find_missing_files(
+    build_context: &BuildContext,
+    root_dir: &Path,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let pyproject_toml_path = build_context
+        .pyproject_toml_path
+        .normalize()?
+        .into_path_buf();
+    let pyproject_dir = pyproject_toml_path.parent().unwrap();
+    let abs_manifest_path = build_context.manifest_path.normalize()?.into_path_buf();
+    let abs_manifest_dir = abs_manifest_path.parent().unwrap();
+
+    // The directories that were packed and where they are in the source distribution
+    let mut packed_dirs = vec![(
+        abs_manifest_dir.to_path_buf(),
+        match abs_manifest_dir.strip_prefix(pyproject_dir) {
+            Ok(relative) => root_dir.join(relative),
+            Err(_) => root_dir.to_path_buf(),
+        },
+    )];
+    if let Some(python_source) = build_context.project_layout.python_module.as_ref() {
+        if let Ok(relative) = python_source.strip_prefix(pyproject_dir) {
+            packed_dirs.push((python_source.clone(), root_dir.join(relative)));
+        }
+    }
+    for (name, path_dep) in find_path_deps(
+        &build_context.cargo_metadata,
+        &build_context.extension_modules,
+    )? {
+        let path_dep_dir = path_dep.parent().unwrap().normalize()?.into_path_buf();
+        packed_dirs.push((
+            path_dep_dir,
+            root_dir.join(LOCAL_DEPENDENCIES_FOLDER).join(name),
+        ));
+    }
+
+    let mut skipped_dirs: Vec<PathBuf> = packed_dirs.iter().map(|(dir, _)| dir.clone()).collect();
+    skipped_dirs.push(build_context.target_dir.clone());
+    skipped_dirs.push(build_context.out.clone());
+    let mut missing = Vec::new();
+    for (dir, target_dir) in &packed_dirs {
+        let skipped_dirs = skipped_dirs.clone();
+        let walker = ignore::WalkBuilder::new(dir)
+            // `.gitignore` is one of the reasons for missing files, so we can't use it here
+            .standard_filters(false)
+            .hidden(true)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .filter_entry(move |entry| {
+                let path = entry.path();
+                if entry.depth() == 0 || !path.is_dir() {
+                    return true;
+                }
+                !(skipped_dirs.iter().any(|skipped| path == skipped)
+                    || path.file_name().unwrap() == "target"
+                    || path.file_name().unwrap() == "__pycache__"
+                    || path.join("Cargo.toml").is_file()
+                    || path.join("pyvenv.cfg").is_file())
+            })
+            .build();
+        for entry in walker {
+            let source = entry?.into_path();
+            if source.is_dir()
+                || source
+                    .extension()
+                    .map(|ext| ext == "pyc" || ext == "pyd" || ext == "so")
+                    .unwrap_or_default()
+            {
+                continue;
+            }
+            let target = target_dir.join(source.strip_prefix(dir).unwrap());
+            if !target.exists() {
+                missing.push((source, target));
+            }
+        }
+    }
+    missing.sort();
+    missing.dedup();
+    Ok(missing)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        find_missing_files, unpack_source_distribution, vendored_sources_config,
+        verify_source_distribution,
+    };
+    use crate::{BuildOptions, CargoOptions};
+    use fs_err as fs;
+    use std::path::Path;
+
+    /// Writes a binary crate whose `src/util.rs` is excluded from the sdist if `exclude` is set
+    fn write_project(dir: &Path, exclude: bool) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"verify-me\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+        )
+        .unwrap();
+        let mut pyproject = "[build-system]\nrequires = [\"maturin>=0.14,<0.15\"]\n\
+            build-backend = \"maturin\"\n\n[tool.maturin]\nbindings = \"bin\"\n"
+            .to_string();
+        if exclude {
+            pyproject.push_str("exclude = [{ path = \"src/util.rs\", format = \"sdist\" }]\n");
+        }
+        fs::write(dir.join("pyproject.toml"), pyproject).unwrap();
+        fs::write(
+            dir.join("src").join("main.rs"),
+            "mod util;\n\nfn main() {\n    util::hello();\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("src").join("util.rs"),
+            "pub fn hello() {\n    println!(\"Hello\");\n}\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_verify_source_distribution() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("verify-me");
+        let build_options = BuildOptions {
+            out: Some(temp_dir.path().join("dist")),
+            cargo: CargoOptions {
+                manifest_path: Some(project.join("Cargo.toml")),
+                quiet: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        write_project(&project, true);
+        let build_context = build_options
+            .clone()
+            .into_build_context(false, false, false)
+            .unwrap();
+        let (sdist_path, _) = build_context.build_source_distribution().unwrap().unwrap();
+        let unpacked = temp_dir.path().join("unpacked");
+        let root_dir = unpack_source_distribution(&sdist_path, &unpacked).unwrap();
+        let missing = find_missing_files(&build_context, &root_dir).unwrap();
+        assert_eq!(
+            missing,
+            [(
+                project.join("src").join("util.rs").canonicalize().unwrap(),
+                root_dir.join("src").join("util.rs")
+            )]
+        );
+        assert!(verify_source_distribution(&build_context, &sdist_path).is_err());
+
+        write_project(&project, false);
+        let build_context = build_options
+            .into_build_context(false, false, false)
+            .unwrap();
+        let (sdist_path, _) = build_context.build_source_distribution().unwrap().unwrap();
+        verify_source_distribution(&build_context, &sdist_path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_include_pattern() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("verify-me");
+        write_project(&project, false);
+        let pyproject_toml = project.join("pyproject.toml");
+        let mut pyproject = fs::read_to_string(&pyproject_toml).unwrap();
+        pyproject.push_str("include = [\"src/[util.rs\"]\n");
+        fs::write(&pyproject_toml, pyproject).unwrap();
+        let build_context = BuildOptions {
+            out: Some(temp_dir.path().join("dist")),
+            cargo: CargoOptions {
+                manifest_path: Some(project.join("Cargo.toml")),
+                quiet: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+        .into_build_context(false, false, false)
+        .unwrap();
+        let err = build_context.build_source_distribution().unwrap_err();
+        assert!(format!("{:#}", err).contains("Invalid include pattern \"src/[util.rs\""));
+    }
+
+    #[test]
+    fn test_vendored_sources_config() {
+        let stdout = r#"
+[source.crates-io]
+replace-with = "vendored-sources"
+
+[source."git+https://github.com/PyO3/pyo3?branch=main"]
+git = "https://github.com/PyO3/pyo3"
+branch = "main"
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "/tmp/.tmpAbCdEf/vendor"
+"#;
+        let config = vendored_sources_config(stdout, Path::new("/tmp/.tmpAbCdEf/vendor")).unwrap();
+        let config: toml_edit::Document = config.parse().unwrap();
+        assert_eq!(
+            config["source"]["vendored-sources"]["directory"].as_str(),
+            Some("vendor")
+        );
+        assert_eq!(
+            config["source"]["crates-io"]["replace-with"].as_str(),
+            Some("vendored-sources")
+        );
+        assert_eq!(
+            config["source"]["git+https://github.com/PyO3/pyo3?branch=main"]["git"].as_str(),
+            Some("https://github.com/PyO3/pyo3")
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::auditwheel::format_linux_platform_tag;
 use crate::cross_compile::is_cross_compiling;
 use crate::python_interpreter::InterpreterKind;
 use crate::{PlatformTag, PythonInterpreter};
@@ -302,16 +303,7 @@ impl Target {
                         .map(|info| info.machine().into_owned())
                         .unwrap_or_else(|_| self.arch.to_string())
                 };
-                let mut platform_tags = platform_tags.to_vec();
-                platform_tags.sort();
-                let mut tags = vec![];
-                for platform_tag in platform_tags {
-                    tags.push(format!("{}_{}", platform_tag, arch));
-                    for alias in platform_tag.aliases() {
-                        tags.push(format!("{}_{}", alias, arch));
-                    }
-                }
-                tags.join(".")
+                format_linux_platform_tag(platform_tags, &arch)
             }
             // macOS
             (Os::Macos, Arch::X86_64) => {
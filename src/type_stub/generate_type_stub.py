@@ -0,0 +1,144 @@
+# Imports the native module `MODULE_NAME` from `MODULE_DIR` and prints a type stub for it.
+# maturin defines both variables in front of this script.
+import importlib
+import inspect
+import sys
+
+sys.path.insert(0, MODULE_DIR)
+module = importlib.import_module(MODULE_NAME)
+
+# Attributes every class has that don't belong into the stub
+SKIPPED_CLASS_ATTRIBUTES = {
+    "__dict__",
+    "__doc__",
+    "__module__",
+    "__new__",
+    "__qualname__",
+    "__text_signature__",
+    "__weakref__",
+}
+
+
+def docstring(obj, indent):
+    doc = inspect.getdoc(obj)
+    if not doc:
+        return []
+    doc = doc.replace("\\", "\\\\").replace('"""', '\\"\\"\\"')
+    lines = ('"""' + doc + '"""').splitlines()
+    return [indent + line if line else "" for line in lines]
+
+
+def parameters(obj, bound):
+    try:
+        signature = inspect.signature(obj)
+    except (TypeError, ValueError):
+        return ["self", "*args", "**kwargs"] if bound else ["*args", "**kwargs"]
+    params = []
+    positional_only = False
+    keyword_only = False
+    for param in signature.parameters.values():
+        if param.kind == param.POSITIONAL_ONLY:
+            positional_only = True
+        elif positional_only:
+            params.append("/")
+            positional_only = False
+        if param.kind == param.VAR_POSITIONAL:
+            keyword_only = True
+            params.append("*" + param.name)
+        elif param.kind == param.VAR_KEYWORD:
+            params.append("**" + param.name)
+        else:
+            if param.kind == param.KEYWORD_ONLY and not keyword_only:
+                keyword_only = True
+                params.append("*")
+            if param.default is param.empty:
+                params.append(param.name)
+            else:
+                params.append(param.name + "=...")
+    if positional_only:
+        params.append("/")
+    if bound and (not params or params[0] not in ("self", "cls")):
+        params.insert(0, "self")
+    return params
+
+
+def function(name, obj, indent, bound=False, decorator=None):
+    lines = []
+    if decorator:
+        lines.append(indent + "@" + decorator)
+    signature = "def {}({}):".format(name, ", ".join(parameters(obj, bound)))
+    doc = docstring(obj, indent + "    ")
+    if doc:
+        lines.append(indent + signature)
+        lines.extend(doc)
+        lines.append(indent + "    ...")
+    else:
+        lines.append(indent + signature + " ...")
+    return lines
+
+
+def annotation(value):
+    if isinstance(value, (bool, int, float, str, bytes)):
+        return type(value).__name__
+    return "Any"
+
+
+def base_names(cls):
+    names = []
+    for base in cls.__bases__:
+        if base is object:
+            continue
+        if base.__module__ == "builtins":
+            names.append(base.__name__)
+        elif base.__module__ == cls.__module__:
+            names.append(base.__qualname__)
+    return names
+
+
+def class_stub(name, cls, indent):
+    bases = base_names(cls)
+    lines = [indent + "class {}{}:".format(name, "(" + ", ".join(bases) + ")" if bases else "")]
+    body = docstring(cls, indent + "    ")
+    if getattr(cls, "__text_signature__", None):
+        body.extend(function("__init__", cls, indent + "    ", bound=True))
+    for attr, value in cls.__dict__.items():
+        if attr in SKIPPED_CLASS_ATTRIBUTES:
+            continue
+        if isinstance(value, classmethod) or type(value).__name__ == "classmethod_descriptor":
+            body.extend(function(attr, getattr(cls, attr), indent + "    ", decorator="classmethod"))
+        elif isinstance(value, staticmethod):
+            body.extend(
+                function(attr, value.__func__, indent + "    ", decorator="staticmethod")
+            )
+        elif inspect.isdatadescriptor(value):
+            body.append(indent + "    {}: Any".format(attr))
+        elif callable(value):
+            body.extend(function(attr, value, indent + "    ", bound=True))
+        elif not attr.startswith("__"):
+            body.append(indent + "    {}: {}".format(attr, annotation(value)))
+    if not body:
+        lines[0] += " ..."
+    return lines + body
+
+
+def module_stub(module):
+    lines = docstring(module, "")
+    lines.append("from typing import Any")
+    names = getattr(module, "__all__", None)
+    if names is None:
+        names = [name for name in dir(module) if not name.startswith("_")]
+    for name in names:
+        value = getattr(module, name)
+        if inspect.ismodule(value):
+            continue
+        lines.append("")
+        if inspect.isclass(value):
+            lines.extend(class_stub(name, value, ""))
+        elif callable(value):
+            lines.extend(function(name, value, ""))
+        else:
+            lines.append("{}: {}".format(name, annotation(value)))
+    return "\n".join(lines) + "\n"
+
+
+print(module_stub(module), end="")
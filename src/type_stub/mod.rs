@@ -0,0 +1,73 @@
+use crate::PythonInterpreter;
+use anyhow::{Context, Result};
+use fs_err as fs;
+use std::path::Path;
+
+/// Prints the type stub of `MODULE_NAME` imported from `MODULE_DIR`
+const GENERATE_TYPE_STUB: &str = include_str!("generate_type_stub.py");
+
+/// The first line of every generated stub, which tells them apart from handwritten ones that
+/// must not be overwritten
+pub(crate) const GENERATED_STUB_HEADER: &str = "# This file is generated by maturin";
+
+/// Whether the file is a type stub generated by maturin
+pub(crate) fn is_generated_stub(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .map(|stub| stub.starts_with(GENERATED_STUB_HEADER))
+        .unwrap_or(false)
+}
+
+/// Generates a `.pyi` type stub for a compiled native module by importing it with the given
+/// interpreter and introspecting its functions, classes and constants
+///
+/// The signatures come from the `text_signature`s pyo3 exports, so only parameter names,
+/// defaults and docstrings end up in the stub, not the rust types.
+pub(crate) fn generate_type_stub(
+    interpreter: &PythonInterpreter,
+    artifact: &Path,
+    ext_name: &str,
+) -> Result<String> {
+    // Copy the library so it can be imported under the name python expects
+    let temp_dir = tempfile::tempdir()?;
+    let library = temp_dir.path().join(interpreter.get_library_name(ext_name));
+    fs::copy(artifact, &library)?;
+    let stub = interpreter
+        .run_script(&stub_script(temp_dir.path(), ext_name)?)
+        .with_context(|| format!("Failed to import {} to generate its type stub", ext_name))?;
+    Ok(format!("{}\n{}", GENERATED_STUB_HEADER, stub))
+}
+
+/// Prepends the location of the module to the stub generation script
+fn stub_script(module_dir: &Path, module_name: &str) -> Result<String> {
+    // json strings are valid python string literals
+    Ok(format!(
+        "MODULE_DIR = {}\nMODULE_NAME = {}\n{}",
+        serde_json::to_string(module_dir)?,
+        serde_json::to_string(module_name)?,
+        GENERATE_TYPE_STUB
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::stub_script;
+    use crate::{BridgeModel, PythonInterpreter, Target};
+    use std::path::Path;
+
+    #[test]
+    fn test_stub_script_builtin_module() {
+        let target = Target::from_target_triple(None).unwrap();
+        let python = match PythonInterpreter::check_executable(
+            target.get_python(),
+            &target,
+            &BridgeModel::Cffi,
+        ) {
+            Ok(Some(python)) => python,
+            _ => return,
+        };
+        let script = stub_script(Path::new("."), "math").unwrap();
+        let stub = python.run_script(&script).unwrap();
+        assert!(stub.contains("\ndef sqrt(x, /):\n"));
+        assert!(stub.contains("\npi: float\n"));
+    }
+}
@@ -2,6 +2,7 @@
 //! documentation at https://warehouse.readthedocs.io/api-reference/legacy/#upload-api
 
 use crate::build_context::hash_file;
+use crate::check_dist::check_dists;
 use anyhow::{bail, Context, Result};
 use bytesize::ByteSize;
 use configparser::ini::Ini;
@@ -9,19 +10,36 @@ use fs_err as fs;
 use fs_err::File;
 use multipart::client::lazy::Multipart;
 use regex::Regex;
+use serde_json::json;
+use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 use thiserror::Error;
 
 /// An account with a registry, possibly incomplete
 #[derive(Debug, clap::Parser)]
 pub struct PublishOpt {
     /// The repository (package index) to upload the package to. Should be a section in the config file.
+    /// Can be repeated to upload to multiple repositories.
     ///
-    /// Can also be set via MATURIN_REPOSITORY environment variable.
-    #[arg(short = 'r', long, env = "MATURIN_REPOSITORY", default_value = "pypi")]
-    repository: String,
+    /// Can also be set via MATURIN_REPOSITORY environment variable, separated by commas.
+    #[arg(
+        short = 'r',
+        long,
+        env = "MATURIN_REPOSITORY",
+        default_value = "pypi",
+        value_delimiter = ','
+    )]
+    repository: Vec<String>,
+    /// Upload to all repositories listed in `index-servers` of the `[distutils]` section of the
+    /// config file
+    #[arg(long, conflicts_with_all = ["repository", "repository_url"])]
+    all_repositories: bool,
     /// The URL of the registry where the wheels are uploaded to. This overrides --repository.
     ///
     /// Can also be set via MATURIN_REPOSITORY_URL environment variable.
@@ -45,8 +63,34 @@ pub struct PublishOpt {
     password: Option<String>,
     /// Continue uploading files if one already exists.
     /// (Only valid when uploading to PyPI. Other implementations may not support this.)
+    ///
+    /// Files that are on the simple index of the repository with the same sha256 are not uploaded
+    /// at all, while a file with the same name but a different sha256 is an error.
     #[arg(long = "skip-existing")]
     skip_existing: bool,
+    /// The simple index (PEP 503/691) that --skip-existing checks for existing files. Defaults to
+    /// `/simple/` on the host of the repository url, e.g. https://pypi.org/simple/ for pypi.
+    ///
+    /// Can also be set via MATURIN_INDEX_URL environment variable.
+    #[arg(long = "index-url", env = "MATURIN_INDEX_URL")]
+    index_url: Option<String>,
+    /// Require trusted publishing instead of falling back to other credentials if it fails.
+    ///
+    /// Without this flag, trusted publishing is used when there is no token, pypirc entry,
+    /// username or password and an OIDC identity token is available from MATURIN_OIDC_TOKEN,
+    /// MATURIN_OIDC_TOKEN_FILE or GitHub Actions
+    #[arg(long = "trusted-publishing")]
+    trusted_publishing: bool,
+    /// Check the files like `maturin check-dist` and don't upload anything if one of them fails
+    #[arg(long)]
+    check: bool,
+    /// The number of packages to upload at the same time
+    #[arg(long, default_value = "4")]
+    concurrency: usize,
+    /// How often to retry an upload that failed with a connection error or a 5xx status, waiting
+    /// 1s, 2s, 4s, ... between the attempts
+    #[arg(long, default_value = "3")]
+    retries: u32,
 }
 
 impl PublishOpt {
@@ -64,7 +108,7 @@ impl PublishOpt {
 pub enum UploadError {
     /// Any ureq error
     #[error("Http error")]
-    UreqError(#[source] ureq::Error),
+    UreqError(#[source] Box<ureq::Error>),
     /// The registry returned a "403 Forbidden"
     #[error("Username or password are incorrect")]
     AuthenticationError,
@@ -94,7 +138,7 @@ impl From<io::Error> for UploadError {
 
 impl From<ureq::Error> for UploadError {
     fn from(error: ureq::Error) -> Self {
-        UploadError::UreqError(error)
+        UploadError::UreqError(Box::new(error))
     }
 }
 
@@ -159,6 +203,7 @@ fn get_username() -> String {
 
 fn load_pypirc() -> Ini {
     let mut config = Ini::new();
+    config.set_multiline(true);
     if let Some(mut config_path) = dirs::home_dir() {
         config_path.push(".pypirc");
         if let Ok(pypirc) = fs::read_to_string(config_path.as_path()) {
@@ -178,46 +223,351 @@ fn load_pypi_cred_from_config(config: &Ini, registry_name: &str) -> Option<(Stri
     None
 }
 
+/// The sources of an ambient OIDC identity token for trusted publishing
+#[derive(Debug, Default)]
+struct OidcEnv {
+    /// `MATURIN_OIDC_TOKEN`, the identity token itself
+    token: Option<String>,
+    /// `MATURIN_OIDC_TOKEN_FILE`, a file containing the identity token
+    token_file: Option<PathBuf>,
+    /// `ACTIONS_ID_TOKEN_REQUEST_URL` and `ACTIONS_ID_TOKEN_REQUEST_TOKEN` on GitHub Actions
+    /// with the `id-token: write` permission
+    github_request: Option<(String, String)>,
+}
+
+impl OidcEnv {
+    fn from_env() -> Self {
+        OidcEnv {
+            token: env::var("MATURIN_OIDC_TOKEN").ok(),
+            token_file: env::var_os("MATURIN_OIDC_TOKEN_FILE").map(PathBuf::from),
+            github_request: env::var("ACTIONS_ID_TOKEN_REQUEST_URL")
+                .ok()
+                .zip(env::var("ACTIONS_ID_TOKEN_REQUEST_TOKEN").ok()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.token.is_none() && self.token_file.is_none() && self.github_request.is_none()
+    }
+}
+
+/// The url of the index that serves the trusted publishing endpoints for the upload url,
+/// e.g. `https://pypi.org` for `https://upload.pypi.org/legacy/`
+fn index_base_url(upload_url: &str) -> Result<String> {
+    let (scheme, rest) = upload_url
+        .split_once("://")
+        .with_context(|| format!("Invalid repository url {}", upload_url))?;
+    let host = rest.split('/').next().unwrap();
+    let host = host.strip_prefix("upload.").unwrap_or(host);
+    Ok(format!("{}://{}", scheme, host))
+}
+
+/// Reads the json body of a response, turning error statuses into errors with the messages of
+/// the index
+fn read_json(
+    response: Result<ureq::Response, ureq::Error>,
+    url: &str,
+) -> Result<serde_json::Value> {
+    match response {
+        Ok(response) => {
+            let body = response.into_string()?;
+            serde_json::from_str(&body)
+                .with_context(|| format!("Invalid json from {}: {}", url, body))
+        }
+        Err(ureq::Error::Status(status, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            // Warehouse returns `{"message": ..., "errors": [{"code": ..., "description": ...}]}`
+            let message = match serde_json::from_str::<serde_json::Value>(&body) {
+                Ok(value) if value["message"].is_string() => {
+                    let mut message = value["message"].as_str().unwrap().to_string();
+                    for error in value["errors"].as_array().into_iter().flatten() {
+                        message.push_str(&format!(
+                            "\n  * {}: {}",
+                            error["code"].as_str().unwrap_or("unknown"),
+                            error["description"].as_str().unwrap_or_default()
+                        ));
+                    }
+                    message
+                }
+                _ => body,
+            };
+            bail!("{} returned status {}: {}", url, status, message)
+        }
+        Err(err) => Err(err).with_context(|| format!("Failed to connect to {}", url)),
+    }
+}
+
+/// Percent-encodes a query parameter value
+fn encode_query_value(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Reads the identity token from the environment or a file, or requests one for the audience
+/// of the index from GitHub Actions
+fn get_oidc_token(agent: &ureq::Agent, base_url: &str, oidc: &OidcEnv) -> Result<String> {
+    if let Some(token) = &oidc.token {
+        return Ok(token.trim().to_string());
+    }
+    if let Some(token_file) = &oidc.token_file {
+        return Ok(fs::read_to_string(token_file)?.trim().to_string());
+    }
+    let (request_url, request_token) = oidc
+        .github_request
+        .as_ref()
+        .context("No OIDC identity token available")?;
+
+    let audience_url = format!("{}/_/oidc/audience", base_url);
+    let audience = read_json(agent.get(&audience_url).call(), &audience_url)?;
+    let audience = audience["audience"]
+        .as_str()
+        .with_context(|| format!("{} didn't return an audience", audience_url))?;
+
+    let separator = if request_url.contains('?') { '&' } else { '?' };
+    let token_url = format!(
+        "{}{}audience={}",
+        request_url,
+        separator,
+        encode_query_value(audience)
+    );
+    let response = agent
+        .get(&token_url)
+        .set("Authorization", &format!("bearer {}", request_token))
+        .call();
+    let token = read_json(response, "the GitHub Actions token endpoint")?;
+    token["value"]
+        .as_str()
+        .map(ToString::to_string)
+        .context("GitHub Actions didn't return an OIDC identity token")
+}
+
+/// Exchanges the identity token for a short-lived upload token at the mint-token endpoint of
+/// the index
+fn mint_token(agent: &ureq::Agent, base_url: &str, oidc_token: &str) -> Result<String> {
+    let mint_url = format!("{}/_/oidc/mint-token", base_url);
+    let response = agent
+        .post(&mint_url)
+        .set("Content-Type", "application/json")
+        .send_string(&json!({ "token": oidc_token }).to_string());
+    let minted = read_json(response, &mint_url)?;
+    minted["token"]
+        .as_str()
+        .map(ToString::to_string)
+        .with_context(|| format!("{} didn't return a token", mint_url))
+}
+
+/// Gets a short-lived upload token through trusted publishing if there is an OIDC identity token
+///
+/// Failures fall back to the other credential sources with a warning, unless
+/// `--trusted-publishing` requires it
+fn resolve_trusted_publishing(
+    opt: &PublishOpt,
+    agent: &ureq::Agent,
+    registry_url: &str,
+    oidc: &OidcEnv,
+) -> Result<Option<String>> {
+    if oidc.is_empty() {
+        if opt.trusted_publishing {
+            bail!(
+                "Trusted publishing requires an OIDC identity token from MATURIN_OIDC_TOKEN, \
+                MATURIN_OIDC_TOKEN_FILE or GitHub Actions with the `id-token: write` permission"
+            );
+        }
+        return Ok(None);
+    }
+    let token = index_base_url(registry_url).and_then(|base_url| {
+        let oidc_token = get_oidc_token(agent, &base_url, oidc)?;
+        mint_token(agent, &base_url, &oidc_token)
+    });
+    match token {
+        Ok(token) => {
+            println!("🔐 Using trusted publishing for upload");
+            Ok(Some(token))
+        }
+        Err(err) if !opt.trusted_publishing => {
+            eprintln!(
+                "⚠️  Warning: Trusted publishing failed, falling back to other credentials: {:#}",
+                err
+            );
+            Ok(None)
+        }
+        Err(err) => Err(err.context("Trusted publishing failed")),
+    }
+}
+
 /// Gets the PyPI credentials from (in precedence order):
 ///
-/// 1. `MATURIN_PYPI_TOKEN` environment variable
-/// 2. `.pypirc` config file
-/// 3. maturin command arguments
-/// 4. `MATURIN_USERNAME` and `MATURIN_PASSWORD` environment variables
-/// 5. the password keyring
-/// 6. interactive prompt
+/// 1. trusted publishing if `--trusted-publishing` is passed
+/// 2. `MATURIN_PYPI_TOKEN` environment variable
+/// 3. `.pypirc` config file
+/// 4. trusted publishing if there is an OIDC identity token and neither a username nor a
+///    password are given
+/// 5. maturin command arguments
+/// 6. `MATURIN_USERNAME` and `MATURIN_PASSWORD` environment variables
+/// 7. the password keyring
+/// 8. interactive prompt
+///
+/// Without `use_global_cred`, only the `.pypirc` config file, the keyring and the prompt are
+/// used, so that credentials meant for another repository aren't sent to this one.
+///
+/// Also returns whether the password is a short-lived token from trusted publishing
 fn resolve_pypi_cred(
     opt: &PublishOpt,
     config: &Ini,
     registry_name: Option<&str>,
-) -> (String, String) {
+    registry_url: &str,
+    use_global_cred: bool,
+) -> Result<(String, String, bool)> {
+    if !use_global_cred {
+        if let Some((username, password)) =
+            registry_name.and_then(|name| load_pypi_cred_from_config(config, name))
+        {
+            println!("🔐 Using credential in pypirc for upload");
+            return Ok((username, password, false));
+        }
+        let username = get_username();
+        let password = get_password(&username);
+        return Ok((username, password, false));
+    }
+
+    if opt.trusted_publishing {
+        let token =
+            resolve_trusted_publishing(opt, &http_agent()?, registry_url, &OidcEnv::from_env())?
+                .context("Trusted publishing didn't return a token")?;
+        return Ok(("__token__".to_string(), token, true));
+    }
+
     // API token from environment variable takes priority
     if let Ok(token) = env::var("MATURIN_PYPI_TOKEN") {
-        return ("__token__".to_string(), token);
+        return Ok(("__token__".to_string(), token, false));
     }
 
     if let Some((username, password)) =
         registry_name.and_then(|name| load_pypi_cred_from_config(config, name))
     {
         println!("🔐 Using credential in pypirc for upload");
-        return (username, password);
+        return Ok((username, password, false));
     }
 
-    // fallback to username and password
-    let username = opt.username.clone().unwrap_or_else(get_username);
     let password = opt
         .password
         .clone()
-        .or_else(|| env::var("MATURIN_PASSWORD").ok())
-        .unwrap_or_else(|| get_password(&username));
+        .or_else(|| env::var("MATURIN_PASSWORD").ok());
+    if opt.username.is_none() && password.is_none() {
+        let oidc = OidcEnv::from_env();
+        if !oidc.is_empty() {
+            if let Some(token) =
+                resolve_trusted_publishing(opt, &http_agent()?, registry_url, &oidc)?
+            {
+                return Ok(("__token__".to_string(), token, true));
+            }
+        }
+    }
 
-    (username, password)
+    // fallback to username and password
+    let username = opt.username.clone().unwrap_or_else(get_username);
+    let password = password.unwrap_or_else(|| get_password(&username));
+
+    Ok((username, password, false))
 }
 
-/// Asks for username and password for a registry account where missing.
-fn complete_registry(opt: &PublishOpt) -> Result<Registry> {
-    // load creds from pypirc if found
-    let pypirc = load_pypirc();
+/// The repositories to upload to: the repository url, all `index-servers` of the config file
+/// with `--all-repositories` or the `--repository` names without duplicates
+fn repositories(opt: &PublishOpt, pypirc: &Ini) -> Result<Vec<String>> {
+    if let Some(repository_url) = &opt.repository_url {
+        return Ok(vec![repository_url.clone()]);
+    }
+    let names = if opt.all_repositories {
+        let index_servers = pypirc.get("distutils", "index-servers").unwrap_or_default();
+        let names: Vec<String> = index_servers
+            .split_whitespace()
+            .map(ToString::to_string)
+            .collect();
+        if names.is_empty() {
+            bail!(
+                "--all-repositories requires an index-servers list in the [distutils] section of .pypirc"
+            );
+        }
+        names
+    } else {
+        opt.repository.clone()
+    };
+    let mut repositories = Vec::new();
+    for name in names {
+        if !repositories.contains(&name) {
+            repositories.push(name);
+        }
+    }
+    Ok(repositories)
+}
+
+/// Whether one of `MATURIN_PYPI_TOKEN`, `--username`, `--password` or `--trusted-publishing`
+/// is given, which don't belong to a specific repository
+fn has_global_cred(opt: &PublishOpt) -> bool {
+    opt.trusted_publishing
+        || opt.username.is_some()
+        || opt.password.is_some()
+        || env::var_os("MATURIN_PYPI_TOKEN").is_some()
+        || env::var_os("MATURIN_PASSWORD").is_some()
+}
+
+/// Decides which repositories may use the credentials that don't belong to a specific
+/// repository, e.g. `MATURIN_PYPI_TOKEN`
+///
+/// A single repository always uses them. With several repositories, each one uses its own
+/// section of `.pypirc` first, and the global credentials go to the one repository without
+/// credentials in `.pypirc`. If there are several of those, the global credentials are rejected
+/// instead of being sent to all of them.
+fn global_cred_repositories(
+    repositories: &[String],
+    pypirc: &Ini,
+    has_global_cred: bool,
+) -> Result<Vec<bool>> {
+    if repositories.len() <= 1 {
+        return Ok(vec![true; repositories.len()]);
+    }
+    let without_pypirc: Vec<bool> = repositories
+        .iter()
+        .map(|repository| load_pypi_cred_from_config(pypirc, repository).is_none())
+        .collect();
+    let count = without_pypirc.iter().filter(|&&without| without).count();
+    if has_global_cred && count > 1 {
+        let names: Vec<_> = repositories
+            .iter()
+            .zip(&without_pypirc)
+            .filter(|(_, &without)| without)
+            .map(|(repository, _)| repository.as_str())
+            .collect();
+        bail!(
+            "MATURIN_PYPI_TOKEN, --username, --password and --trusted-publishing can only be used \
+            for one repository when uploading to multiple repositories, but {} have no credentials \
+            in .pypirc",
+            names.join(", ")
+        );
+    }
+    Ok(without_pypirc)
+}
+
+/// Asks for username and password for an account with the repository where missing.
+///
+/// `use_global_cred` is whether the credentials that don't belong to a specific repository may
+/// be used, see [global_cred_repositories].
+///
+/// Also returns whether the password is a short-lived token from trusted publishing, which
+/// must not be stored in the keyring
+fn complete_registry(
+    opt: &PublishOpt,
+    pypirc: &Ini,
+    repository: &str,
+    use_global_cred: bool,
+) -> Result<(Registry, bool)> {
     let (registry_name, registry_url) = if let Some(repository_url) = opt.repository_url.as_deref()
     {
         let name = match repository_url {
@@ -226,11 +576,11 @@ fn complete_registry(opt: &PublishOpt) -> Result<Registry> {
             _ => None,
         };
         (name, repository_url.to_string())
-    } else if let Some(url) = pypirc.get(&opt.repository, "repository") {
-        (Some(opt.repository.as_str()), url)
-    } else if opt.repository == "pypi" {
+    } else if let Some(url) = pypirc.get(repository, "repository") {
+        (Some(repository), url)
+    } else if repository == "pypi" {
         (Some("pypi"), PublishOpt::DEFAULT_REPOSITORY_URL.to_string())
-    } else if opt.repository == "testpypi" {
+    } else if repository == "testpypi" {
         (
             Some("testpypi"),
             PublishOpt::TEST_REPOSITORY_URL.to_string(),
@@ -240,13 +590,14 @@ fn complete_registry(opt: &PublishOpt) -> Result<Registry> {
             "Failed to get registry {} in .pypirc. \
                 Note: Your index didn't start with http:// or https://, \
                 which is required for non-pypirc indices.",
-            opt.repository
+            repository
         );
     };
-    let (username, password) = resolve_pypi_cred(opt, &pypirc, registry_name);
+    let (username, password, trusted_publishing) =
+        resolve_pypi_cred(opt, pypirc, registry_name, &registry_url, use_global_cred)?;
     let registry = Registry::new(username, password, registry_url);
 
-    Ok(registry)
+    Ok((registry, trusted_publishing))
 }
 
 /// Port of pip's `canonicalize_name`
@@ -258,6 +609,167 @@ fn canonicalize_name(name: &str) -> String {
         .to_lowercase()
 }
 
+/// The sha256 of the files of a project on a simple index by filename, `None` if the index
+/// doesn't list a sha256 for the file
+type ExistingFiles = HashMap<String, Option<String>>;
+
+/// Lists the files of the project on the simple index, preferring the PEP 691 json api over the
+/// PEP 503 html pages. A project that doesn't exist yet has no files.
+///
+/// `authorization` is only sent to indexes on the same host as the repository
+fn fetch_existing_files(
+    agent: &ureq::Agent,
+    index_url: &str,
+    name: &str,
+    authorization: Option<&str>,
+) -> Result<ExistingFiles> {
+    let url = format!("{}/{}/", index_url.trim_end_matches('/'), name);
+    let mut request = agent.get(&url).set(
+        "Accept",
+        "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html;q=0.2, text/html;q=0.1",
+    );
+    if let Some(authorization) = authorization {
+        request = request.set("Authorization", authorization);
+    }
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(HashMap::new()),
+        Err(ureq::Error::Status(status, _)) => bail!("{} returned status {}", url, status),
+        Err(err) => return Err(err).with_context(|| format!("Failed to connect to {}", url)),
+    };
+
+    let mut existing = HashMap::new();
+    if response.content_type().contains("json") {
+        let project: serde_json::Value = serde_json::from_str(&response.into_string()?)
+            .with_context(|| format!("Invalid json from {}", url))?;
+        for file in project["files"].as_array().into_iter().flatten() {
+            if let Some(filename) = file["filename"].as_str() {
+                let sha256 = file["hashes"]["sha256"].as_str().map(ToString::to_string);
+                existing.insert(filename.to_string(), sha256);
+            }
+        }
+    } else {
+        let html = response.into_string()?;
+        let anchor = Regex::new(r#"<a\s[^>]*href="([^"]*)"[^>]*>([^<]*)</a>"#).unwrap();
+        for captures in anchor.captures_iter(&html) {
+            let sha256 = captures[1]
+                .split_once("#sha256=")
+                .map(|(_, sha256)| sha256.to_string());
+            existing.insert(captures[2].trim().to_string(), sha256);
+        }
+    }
+    Ok(existing)
+}
+
+/// Whether to skip the file because the index already has it with the same sha256. The same
+/// filename with a different sha256 is an error, since the index won't accept the upload.
+///
+/// Without a sha256 from the index, the upload decides
+fn is_existing_file(filename: &str, sha256: &str, existing: &ExistingFiles) -> Result<bool> {
+    match existing.get(filename) {
+        Some(Some(existing_sha256)) if existing_sha256.eq_ignore_ascii_case(sha256) => Ok(true),
+        Some(Some(existing_sha256)) => bail!(
+            "{} already exists on the index with a different sha256 ({} instead of {})",
+            filename,
+            existing_sha256,
+            sha256
+        ),
+        Some(None) | None => Ok(false),
+    }
+}
+
+/// Checks the simple index for the files for `--skip-existing` and returns the ones that still
+/// need to be uploaded
+fn filter_existing_files(
+    registry: &Registry,
+    publish: &PublishOpt,
+    items: &[PathBuf],
+) -> Result<Vec<PathBuf>> {
+    let index_url = match &publish.index_url {
+        Some(index_url) => index_url.clone(),
+        None => format!("{}/simple/", index_base_url(&registry.url)?),
+    };
+    // Don't send the credentials for the upload to a different host, e.g. from
+    // upload.pypi.org to pypi.org
+    let same_host = index_url
+        .split('/')
+        .take(3)
+        .eq(registry.url.split('/').take(3));
+    let authorization = format!(
+        "Basic {}",
+        base64::encode(format!("{}:{}", registry.username, registry.password))
+    );
+    let agent = http_agent()?;
+
+    let mut projects: HashMap<String, Option<ExistingFiles>> = HashMap::new();
+    let mut missing = Vec::new();
+    for item in items {
+        let dist = python_pkginfo::Distribution::new(item)
+            .map_err(|err| UploadError::PkgInfoError(item.to_owned(), err))?;
+        let name = canonicalize_name(&dist.metadata().name);
+        let existing = projects.entry(name.clone()).or_insert_with(|| {
+            let authorization = Some(authorization.as_str()).filter(|_| same_host);
+            match fetch_existing_files(&agent, &index_url, &name, authorization) {
+                Ok(existing) => Some(existing),
+                Err(err) => {
+                    eprintln!(
+                        "⚠️  Warning: Failed to check the index for existing files of {}, \
+                        relying on the response of the upload instead: {:#}",
+                        name, err
+                    );
+                    None
+                }
+            }
+        });
+        let filename = item.file_name().unwrap().to_string_lossy();
+        let skip = match existing {
+            Some(existing) => is_existing_file(&filename, &hash_file(item)?, existing)?,
+            None => false,
+        };
+        if skip {
+            println!(
+                "⚠️ Note: Skipping {:?} because it already exists with the same sha256",
+                filename
+            );
+        } else {
+            missing.push(item.clone());
+        }
+    }
+    Ok(missing)
+}
+
+/// Creates the http agent for the registry, which respects the proxy environment variables
+fn http_agent() -> Result<ureq::Agent, UploadError> {
+    let http_proxy = env::var("HTTPS_PROXY")
+        .or_else(|_| env::var("https_proxy"))
+        .or_else(|_| env::var("HTTP_PROXY"))
+        .or_else(|_| env::var("http_proxy"));
+
+    #[cfg(not(feature = "native-tls"))]
+    let agent = {
+        let mut builder = ureq::builder();
+        if let Ok(proxy) = http_proxy {
+            let proxy = ureq::Proxy::new(proxy)?;
+            builder = builder.proxy(proxy);
+        };
+        builder.build()
+    };
+
+    #[cfg(feature = "native-tls")]
+    let agent = {
+        use std::sync::Arc;
+        let mut builder =
+            ureq::builder().tls_connector(Arc::new(native_tls_crate::TlsConnector::new()?));
+        if let Ok(proxy) = http_proxy {
+            let proxy = ureq::Proxy::new(proxy)?;
+            builder = builder.proxy(proxy);
+        };
+        builder.build()
+    };
+
+    Ok(agent)
+}
+
 /// Uploads a single wheel to the registry
 pub fn upload(registry: &Registry, wheel_path: &Path) -> Result<(), UploadError> {
     let hash_hex = hash_file(&wheel_path)?;
@@ -337,32 +849,7 @@ pub fn upload(registry: &Registry, wheel_path: &Path) -> Result<(), UploadError>
 
     let encoded = base64::encode(&format!("{}:{}", registry.username, registry.password));
 
-    let http_proxy = env::var("HTTPS_PROXY")
-        .or_else(|_| env::var("https_proxy"))
-        .or_else(|_| env::var("HTTP_PROXY"))
-        .or_else(|_| env::var("http_proxy"));
-
-    #[cfg(not(feature = "native-tls"))]
-    let agent = {
-        let mut builder = ureq::builder();
-        if let Ok(proxy) = http_proxy {
-            let proxy = ureq::Proxy::new(proxy)?;
-            builder = builder.proxy(proxy);
-        };
-        builder.build()
-    };
-
-    #[cfg(feature = "native-tls")]
-    let agent = {
-        use std::sync::Arc;
-        let mut builder =
-            ureq::builder().tls_connector(Arc::new(native_tls_crate::TlsConnector::new()?));
-        if let Ok(proxy) = http_proxy {
-            let proxy = ureq::Proxy::new(proxy)?;
-            builder = builder.proxy(proxy);
-        };
-        builder.build()
-    };
+    let agent = http_agent()?;
 
     let response = agent
         .post(registry.url.as_str())
@@ -415,68 +902,260 @@ pub fn upload(registry: &Registry, wheel_path: &Path) -> Result<(), UploadError>
                 }
             }
         }
-        Err(err) => Err(UploadError::UreqError(err)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The delay before the first retry of a failed upload, which doubles with every further retry
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Whether an upload failed for a reason that is likely temporary, i.e. a connection problem,
+/// a server error or rate limiting
+fn is_retryable(err: &UploadError) -> bool {
+    match err {
+        UploadError::UreqError(err) => matches!(**err, ureq::Error::Transport(_)),
+        UploadError::StatusCodeError(status, _) => status.starts_with('5') || status == "429",
+        _ => false,
+    }
+}
+
+/// Uploads a single file, retrying temporary failures with exponential backoff
+fn upload_with_retries(
+    registry: &Registry,
+    path: &Path,
+    retries: u32,
+    delay: Duration,
+) -> Result<(), UploadError> {
+    let mut attempt = 0;
+    loop {
+        match upload(registry, path) {
+            Err(err) if attempt < retries && is_retryable(&err) => {
+                let wait = delay * 2u32.saturating_pow(attempt);
+                attempt += 1;
+                eprintln!(
+                    "⚠️  Warning: Uploading {:?} failed, retrying in {:?} ({}/{}): {:#}",
+                    path.file_name().unwrap_or(path.as_os_str()),
+                    wait,
+                    attempt,
+                    retries,
+                    anyhow::Error::from(err)
+                );
+                thread::sleep(wait);
+            }
+            result => return result,
+        }
     }
 }
 
+/// Uploads the files with up to `concurrency` uploads at the same time and returns the result for
+/// each file in the same order
+///
+/// No new uploads are started once the registry rejected the credentials, those files have no
+/// result
+fn upload_parallel(
+    registry: &Registry,
+    items: &[PathBuf],
+    concurrency: usize,
+    retries: u32,
+    delay: Duration,
+) -> Vec<Option<Result<(), UploadError>>> {
+    let queue = Arc::new(Mutex::new(
+        items.iter().cloned().enumerate().collect::<VecDeque<_>>(),
+    ));
+    let registry = Arc::new(registry.clone());
+    let rejected = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..concurrency.clamp(1, items.len().max(1)) {
+        let queue = Arc::clone(&queue);
+        let registry = Arc::clone(&registry);
+        let rejected = Arc::clone(&rejected);
+        let sender = sender.clone();
+        thread::spawn(move || {
+            while !rejected.load(Ordering::SeqCst) {
+                let next = queue.lock().unwrap().pop_front();
+                let (index, path) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let result = upload_with_retries(&registry, &path, retries, delay);
+                if let Err(UploadError::AuthenticationError) = result {
+                    rejected.store(true, Ordering::SeqCst);
+                }
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    // The receiver is done once all workers dropped their sender
+    drop(sender);
+
+    let mut results: Vec<_> = items.iter().map(|_| None).collect();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    results
+}
+
 /// Handles authentication/keyring integration and retrying of the publish subcommand
+///
+/// With multiple repositories, the credentials for all of them are resolved before uploading
+/// anything, and a failing repository doesn't stop the uploads to the others
 pub fn upload_ui(items: &[PathBuf], publish: &PublishOpt) -> Result<()> {
-    let registry = complete_registry(publish)?;
+    if publish.check {
+        check_dists(items)?;
+    }
+
+    let pypirc = load_pypirc();
+    let repositories = repositories(publish, &pypirc)?;
+    if repositories.len() > 1 && publish.index_url.is_some() {
+        bail!("--index-url can only be used when uploading to a single repository");
+    }
+    let use_global_cred =
+        global_cred_repositories(&repositories, &pypirc, has_global_cred(publish))?;
+    let mut registries = repositories
+        .iter()
+        .zip(use_global_cred)
+        .map(|(repository, use_global_cred)| {
+            complete_registry(publish, &pypirc, repository, use_global_cred)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if registries.len() == 1 {
+        let (registry, trusted_publishing) = registries.remove(0);
+        return upload_to_registry(items, publish, registry, trusted_publishing);
+    }
+
+    let mut results = Vec::new();
+    for (repository, (registry, trusted_publishing)) in repositories.iter().zip(registries) {
+        println!("🌐 Uploading to {} ({})", repository, registry.url);
+        let result = upload_to_registry(items, publish, registry, trusted_publishing);
+        if let Err(err) = &result {
+            eprintln!("💥 Uploading to {} failed: {:#}", repository, err);
+        }
+        results.push((repository, result));
+    }
+
+    println!("📋 Repository summary:");
+    let mut failed = Vec::new();
+    for (repository, result) in &results {
+        match result {
+            Ok(()) => println!("    uploaded  {}", repository),
+            Err(err) => {
+                println!("    failed    {}: {}", repository, err);
+                failed.push(repository.as_str());
+            }
+        }
+    }
+    if !failed.is_empty() {
+        bail!(
+            "Failed to upload to {} of {} repositories: {}",
+            failed.len(),
+            results.len(),
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Uploads the files to a single registry, removing a rejected password from the keyring or
+/// storing an accepted one
+fn upload_to_registry(
+    items: &[PathBuf],
+    publish: &PublishOpt,
+    registry: Registry,
+    _trusted_publishing: bool,
+) -> Result<()> {
+    let items = if publish.skip_existing {
+        filter_existing_files(&registry, publish, items)?
+    } else {
+        items.to_vec()
+    };
+    if items.is_empty() {
+        println!("✨ All packages already exist on the index");
+        return Ok(());
+    }
 
     println!("🚀 Uploading {} packages", items.len());
 
-    for i in items {
-        let upload_result = upload(&registry, i);
+    let results = upload_parallel(
+        &registry,
+        &items,
+        publish.concurrency,
+        publish.retries,
+        RETRY_DELAY,
+    );
 
-        match upload_result {
-            Ok(()) => (),
-            Err(UploadError::AuthenticationError) => {
-                println!("⛔ Username and/or password are wrong");
+    if results
+        .iter()
+        .any(|result| matches!(result, Some(Err(UploadError::AuthenticationError))))
+    {
+        println!("⛔ Username and/or password are wrong");
 
-                #[cfg(feature = "keyring")]
-                {
-                    // Delete the wrong password from the keyring
-                    let old_username = registry.username;
-                    let keyring = keyring::Entry::new(env!("CARGO_PKG_NAME"), &old_username);
-                    match keyring.delete_password() {
-                        Ok(()) => {
-                            println!("🔑 Removed wrong password from keyring")
-                        }
-                        Err(keyring::Error::NoEntry)
-                        | Err(keyring::Error::NoStorageAccess(_))
-                        | Err(keyring::Error::PlatformFailure(_)) => {}
-                        Err(err) => {
-                            eprintln!("⚠️ Warning: Failed to remove password from keyring: {}", err)
-                        }
-                    }
+        #[cfg(feature = "keyring")]
+        if !_trusted_publishing {
+            // Delete the wrong password from the keyring
+            let old_username = registry.username;
+            let keyring = keyring::Entry::new(env!("CARGO_PKG_NAME"), &old_username);
+            match keyring.delete_password() {
+                Ok(()) => {
+                    println!("🔑 Removed wrong password from keyring")
                 }
+                Err(keyring::Error::NoEntry)
+                | Err(keyring::Error::NoStorageAccess(_))
+                | Err(keyring::Error::PlatformFailure(_)) => {}
+                Err(err) => {
+                    eprintln!(
+                        "⚠️ Warning: Failed to remove password from keyring: {}",
+                        err
+                    )
+                }
+            }
+        }
 
-                bail!("Username and/or password are wrong");
+        bail!("Username and/or password are wrong");
+    }
+
+    println!("📋 Upload summary:");
+    let mut failed = 0;
+    for (item, result) in items.iter().zip(results) {
+        let filename = item.file_name().unwrap_or(item.as_os_str());
+        match result {
+            Some(Ok(())) => println!("    uploaded  {:?}", filename),
+            Some(Err(UploadError::FileExistsError(_))) if publish.skip_existing => {
+                println!(
+                    "    skipped   {:?} because it appears to already exist",
+                    filename
+                )
             }
-            Err(err) => {
-                let filename = i.file_name().unwrap_or(i.as_os_str());
-                if let UploadError::FileExistsError(_) = err {
-                    if publish.skip_existing {
-                        println!(
-                            "⚠️ Note: Skipping {:?} because it appears to already exist",
-                            filename
-                        );
-                        continue;
-                    }
-                }
-                let filesize = fs::metadata(&i)
+            Some(Err(err)) => {
+                failed += 1;
+                let filesize = fs::metadata(item)
                     .map(|x| ByteSize(x.len()).to_string())
-                    .unwrap_or_else(|e| format!("Failed to get the filesize of {:?}: {}", &i, e));
-                return Err(err)
-                    .context(format!("💥 Failed to upload {:?} ({})", filename, filesize));
+                    .unwrap_or_else(|e| {
+                        format!("Failed to get the filesize of {:?}: {}", &item, e)
+                    });
+                println!(
+                    "    failed    {:?} ({}): {:#}",
+                    filename,
+                    filesize,
+                    anyhow::Error::from(err)
+                );
+            }
+            None => {
+                failed += 1;
+                println!("    failed    {:?}: not uploaded", filename);
             }
         }
     }
+    if failed > 0 {
+        bail!("💥 Failed to upload {} of {} packages", failed, items.len());
+    }
 
     println!("✨ Packages uploaded successfully");
 
+    // The short-lived token of trusted publishing is useless later
     #[cfg(feature = "keyring")]
-    {
+    if !_trusted_publishing {
         // We know the password is correct, so we can save it in the keyring
         let username = registry.username.clone();
         let keyring = keyring::Entry::new(env!("CARGO_PKG_NAME"), &username);
@@ -496,3 +1175,370 @@ pub fn upload_ui(items: &[PathBuf], publish: &PublishOpt) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        complete_registry, fetch_existing_files, get_oidc_token, global_cred_repositories,
+        index_base_url, is_existing_file, mint_token, repositories, resolve_trusted_publishing,
+        upload_parallel, OidcEnv, PublishOpt, Registry, UploadError,
+    };
+    use clap::Parser;
+    use configparser::ini::Ini;
+    use fs_err::File;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::path::{Path, PathBuf};
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    /// Answers one request with each of the status codes and json (or html) bodies and returns
+    /// the request lines and bodies it received. Status 0 closes the connection without a response.
+    fn mock_index(responses: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut content_length = 0;
+                let mut chunked = false;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                            chunked = value.trim() == "chunked";
+                        }
+                    }
+                }
+                let mut content = vec![0; content_length];
+                reader.read_exact(&mut content).unwrap();
+                while chunked {
+                    let mut size = String::new();
+                    reader.read_line(&mut size).unwrap();
+                    let size = usize::from_str_radix(size.trim(), 16).unwrap();
+                    let mut chunk = vec![0; size + 2];
+                    reader.read_exact(&mut chunk).unwrap();
+                    content.extend_from_slice(&chunk[..size]);
+                    chunked = size > 0;
+                }
+                request.push_str(&String::from_utf8_lossy(&content));
+                requests.push(request);
+                if status == 0 {
+                    continue;
+                }
+                let content_type = if body.starts_with('<') {
+                    "text/html"
+                } else {
+                    "application/vnd.pypi.simple.v1+json"
+                };
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} Mock\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn publish_opt(args: &[&str]) -> PublishOpt {
+        PublishOpt::try_parse_from(["upload"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn test_index_base_url() {
+        assert_eq!(
+            index_base_url("https://upload.pypi.org/legacy/").unwrap(),
+            "https://pypi.org"
+        );
+        assert_eq!(
+            index_base_url("https://test.pypi.org/legacy/").unwrap(),
+            "https://test.pypi.org"
+        );
+        assert!(index_base_url("pypi").is_err());
+    }
+
+    #[test]
+    fn test_trusted_publishing_with_github_actions() {
+        let (url, server) = mock_index(vec![
+            (200, r#"{"audience": "pypi"}"#),
+            (200, r#"{"count": 1, "value": "oidc-token"}"#),
+            (200, r#"{"success": true, "token": "pypi-short-lived"}"#),
+        ]);
+        let oidc = OidcEnv {
+            github_request: Some((
+                format!("{}/github?api-version=2.0", url),
+                "request-token".to_string(),
+            )),
+            ..Default::default()
+        };
+        let agent = ureq::agent();
+        let oidc_token = get_oidc_token(&agent, &url, &oidc).unwrap();
+        assert_eq!(oidc_token, "oidc-token");
+        let token = mint_token(&agent, &url, &oidc_token).unwrap();
+        assert_eq!(token, "pypi-short-lived");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /_/oidc/audience "));
+        assert!(requests[1].starts_with("GET /github?api-version=2.0&audience=pypi "));
+        assert!(requests[2].starts_with("POST /_/oidc/mint-token "));
+        assert!(requests[2].ends_with(r#"{"token":"oidc-token"}"#));
+    }
+
+    #[test]
+    fn test_trusted_publishing_errors() {
+        let (url, server) = mock_index(vec![(
+            422,
+            r#"{"message": "Token request failed", "errors": [{"code": "invalid-publisher", "description": "valid token, but no corresponding publisher"}]}"#,
+        )]);
+        let err = mint_token(&ureq::agent(), &url, "oidc-token").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}/_/oidc/mint-token returned status 422: Token request failed\n  \
+                * invalid-publisher: valid token, but no corresponding publisher",
+                url
+            )
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_trusted_publishing_fallback() {
+        let oidc = OidcEnv {
+            token: Some("oidc-token\n".to_string()),
+            ..Default::default()
+        };
+        let failure = r#"{"message": "Token request failed", "errors": []}"#;
+        let (url, server) = mock_index(vec![(422, failure), (422, failure)]);
+        let registry_url = format!("{}/legacy/", url);
+        let agent = ureq::agent();
+
+        // Falls back to the other credentials by default
+        let token =
+            resolve_trusted_publishing(&publish_opt(&[]), &agent, &registry_url, &oidc).unwrap();
+        assert_eq!(token, None);
+        // But not if trusted publishing is required
+        let required = publish_opt(&["--trusted-publishing"]);
+        let err = resolve_trusted_publishing(&required, &agent, &registry_url, &oidc).unwrap_err();
+        assert!(format!("{:#}", err).contains("Token request failed"));
+        server.join().unwrap();
+
+        // Without an identity token there's nothing to try
+        let none = OidcEnv::default();
+        let token = resolve_trusted_publishing(&publish_opt(&[]), &agent, &registry_url, &none);
+        assert_eq!(token.unwrap(), None);
+        assert!(resolve_trusted_publishing(&required, &agent, &registry_url, &none).is_err());
+    }
+
+    #[test]
+    fn test_repositories() {
+        let mut pypirc = Ini::new();
+        pypirc.set_multiline(true);
+        pypirc
+            .read(
+                r#"
+[distutils]
+index-servers =
+    internal
+    pypi
+
+[internal]
+repository = https://pypi.example.com/legacy/
+username = ci
+password = secret
+"#
+                .to_string(),
+            )
+            .unwrap();
+
+        let opt = publish_opt(&["-r", "internal", "-r", "pypi", "--repository", "internal"]);
+        assert_eq!(repositories(&opt, &pypirc).unwrap(), ["internal", "pypi"]);
+        let opt = publish_opt(&["--all-repositories"]);
+        assert_eq!(repositories(&opt, &pypirc).unwrap(), ["internal", "pypi"]);
+        assert!(repositories(&opt, &Ini::new()).is_err());
+        let opt = publish_opt(&["--repository-url", "https://upload.example.com/"]);
+        assert_eq!(
+            repositories(&opt, &pypirc).unwrap(),
+            ["https://upload.example.com/"]
+        );
+        assert!(
+            PublishOpt::try_parse_from(["upload", "--all-repositories", "-r", "pypi"]).is_err()
+        );
+
+        // Each repository uses its own section of the config file
+        let (registry, trusted_publishing) =
+            complete_registry(&publish_opt(&[]), &pypirc, "internal", true).unwrap();
+        assert_eq!(
+            registry,
+            Registry::new(
+                "ci".to_string(),
+                "secret".to_string(),
+                "https://pypi.example.com/legacy/".to_string()
+            )
+        );
+        assert!(!trusted_publishing);
+        assert!(complete_registry(&publish_opt(&[]), &pypirc, "missing", true).is_err());
+
+        // A global token or username only goes to the repository without a pypirc section
+        let names = ["internal".to_string(), "pypi".to_string()];
+        assert_eq!(
+            global_cred_repositories(&names, &pypirc, true).unwrap(),
+            [false, true]
+        );
+        assert_eq!(
+            global_cred_repositories(&names[..1], &pypirc, true).unwrap(),
+            [true]
+        );
+        let opt = publish_opt(&["-u", "global", "-p", "global-secret"]);
+        let (registry, _) = complete_registry(&opt, &pypirc, "internal", false).unwrap();
+        assert_eq!(registry.username, "ci");
+        assert_eq!(registry.password, "secret");
+        let (registry, _) = complete_registry(&opt, &pypirc, "pypi", true).unwrap();
+        assert_eq!(registry.username, "global");
+        assert_eq!(registry.password, "global-secret");
+        // It's ambiguous which repository they belong to if several lack a pypirc section
+        let names = ["pypi".to_string(), "testpypi".to_string()];
+        assert!(global_cred_repositories(&names, &pypirc, true).is_err());
+        assert_eq!(
+            global_cred_repositories(&names, &pypirc, false).unwrap(),
+            [true, true]
+        );
+    }
+
+    #[test]
+    fn test_fetch_existing_files() {
+        let json = r#"{"meta": {"api-version": "1.0"}, "name": "foo", "files": [
+            {"filename": "foo-1.0.tar.gz", "url": "foo-1.0.tar.gz", "hashes": {"sha256": "abc"}},
+            {"filename": "foo-1.0-py3-none-any.whl", "url": "foo-1.0-py3-none-any.whl", "hashes": {}}
+        ]}"#;
+        let html = r#"<!DOCTYPE html><html><body>
+            <a href="../../packages/foo-1.0.tar.gz#sha256=abc">foo-1.0.tar.gz</a><br/>
+            <a href="../../packages/foo-1.0-py3-none-any.whl" data-requires-python="&gt;=3.7">foo-1.0-py3-none-any.whl</a><br/>
+            </body></html>"#;
+        let (url, server) = mock_index(vec![(200, json), (200, html), (404, "")]);
+        let expected: HashMap<String, Option<String>> = vec![
+            ("foo-1.0.tar.gz".to_string(), Some("abc".to_string())),
+            ("foo-1.0-py3-none-any.whl".to_string(), None),
+        ]
+        .into_iter()
+        .collect();
+        let agent = ureq::agent();
+        let index_url = format!("{}/simple/", url);
+        assert_eq!(
+            fetch_existing_files(&agent, &index_url, "foo", Some("Basic Zm9vOmJhcg==")).unwrap(),
+            expected
+        );
+        assert_eq!(
+            fetch_existing_files(&agent, &index_url, "foo", None).unwrap(),
+            expected
+        );
+        assert!(fetch_existing_files(&agent, &index_url, "bar", None)
+            .unwrap()
+            .is_empty());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /simple/foo/ "));
+        assert!(requests[2].starts_with("GET /simple/bar/ "));
+    }
+
+    #[test]
+    fn test_is_existing_file() {
+        let existing: HashMap<String, Option<String>> = vec![
+            ("foo-1.0.tar.gz".to_string(), Some("abc".to_string())),
+            ("foo-1.0-py3-none-any.whl".to_string(), None),
+        ]
+        .into_iter()
+        .collect();
+        assert!(is_existing_file("foo-1.0.tar.gz", "ABC", &existing).unwrap());
+        let err = is_existing_file("foo-1.0.tar.gz", "def", &existing).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "foo-1.0.tar.gz already exists on the index with a different sha256 (abc instead of def)"
+        );
+        // Without a hash, the upload has to find out
+        assert!(!is_existing_file("foo-1.0-py3-none-any.whl", "abc", &existing).unwrap());
+        assert!(!is_existing_file("foo-1.1.tar.gz", "abc", &existing).unwrap());
+    }
+
+    /// Writes a wheel with just enough metadata to be uploaded
+    fn write_wheel(dir: &Path, version: &str) -> PathBuf {
+        let path = dir.join(format!("foo-{}-py3-none-any.whl", version));
+        let mut zip = zip::ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file(
+            format!("foo-{}.dist-info/METADATA", version),
+            zip::write::FileOptions::default(),
+        )
+        .unwrap();
+        write!(
+            zip,
+            "Metadata-Version: 2.1\nName: foo\nVersion: {}\n",
+            version
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_upload_parallel_retries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let items: Vec<PathBuf> = vec!["1.0.0", "1.0.1", "1.0.2"]
+            .into_iter()
+            .map(|version| write_wheel(temp_dir.path(), version))
+            .collect();
+        let delay = Duration::from_millis(1);
+
+        // A server error and a reset connection are retried
+        let (url, handle) = mock_index(vec![(503, ""), (0, ""), (200, ""), (200, ""), (200, "")]);
+        let registry = Registry::new("user".to_string(), "password".to_string(), url);
+        let results = upload_parallel(&registry, &items, 2, 2, delay);
+        assert!(results.iter().all(|result| matches!(result, Some(Ok(())))));
+        let requests = handle.join().unwrap();
+        assert_eq!(requests.len(), 5);
+        assert!(requests
+            .iter()
+            .all(|request| request.contains("name=\"version\"")));
+
+        // Other client errors are final, while server errors are retried until the retries run out
+        let (url, handle) = mock_index(vec![(502, ""), (502, ""), (400, "Invalid version")]);
+        let registry = Registry::new("user".to_string(), "password".to_string(), url);
+        let results = upload_parallel(&registry, &items[..2], 1, 1, delay);
+        handle.join().unwrap();
+        match &results[..] {
+            [Some(Err(UploadError::StatusCodeError(first, _))), Some(Err(UploadError::StatusCodeError(second, text)))] =>
+            {
+                assert_eq!(first, "502");
+                assert_eq!(second, "400");
+                assert_eq!(text, "Invalid version");
+            }
+            _ => panic!("Unexpected results {:?}", results),
+        }
+
+        // No further uploads start once the credentials are rejected
+        let (url, handle) = mock_index(vec![(403, "")]);
+        let registry = Registry::new("user".to_string(), "password".to_string(), url);
+        let results = upload_parallel(&registry, &items, 1, 3, delay);
+        handle.join().unwrap();
+        assert!(matches!(
+            results[..],
+            [Some(Err(UploadError::AuthenticationError)), None, None]
+        ));
+    }
+}
@@ -0,0 +1,13 @@
+use pyo3::prelude::*;
+
+#[pyfunction]
+fn get_21() -> usize {
+    21
+}
+
+#[pymodule]
+fn pyo3_lib_with_bin(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(get_21))?;
+
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use pyo3::prelude::*;
+
+#[pyfunction]
+fn encode(text: &str) -> Vec<u8> {
+    text.as_bytes().to_vec()
+}
+
+#[pymodule]
+fn _codec(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(encode))?;
+
+    Ok(())
+}
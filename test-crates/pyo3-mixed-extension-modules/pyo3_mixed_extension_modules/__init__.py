@@ -0,0 +1,6 @@
+from ._simd import double
+from .pyo3_mixed_extension_modules import get_21
+
+
+def get_42() -> int:
+    return double(get_21())
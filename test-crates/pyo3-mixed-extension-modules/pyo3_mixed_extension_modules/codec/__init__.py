@@ -0,0 +1 @@
+from ._codec import encode
@@ -0,0 +1,17 @@
+use pyo3::prelude::*;
+
+#[pyfunction]
+fn get_21() -> usize {
+    if cfg!(feature = "fast") {
+        21
+    } else {
+        (0..21).count()
+    }
+}
+
+#[pymodule]
+fn pyo3_mixed_extension_modules(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_wrapped(wrap_pyfunction!(get_21))?;
+
+    Ok(())
+}
@@ -1,12 +1,13 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use flate2::read::GzDecoder;
 use maturin::{BuildOptions, CargoOptions};
 use pretty_assertions::assert_eq;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 use std::io::Read;
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use tar::Archive;
 
 /// Tries to compile a sample crate (pyo3-pure) for musl,
@@ -15,12 +16,10 @@ use tar::Archive;
 /// The bool in the Ok() response says whether the test was actually run
 #[cfg(target_os = "linux")]
 pub fn test_musl() -> Result<bool> {
-    use anyhow::bail;
     use fs_err as fs;
     use fs_err::File;
     use goblin::elf::Elf;
     use std::io::ErrorKind;
-    use std::process::Command;
 
     let get_target_list = Command::new("rustup")
         .args(&["target", "list", "--installed"])
@@ -114,7 +113,7 @@ pub fn test_workspace_cargo_lock() -> Result<()> {
 pub fn test_source_distribution(
     package: impl AsRef<Path>,
     expected_files: Vec<&str>,
-    expected_cargo_toml: Option<(&Path, &str)>,
+    expected_cargo_tomls: &[(&Path, &str)],
     unique_name: &str,
 ) -> Result<()> {
     let manifest_path = package.as_ref().join("Cargo.toml");
@@ -143,17 +142,16 @@ pub fn test_source_distribution(
     let mut archive = Archive::new(tar);
     let mut files = BTreeSet::new();
     let mut file_count = 0;
-    let mut cargo_toml = None;
+    let mut cargo_tomls = HashMap::new();
     for entry in archive.entries()? {
         let mut entry = entry?;
         files.insert(format!("{}", entry.path()?.display()));
         file_count += 1;
-        if let Some(cargo_toml_path) = expected_cargo_toml.as_ref().map(|(p, _)| *p) {
-            if entry.path()? == cargo_toml_path {
-                let mut contents = String::new();
-                entry.read_to_string(&mut contents)?;
-                cargo_toml = Some(contents);
-            }
+        let path = entry.path()?.into_owned();
+        if expected_cargo_tomls.iter().any(|(p, _)| *p == path) {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            cargo_tomls.insert(path, contents);
         }
     }
     assert_eq!(
@@ -162,8 +160,9 @@ pub fn test_source_distribution(
     );
     assert_eq!(file_count, files.len(), "duplicated files found in sdist");
 
-    if let Some((cargo_toml_path, expected)) = expected_cargo_toml {
-        let cargo_toml = cargo_toml
+    for (cargo_toml_path, expected) in expected_cargo_tomls {
+        let cargo_toml = cargo_tomls
+            .get(*cargo_toml_path)
             .with_context(|| format!("{} not found in sdist", cargo_toml_path.display()))?;
         assert_eq!(cargo_toml, expected);
     }
@@ -224,3 +223,120 @@ pub fn abi3_python_interpreter_args() -> Result<()> {
 
     Ok(())
 }
+
+/// `maturin inspect` must only print the build plan to stdout, so it can be parsed
+pub fn test_inspect_stdout(package: impl AsRef<Path>) -> Result<()> {
+    let manifest_path = package.as_ref().join("Cargo.toml");
+    for format in ["json", "toml"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_maturin"))
+            .args(["inspect", "--format", format, "--manifest-path"])
+            .arg(&manifest_path)
+            .output()?;
+        if !output.status.success() {
+            bail!(
+                "maturin inspect failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        let stdout = String::from_utf8(output.stdout)?;
+        let plan: serde_json::Value = match format {
+            "json" => serde_json::from_str(&stdout)
+                .with_context(|| format!("stdout is not valid json:\n{}", stdout))?,
+            _ => toml_edit::easy::from_str(&stdout)
+                .with_context(|| format!("stdout is not valid toml:\n{}", stdout))?,
+        };
+        assert_eq!(
+            plan["manifest_path"].as_str().map(Path::new),
+            Some(manifest_path.canonicalize()?.as_path())
+        );
+        assert!(plan["bridge"]["bindings"].is_string());
+        assert!(plan["interpreters"].is_array());
+    }
+    Ok(())
+}
+
+/// The workspace members of `[[tool.maturin.extension-modules]]` are built without the
+/// `--features` of the main crate, and their modules go into the python package next to it
+pub fn test_extension_modules() -> Result<()> {
+    // The first arg gets ignored
+    let options = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/pyo3-mixed-extension-modules/Cargo.toml",
+        "--features",
+        "fast",
+        "--interpreter",
+        "python3",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/test_extension_modules",
+        "--out",
+        "test-crates/wheels/test_extension_modules",
+    ])?;
+    let build_context = options.into_build_context(false, cfg!(feature = "faster-tests"), false)?;
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+
+    let archive = zip::ZipArchive::new(fs_err::File::open(&wheels[0].0)?)?;
+    let names: Vec<&str> = archive.file_names().collect();
+    for module in [
+        "pyo3_mixed_extension_modules/pyo3_mixed_extension_modules",
+        "pyo3_mixed_extension_modules/_simd",
+        "pyo3_mixed_extension_modules/codec/_codec",
+    ] {
+        assert!(
+            names
+                .iter()
+                .any(|name| name.starts_with(&format!("{}.", module))
+                    && (name.ends_with(".so") || name.ends_with(".pyd"))),
+            "{} not found in {:?}",
+            module,
+            names
+        );
+    }
+    Ok(())
+}
+
+/// With `include-bins`, the binaries of a pyo3 crate go into the scripts of the wheel that
+/// contains its native module
+pub fn test_include_bins() -> Result<()> {
+    // The first arg gets ignored
+    let options = BuildOptions::try_parse_from([
+        "build",
+        "--manifest-path",
+        "test-crates/pyo3-lib-with-bin/Cargo.toml",
+        "--interpreter",
+        "python3",
+        "--quiet",
+        "--target-dir",
+        "test-crates/targets/test_include_bins",
+        "--out",
+        "test-crates/wheels/test_include_bins",
+    ])?;
+    let build_context = options.into_build_context(false, cfg!(feature = "faster-tests"), false)?;
+    let wheels = build_context.build_wheels()?;
+    assert_eq!(wheels.len(), 1);
+
+    let mut archive = zip::ZipArchive::new(fs_err::File::open(&wheels[0].0)?)?;
+    let names: Vec<String> = archive.file_names().map(ToString::to_string).collect();
+    assert!(
+        names.iter().any(|name| {
+            name.starts_with("pyo3_lib_with_bin/pyo3_lib_with_bin.")
+                && (name.ends_with(".so") || name.ends_with(".pyd"))
+        }),
+        "native module not found in {:?}",
+        names
+    );
+    let script = if cfg!(windows) {
+        "pyo3_lib_with_bin-0.1.0.data/scripts/lib-with-bin-cli.exe"
+    } else {
+        "pyo3_lib_with_bin-0.1.0.data/scripts/lib-with-bin-cli"
+    };
+    let bin = archive
+        .by_name(script)
+        .with_context(|| format!("{} not found in {:?}", script, names))?;
+    if cfg!(unix) {
+        assert_eq!(bin.unix_mode().map(|mode| mode & 0o777), Some(0o755));
+    }
+    Ok(())
+}
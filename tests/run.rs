@@ -419,7 +419,7 @@ fn workspace_members_non_local_dep_sdist() {
             "pyo3_pure-0.1.0+abc123de/tests/test_pyo3_pure.py",
             "pyo3_pure-0.1.0+abc123de/tox.ini",
         ],
-        Some((Path::new("pyo3_pure-0.1.0+abc123de/Cargo.toml"), cargo_toml)),
+        &[(Path::new("pyo3_pure-0.1.0+abc123de/Cargo.toml"), cargo_toml)],
         "sdist-workspace-members-non-local-dep",
     ))
 }
@@ -439,7 +439,7 @@ fn lib_with_path_dep_sdist() {
             "sdist_with_path_dep-0.1.0/src/lib.rs",
             "sdist_with_path_dep-0.1.0/PKG-INFO",
         ],
-        None,
+        &[],
         "sdist-lib-with-path-dep",
     ))
 }
@@ -458,7 +458,7 @@ fn pyo3_mixed_src_layout_sdist() {
             "pyo3_mixed_src-2.1.3/rust/src/lib.rs",
             "pyo3_mixed_src-2.1.3/PKG-INFO",
         ],
-        None,
+        &[],
         "sdist-pyo3-mixed-src-layout",
     ))
 }
@@ -477,7 +477,7 @@ fn workspace_with_path_dep_sdist() {
             "workspace_with_path_dep-0.1.0/src/lib.rs",
             "workspace_with_path_dep-0.1.0/PKG-INFO",
         ],
-        None,
+        &[],
         "sdist-workspace-with-path-dep",
     ))
 }
@@ -495,12 +495,104 @@ fn workspace_inheritance_sdist() {
             "workspace_inheritance-0.1.0/src/lib.rs",
             "workspace_inheritance-0.1.0/PKG-INFO",
         ],
-        None,
+        &[],
         "sdist-workspace-inheritance",
     ))
 }
 
+#[test]
+fn inspect_pyo3_mixed() {
+    handle_result(other::test_inspect_stdout("test-crates/pyo3-mixed"));
+}
+
+#[test]
+fn inspect_hello_world() {
+    handle_result(other::test_inspect_stdout("test-crates/hello-world"));
+}
+
 #[test]
 fn abi3_python_interpreter_args() {
     handle_result(other::abi3_python_interpreter_args());
 }
+
+#[test]
+fn pyo3_mixed_extension_modules_sdist() {
+    let cargo_toml = indoc!(
+        r#"
+        [package]
+        name = "pyo3-mixed-extension-modules"
+        version = "0.1.0"
+        description = "A mixed python/rust project with native modules from other workspace members"
+        edition = "2018"
+
+        [dependencies]
+        pyo3 = { version = "0.17.3", features = ["extension-module"] }
+
+        [features]
+        # Only the main crate has this feature
+        fast = []
+
+        [lib]
+        name = "pyo3_mixed_extension_modules"
+        crate-type = ["cdylib"]
+
+        [workspace]
+        members = ["local_dependencies/pyo3-mixed-simd", "local_dependencies/pyo3-mixed-codec"]
+        "#
+    );
+    let simd_cargo_toml = indoc!(
+        r#"
+        [package]
+        name = "pyo3-mixed-simd"
+        version = "0.1.0"
+        edition = "2018"
+        workspace = "../.."
+
+        [dependencies]
+        pyo3 = { version = "0.17.3", features = ["extension-module"] }
+
+        [lib]
+        name = "_simd"
+        crate-type = ["cdylib"]
+        "#
+    );
+    handle_result(other::test_source_distribution(
+        "test-crates/pyo3-mixed-extension-modules",
+        vec![
+            "pyo3_mixed_extension_modules-0.1.0/local_dependencies/pyo3-mixed-codec/Cargo.toml",
+            "pyo3_mixed_extension_modules-0.1.0/local_dependencies/pyo3-mixed-codec/src/lib.rs",
+            "pyo3_mixed_extension_modules-0.1.0/local_dependencies/pyo3-mixed-simd/Cargo.toml",
+            "pyo3_mixed_extension_modules-0.1.0/local_dependencies/pyo3-mixed-simd/src/lib.rs",
+            "pyo3_mixed_extension_modules-0.1.0/pyo3_mixed_extension_modules/__init__.py",
+            "pyo3_mixed_extension_modules-0.1.0/pyo3_mixed_extension_modules/codec/__init__.py",
+            "pyo3_mixed_extension_modules-0.1.0/Cargo.toml",
+            "pyo3_mixed_extension_modules-0.1.0/Cargo.lock",
+            "pyo3_mixed_extension_modules-0.1.0/pyproject.toml",
+            "pyo3_mixed_extension_modules-0.1.0/src/lib.rs",
+            "pyo3_mixed_extension_modules-0.1.0/PKG-INFO",
+        ],
+        &[
+            (
+                Path::new("pyo3_mixed_extension_modules-0.1.0/Cargo.toml"),
+                cargo_toml,
+            ),
+            (
+                Path::new(
+                    "pyo3_mixed_extension_modules-0.1.0/local_dependencies/pyo3-mixed-simd/Cargo.toml",
+                ),
+                simd_cargo_toml,
+            ),
+        ],
+        "sdist-pyo3-mixed-extension-modules",
+    ))
+}
+
+#[test]
+fn pyo3_mixed_extension_modules() {
+    handle_result(other::test_extension_modules())
+}
+
+#[test]
+fn pyo3_lib_with_bin_include_bins() {
+    handle_result(other::test_include_bins())
+}